@@ -3,7 +3,8 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use std::error::Error;
-use std::path::Path;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 fn git_sha() -> Result<String, String> {
@@ -20,6 +21,86 @@ fn git_sha() -> Result<String, String> {
     }
 }
 
+/// Rerun the build script when a commit is made or checked out, as printing any
+/// `rerun-if-changed` turns off rerunning it on every change, which `GIT_SHA` relies on.
+fn rerun_if_git_head_changed() {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|output| output.trim().to_owned())
+    };
+    let Some(git_dir) = git(&["rev-parse", "--git-dir"]) else {
+        return;
+    };
+    let git_dir = Path::new(&git_dir);
+    // Commits change the branch that HEAD points to, which may only be in `packed-refs`. Files
+    // that don't exist are left out, as Cargo would rerun the build script every time.
+    let head_ref = git(&["symbolic-ref", "-q", "HEAD"]);
+    let watched = [Some("HEAD"), head_ref.as_deref(), Some("packed-refs")];
+    for path in watched.into_iter().flatten().map(|path| git_dir.join(path)) {
+        if path.exists() {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
+
+/// Files in the `resources` directory that are only used to package the browser, and so aren't
+/// compiled in. The large `hsts_preload.fstmap` and `resource_protocol/license.html` are, as
+/// Servo reads the former at startup and the latter is shown at `resource:///license.html`.
+const NOT_EMBEDDED: &[&str] = &[
+    "Credits.rtf.mako",
+    "prism.svg",
+    "prism_256.png",
+    "prism_64.png",
+];
+
+/// All files below `dir`, as paths relative to `base`, sorted so that the output is stable.
+fn resource_files(base: &Path, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(resource_files(base, &path)?);
+        } else {
+            files.push(path.strip_prefix(base)?.to_owned());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Write a list of all files in the `resources` directory with their contents, which
+/// `src/resources.rs` includes so that the executable doesn't need the directory at runtime.
+fn embed_resources(out: &Path) -> Result<(), Box<dyn Error>> {
+    let resources_dir = Path::new(&std::env::var("CARGO_MANIFEST_DIR")?).join("resources");
+    println!("cargo:rerun-if-changed={}", resources_dir.display());
+
+    let mut list = String::from("&[\n");
+    for file in resource_files(&resources_dir, &resources_dir)? {
+        // Resources are looked up with `/` separators on every platform.
+        let name = file
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if NOT_EMBEDDED.contains(&name.as_str()) {
+            continue;
+        }
+        writeln!(
+            list,
+            "    ({name:?}, include_bytes!({:?})),",
+            resources_dir.join(&file)
+        )?;
+    }
+    list.push(']');
+    std::fs::write(out.join("embedded_resources.rs"), list)?;
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     println!("cargo::rustc-check-cfg=cfg(servo_production)");
     println!("cargo::rustc-check-cfg=cfg(servo_do_not_use_in_production)");
@@ -27,6 +108,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     // but we can extract it from OUT_DIR and set a custom cfg() ourselves.
     let out = std::env::var("OUT_DIR")?;
     let out = Path::new(&out);
+    embed_resources(out)?;
     let krate = out.parent().unwrap();
     let build = krate.parent().unwrap();
     let profile = build
@@ -62,6 +144,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             .compile("count_threads");
     }
 
+    rerun_if_git_head_changed();
     match git_sha() {
         Ok(hash) => println!("cargo:rustc-env=GIT_SHA={hash}"),
         Err(error) => {
@@ -0,0 +1,74 @@
+// Shared by the internal pages. The browser writes a token into each page, which the JSON
+// bridge at prism:api/ requires with every call.
+const TOKEN = document.querySelector('meta[name="prism-token"]').content;
+
+// Call an endpoint of the JSON bridge with the given parameters, returning the parsed JSON.
+async function prismApi(endpoint, parameters = {}) {
+  const query = new URLSearchParams({ ...parameters, token: TOKEN });
+  return apiResponse(endpoint, await fetch(`prism:api/${endpoint}?${query}`));
+}
+
+// Call an endpoint of the JSON bridge that changes the browser data, posting the given
+// parameters as JSON, and return the parsed JSON.
+async function prismPost(endpoint, parameters = {}) {
+  const query = new URLSearchParams({ token: TOKEN });
+  const response = await fetch(`prism:api/${endpoint}?${query}`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify(parameters),
+  });
+  return apiResponse(endpoint, response);
+}
+
+async function apiResponse(endpoint, response) {
+  if (!response.ok) {
+    throw new Error(`${endpoint} failed`);
+  }
+  return response.json();
+}
+
+// Create an element with the given class and text.
+function element(tag, className, text) {
+  const el = document.createElement(tag);
+  if (className) el.className = className;
+  if (text !== undefined) el.textContent = text;
+  return el;
+}
+
+// The schemes of the URLs that the pages link to. Others, such as `javascript:`, could run in
+// the page.
+const LINK_SCHEMES = ['http:', 'https:', 'file:'];
+
+// A link to `url` that shows `title`, or the URL itself if there is no title. URLs with other
+// schemes are shown without a link.
+function pageLink(title, url) {
+  const link = element('a', 'page-link');
+  let scheme = null;
+  try {
+    scheme = new URL(url).protocol;
+  } catch {}
+  if (LINK_SCHEMES.includes(scheme)) {
+    link.href = url;
+  }
+  link.appendChild(element('span', 'page-title', title || url));
+  link.appendChild(element('span', 'page-url', url));
+  return link;
+}
+
+function formatTime(time) {
+  return new Date(time).toLocaleString();
+}
+
+// Show the search field of a page, calling `search` with its text as it is typed.
+function onSearch(search) {
+  const field = document.getElementById('search');
+  let timeout = null;
+  field.addEventListener('input', () => {
+    clearTimeout(timeout);
+    timeout = setTimeout(() => search(field.value.trim()), 150);
+  });
+}
+
+function showEmpty(list, text) {
+  list.replaceChildren(element('p', 'empty', text));
+}
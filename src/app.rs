@@ -6,9 +6,10 @@
 
 use log::warn;
 use servo::protocol_handler::ProtocolRegistry;
-use servo::{EventLoopWaker, Preferences, ServoBuilder, ServoUrl};
+use servo::{EventLoopWaker, InputEvent, Preferences, ServoBuilder, ServoUrl};
+use std::env;
 use std::rc::Rc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use url::Url;
 use winit::application::ApplicationHandler;
 use winit::event::WindowEvent;
@@ -18,9 +19,14 @@ use winit::window::WindowId;
 use super::browser_window::{self, BrowserWindow};
 use super::event_loop::{AppEvent, HeadedEventLoopWaker};
 use super::resource_protocol::ResourceProtocolHandler;
+use crate::data_storage::search_engines::default_search_engine;
+use crate::misc_utils::reader_mode::{open_article, original_url};
 use crate::panic_utils::tracing::trace_winit_event;
 use crate::parser::location_bar_input_to_url;
 use crate::prefs::ServoShellPreferences;
+use crate::prism_protocol::{
+    BOOKMARKS_PAGE_URL, DOWNLOADS_PAGE_URL, HISTORY_PAGE_URL, PRISM_SCHEME, PrismProtocolHandler,
+};
 use crate::running_app_state::{RunningAppState, UserInterfaceCommand};
 use crate::{NEW_TAB_PAGE_URL, prefs};
 
@@ -64,6 +70,7 @@ impl App {
     pub fn init(&mut self, active_event_loop: &ActiveEventLoop) {
         let mut protocol_registry = ProtocolRegistry::default();
         let _ = protocol_registry.register("resource", ResourceProtocolHandler::default());
+        let _ = protocol_registry.register(PRISM_SCHEME, PrismProtocolHandler::default());
 
         let servo_builder = ServoBuilder::default()
             .opts(prefs::get_opts())
@@ -108,7 +115,15 @@ impl App {
             self.handle_interface_commands_for_window(active_event_loop, state, window, commands);
         });
 
-        if !state.spin_event_loop() {
+        let keep_running = state.spin_event_loop();
+
+        // Key bindings that the page may override only produce their commands once Servo has
+        // told us that the page did not handle the key event, which happens while spinning.
+        state.foreach_window_and_interface_commands(|window, commands| {
+            self.handle_interface_commands_for_window(active_event_loop, state, window, commands);
+        });
+
+        if !keep_running {
             self.state = AppState::ShuttingDown;
             return false;
         }
@@ -127,15 +142,14 @@ impl App {
             match event {
                 UserInterfaceCommand::Go(location) => {
                     window.set_needs_update();
-                    let Some(url) = location_bar_input_to_url(
-                        &location.clone(),
-                        &state.servoshell_preferences.searchpage,
-                    ) else {
+                    let Some(url) = location_bar_input_to_url(&location, &state.search_engines())
+                    else {
                         warn!("failed to parse location");
                         break;
                     };
+                    let url = state.url_cleaner().clean_or_keep(url.into_url());
                     if let Some(active_webview) = window.active_webview() {
-                        active_webview.load(url.into_url());
+                        active_webview.load(url);
                     }
                 }
                 UserInterfaceCommand::Back => {
@@ -163,6 +177,211 @@ impl App {
                     window.set_needs_update();
                     window.close_webview(id);
                 }
+                UserInterfaceCommand::Screenshot(area, target) => {
+                    if let Some(active_webview) = window.active_webview() {
+                        state.take_screenshot(&active_webview, area, target);
+                    }
+                }
+                UserInterfaceCommand::SavePage(mode) => {
+                    if let Some(active_webview) = window.active_webview() {
+                        state.save_page(&active_webview, mode);
+                    }
+                }
+                UserInterfaceCommand::OpenInNewWebView(url) => {
+                    window.set_needs_update();
+                    window.create_and_activate_toplevel_webview(state.clone(), url);
+                }
+                UserInterfaceCommand::SearchInNewWebView(query) => {
+                    let Some(url) = default_search_engine(&state.search_engines())
+                        .and_then(|search_engine| search_engine.search_url_for(&query))
+                    else {
+                        warn!("failed to create search url");
+                        continue;
+                    };
+                    window.set_needs_update();
+                    window.create_and_activate_toplevel_webview(state.clone(), url.into_url());
+                }
+                UserInterfaceCommand::CopyToClipboard(text) => {
+                    window.copy_text_to_clipboard(text);
+                }
+                UserInterfaceCommand::CopyCleanLink(url) => {
+                    let Some(url) = url.or_else(|| window.active_webview()?.url()) else {
+                        continue;
+                    };
+                    let url = state.url_cleaner().clean_or_keep(url);
+                    window.copy_text_to_clipboard(url.into());
+                }
+                UserInterfaceCommand::AddBookmark { title, url } => {
+                    state.add_bookmark(title, url);
+                }
+                UserInterfaceCommand::InstallSearchEngine(url) => {
+                    if let Some(active_webview) = window.active_webview() {
+                        state.install_search_engine(&active_webview, url);
+                    }
+                }
+                UserInterfaceCommand::ToggleReaderMode => {
+                    let Some(active_webview) = window.active_webview() else {
+                        continue;
+                    };
+                    if let Some(url) = active_webview.url().and_then(|url| original_url(&url)) {
+                        active_webview.load(url);
+                    } else if let Some(article) = window.active_reader_article() {
+                        active_webview.load(open_article(article));
+                    }
+                }
+                UserInterfaceCommand::InstallUserScript { source, url } => {
+                    let Some(active_webview) = window.active_webview() else {
+                        continue;
+                    };
+                    let (title, message) = match state.install_user_script(source, url) {
+                        Ok(name) => (
+                            "User script installed".into(),
+                            format!("{name} can be edited or turned off in Settings."),
+                        ),
+                        Err(error) => ("Could not install user script".into(), error),
+                    };
+                    window.show_message_dialog(active_webview.id(), title, message);
+                }
+                UserInterfaceCommand::SaveResource(url) => {
+                    if let Some(active_webview) = window.active_webview() {
+                        state.save_resource(&active_webview, url);
+                    }
+                }
+                UserInterfaceCommand::Inspect => {
+                    let Some(active_webview) = window.active_webview() else {
+                        continue;
+                    };
+                    let message = match state.devtools_port() {
+                        Some(port) => format!(
+                            "Open about:debugging in Firefox and connect to localhost:{port} to inspect this page."
+                        ),
+                        None => "The developer tools server is not running. Enable the devtools_server_enabled preference to inspect pages.".into(),
+                    };
+                    window.show_message_dialog(active_webview.id(), "Inspect".into(), message);
+                }
+                UserInterfaceCommand::CloseActiveWebView => {
+                    if let Some(active_webview) = window.active_webview() {
+                        window.set_needs_update();
+                        window.close_webview(active_webview.id());
+                    }
+                }
+                UserInterfaceCommand::ActivateWebView(id) => {
+                    if window.webview_by_id(id).is_some() {
+                        window.activate_webview(id);
+                    }
+                }
+                UserInterfaceCommand::ActivateWebViewByIndex(index) => {
+                    if index < window.webviews().len() {
+                        window.activate_webview_by_index(index);
+                    }
+                }
+                UserInterfaceCommand::ActivateLastWebView => {
+                    let len = window.webviews().len();
+                    if len > 0 {
+                        window.activate_webview_by_index(len - 1);
+                    }
+                }
+                UserInterfaceCommand::ActivateNextWebView => {
+                    if let Some(index) = window.get_active_webview_index() {
+                        window.activate_webview_by_index((index + 1) % window.webviews().len());
+                    }
+                }
+                UserInterfaceCommand::ActivatePreviousWebView => {
+                    if let Some(index) = window.get_active_webview_index() {
+                        let len = window.webviews().len();
+                        window.activate_webview_by_index((index + len - 1) % len);
+                    }
+                }
+                UserInterfaceCommand::EditingAction(action) => {
+                    if let Some(active_webview) = window.active_webview() {
+                        active_webview.notify_input_event(InputEvent::EditingAction(action));
+                    }
+                }
+                UserInterfaceCommand::ZoomIn => {
+                    if let Some(active_webview) = window.active_webview() {
+                        active_webview.set_page_zoom(active_webview.page_zoom() + 0.1);
+                    }
+                }
+                UserInterfaceCommand::ZoomOut => {
+                    if let Some(active_webview) = window.active_webview() {
+                        active_webview.set_page_zoom(active_webview.page_zoom() - 0.1);
+                    }
+                }
+                UserInterfaceCommand::ResetZoom => {
+                    if let Some(active_webview) = window.active_webview() {
+                        active_webview.set_page_zoom(1.0);
+                    }
+                }
+                UserInterfaceCommand::ExitFullscreen => {
+                    if let Some(active_webview) = window.active_webview() {
+                        active_webview.exit_fullscreen();
+                    }
+                }
+                UserInterfaceCommand::ToggleFullscreen => {
+                    window.set_fullscreen(!window.get_fullscreen());
+                }
+                UserInterfaceCommand::OpenCommandPalette => window.open_command_palette(),
+                UserInterfaceCommand::ShowHistory => {
+                    window.show_internal_page(state.clone(), HISTORY_PAGE_URL);
+                }
+                UserInterfaceCommand::ShowBookmarks => {
+                    window.show_internal_page(state.clone(), BOOKMARKS_PAGE_URL);
+                }
+                UserInterfaceCommand::ShowDownloads => {
+                    window.show_internal_page(state.clone(), DOWNLOADS_PAGE_URL);
+                }
+                UserInterfaceCommand::ShowSettings => window.show_settings_page(),
+                UserInterfaceCommand::ConfirmClearHistory => {
+                    if let Some(active_webview) = window.active_webview() {
+                        window.show_question_dialog(
+                            active_webview.id(),
+                            "Clear browsing history?".into(),
+                            "Every page you have visited will be removed from your history. \
+                             This can't be undone."
+                                .into(),
+                            "Clear".into(),
+                            UserInterfaceCommand::ClearHistory,
+                        );
+                    }
+                }
+                UserInterfaceCommand::ClearHistory => {
+                    state.clear_browser_history();
+                    if let Some(active_webview) = window.active_webview() {
+                        window.show_message_dialog(
+                            active_webview.id(),
+                            "Clear browsing history".into(),
+                            "Your browsing history has been cleared.".into(),
+                        );
+                    }
+                }
+                UserInterfaceCommand::ToggleSamplingProfiler => {
+                    let Some(active_webview) = window.active_webview() else {
+                        continue;
+                    };
+                    let rate = env::var("SAMPLING_RATE")
+                        .ok()
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(10);
+                    let duration = env::var("SAMPLING_DURATION")
+                        .ok()
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(10);
+                    active_webview.toggle_sampling_profiler(
+                        Duration::from_millis(rate),
+                        Duration::from_secs(duration),
+                    );
+                }
+                UserInterfaceCommand::CaptureWebRender => {
+                    if let Some(active_webview) = window.active_webview() {
+                        active_webview.capture_webrender();
+                    }
+                }
+                UserInterfaceCommand::ToggleWebRenderDebugging(option) => {
+                    if let Some(active_webview) = window.active_webview() {
+                        active_webview.toggle_webrender_debugging(option);
+                    }
+                }
+                UserInterfaceCommand::Quit => state.schedule_exit(),
             }
         }
     }
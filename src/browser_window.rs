@@ -9,23 +9,21 @@
 
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::env;
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::time::Duration;
 
 use euclid::{Length, Point2D, Rect, Scale, Size2D};
-use keyboard_types::ShortcutMatcher;
 use log::{debug, info};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
 use servo::{
     AuthenticationRequest, Cursor, DeviceIndependentPixel, DeviceIntPoint, DeviceIntRect,
     DeviceIntSize, DevicePixel, DevicePoint, EmbedderControl, EmbedderControlId, GenericSender,
-    ImeEvent, InputEvent, InputEventId, InputEventResult, InputMethodControl, Key, KeyboardEvent,
-    MediaSessionEvent, Modifiers, MouseButton as ServoMouseButton, MouseButtonAction,
-    MouseButtonEvent, MouseLeftViewportEvent, MouseMoveEvent, NamedKey, OffscreenRenderingContext,
+    ImeEvent, InputEvent, InputEventId, InputEventResult, InputMethodControl, JSValue,
+    KeyboardEvent, MediaSessionEvent, MouseButton as ServoMouseButton, MouseButtonAction,
+    MouseButtonEvent, MouseLeftViewportEvent, MouseMoveEvent, OffscreenRenderingContext,
     PermissionRequest, RenderingContext, ScreenGeometry, Theme, TouchEvent, TouchEventType,
-    TouchId, WebRenderDebugOption, WebView, WebViewBuilder, WebViewId, WheelDelta, WheelEvent,
-    WheelMode, WindowRenderingContext,
+    TouchId, WebView, WebViewBuilder, WebViewId, WheelDelta, WheelEvent, WheelMode,
+    WindowRenderingContext,
 };
 use url::Url;
 use winit::dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
@@ -45,15 +43,17 @@ use {
 };
 
 use super::geometry::{winit_position_to_euclid_point, winit_size_to_euclid_size};
-use super::keyutils::{CMD_OR_ALT, keyboard_event_from_winit};
-use crate::NEW_TAB_PAGE_URL;
+use super::keyutils::keyboard_event_from_winit;
 use crate::dialog::Dialog;
 use crate::event_loop::AppEvent;
-use crate::keyutils::CMD_OR_CONTROL;
+use crate::keymap::{BrowserAction, Keymap};
 use crate::misc_utils::accelerated_gl_media::setup_gl_accelerated_media;
+use crate::misc_utils::opensearch::AdvertisedSearchEngine;
+use crate::misc_utils::reader_mode::{Article, ArticleFinder};
 #[cfg(feature = "webxr")]
 use crate::misc_utils::webxr::XRWindowPose;
 use crate::running_app_state::{RunningAppState, UserInterfaceCommand, WebViewCollection};
+use crate::user_interface::context_menu::ContextMenuContext;
 use crate::user_interface::gui::Gui;
 
 // This should vary by zoom level and maybe actual text size (focused or under cursor)
@@ -123,6 +123,20 @@ pub struct BrowserWindow {
     /// List of webviews that have favicon textures which are not yet uploaded
     /// to the GPU by egui.
     pending_favicon_loads: RefCell<Vec<WebViewId>>,
+    /// The search engines advertised by the page loaded in each [`WebView`], which are offered
+    /// to the user for installation.
+    advertised_search_engines: RefCell<HashMap<WebViewId, Vec<AdvertisedSearchEngine>>>,
+
+    /// How many requests the content blocker has blocked for the page loaded in each
+    /// [`WebView`].
+    blocked_request_counts: RefCell<HashMap<WebViewId, usize>>,
+
+    /// The article found in the page loaded in each [`WebView`], which can be read in reader
+    /// mode.
+    reader_articles: RefCell<HashMap<WebViewId, Article>>,
+
+    /// Finds the articles in pages on a worker thread.
+    article_finder: ArticleFinder,
 }
 
 impl BrowserWindow {
@@ -195,6 +209,7 @@ impl BrowserWindow {
             .expect("Could not make window RenderingContext current");
 
         let rendering_context = Rc::new(window_rendering_context.offscreen_context(inner_size));
+        let article_finder = ArticleFinder::new(event_loop_proxy.clone(), winit_window.id());
         let gui = RefCell::new(Gui::new(
             &winit_window,
             event_loop,
@@ -225,6 +240,10 @@ impl BrowserWindow {
             needs_update: Default::default(),
             needs_repaint: Default::default(),
             pending_favicon_loads: Default::default(),
+            advertised_search_engines: Default::default(),
+            blocked_request_counts: Default::default(),
+            reader_articles: Default::default(),
+            article_finder,
         })
     }
 
@@ -319,120 +338,19 @@ impl BrowserWindow {
         state: Rc<RunningAppState>,
         key_event: &KeyboardEvent,
     ) -> bool {
-        let Some(active_webview) = self.active_webview() else {
+        if self.active_webview().is_none() {
+            return false;
+        }
+        // Exiting fullscreen only applies while we are fullscreen, otherwise its shortcut is left
+        // to any other binding for it, or to the page.
+        let fullscreen = self.get_fullscreen();
+        let keymap = state.keymap();
+        let enabled = |action: BrowserAction| fullscreen || action != BrowserAction::ExitFullscreen;
+        let Some(action) = keymap.enabled_action_for_event(&key_event.event, false, enabled) else {
             return false;
         };
-
-        let mut handled = true;
-        ShortcutMatcher::from_event(key_event.event.clone())
-            .shortcut(CMD_OR_CONTROL, 'R', || active_webview.reload())
-            .shortcut(CMD_OR_CONTROL, 'W', || {
-                self.close_webview(active_webview.id());
-            })
-            .shortcut(CMD_OR_CONTROL, 'P', || {
-                let rate = env::var("SAMPLING_RATE")
-                    .ok()
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(10);
-                let duration = env::var("SAMPLING_DURATION")
-                    .ok()
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(10);
-                active_webview.toggle_sampling_profiler(
-                    Duration::from_millis(rate),
-                    Duration::from_secs(duration),
-                );
-            })
-            .shortcut(CMD_OR_CONTROL, 'X', || {
-                active_webview
-                    .notify_input_event(InputEvent::EditingAction(servo::EditingActionEvent::Cut));
-            })
-            .shortcut(CMD_OR_CONTROL, 'C', || {
-                active_webview
-                    .notify_input_event(InputEvent::EditingAction(servo::EditingActionEvent::Copy));
-            })
-            .shortcut(CMD_OR_CONTROL, 'V', || {
-                active_webview.notify_input_event(InputEvent::EditingAction(
-                    servo::EditingActionEvent::Paste,
-                ));
-            })
-            .shortcut(Modifiers::CONTROL, Key::Named(NamedKey::F9), || {
-                active_webview.capture_webrender();
-            })
-            .shortcut(Modifiers::CONTROL, Key::Named(NamedKey::F10), || {
-                active_webview.toggle_webrender_debugging(WebRenderDebugOption::RenderTargetDebug);
-            })
-            .shortcut(Modifiers::CONTROL, Key::Named(NamedKey::F11), || {
-                active_webview.toggle_webrender_debugging(WebRenderDebugOption::TextureCacheDebug);
-            })
-            .shortcut(Modifiers::CONTROL, Key::Named(NamedKey::F12), || {
-                active_webview.toggle_webrender_debugging(WebRenderDebugOption::Profiler);
-            })
-            .shortcut(CMD_OR_ALT, Key::Named(NamedKey::ArrowRight), || {
-                active_webview.go_forward(1);
-            })
-            .optional_shortcut(
-                cfg!(not(target_os = "windows")),
-                CMD_OR_CONTROL,
-                ']',
-                || {
-                    active_webview.go_forward(1);
-                },
-            )
-            .shortcut(CMD_OR_ALT, Key::Named(NamedKey::ArrowLeft), || {
-                active_webview.go_back(1);
-            })
-            .optional_shortcut(
-                cfg!(not(target_os = "windows")),
-                CMD_OR_CONTROL,
-                '[',
-                || {
-                    active_webview.go_back(1);
-                },
-            )
-            .optional_shortcut(
-                self.get_fullscreen(),
-                Modifiers::empty(),
-                Key::Named(NamedKey::Escape),
-                || active_webview.exit_fullscreen(),
-            )
-            // Select the first 8 tabs via shortcuts
-            .shortcut(CMD_OR_CONTROL, '1', || self.activate_webview_by_index(0))
-            .shortcut(CMD_OR_CONTROL, '2', || self.activate_webview_by_index(1))
-            .shortcut(CMD_OR_CONTROL, '3', || self.activate_webview_by_index(2))
-            .shortcut(CMD_OR_CONTROL, '4', || self.activate_webview_by_index(3))
-            .shortcut(CMD_OR_CONTROL, '5', || self.activate_webview_by_index(4))
-            .shortcut(CMD_OR_CONTROL, '6', || self.activate_webview_by_index(5))
-            .shortcut(CMD_OR_CONTROL, '7', || self.activate_webview_by_index(6))
-            .shortcut(CMD_OR_CONTROL, '8', || self.activate_webview_by_index(7))
-            // Cmd/Ctrl 9 is a bit different in that it focuses the last tab instead of the 9th
-            .shortcut(CMD_OR_CONTROL, '9', || {
-                let len = self.webviews().len();
-                if len > 0 {
-                    self.activate_webview_by_index(len - 1)
-                }
-            })
-            .shortcut(Modifiers::CONTROL, Key::Named(NamedKey::PageDown), || {
-                if let Some(index) = self.get_active_webview_index() {
-                    self.activate_webview_by_index((index + 1) % self.webviews().len())
-                }
-            })
-            .shortcut(Modifiers::CONTROL, Key::Named(NamedKey::PageUp), || {
-                if let Some(index) = self.get_active_webview_index() {
-                    let len = self.webviews().len();
-                    self.activate_webview_by_index((index + len - 1) % len);
-                }
-            })
-            .shortcut(CMD_OR_CONTROL, 'T', || {
-                self.create_and_activate_toplevel_webview(
-                    state.clone(),
-                    Url::parse(NEW_TAB_PAGE_URL)
-                        .expect("Should be able to unconditionally parse new tab url as URL"),
-                );
-            })
-            .shortcut(CMD_OR_CONTROL, 'Q', || state.schedule_exit())
-            .otherwise(|| handled = false);
-        handled
+        self.queue_user_interface_command(action.user_interface_command());
+        true
     }
 
     #[cfg_attr(not(target_os = "macos"), expect(unused_variables))]
@@ -466,7 +384,7 @@ impl BrowserWindow {
         );
     }
 
-    pub(crate) fn for_each_active_dialog(&self, callback: impl Fn(&mut Dialog) -> bool) {
+    pub(crate) fn for_each_active_dialog(&self, callback: impl FnMut(&mut Dialog) -> bool) {
         let Some(active_webview) = self.active_webview() else {
             return;
         };
@@ -639,6 +557,11 @@ impl BrowserWindow {
             return;
         }
         self.dismiss_embedder_controls_for_webview(webview_id);
+        self.advertised_search_engines
+            .borrow_mut()
+            .remove(&webview_id);
+        self.blocked_request_counts.borrow_mut().remove(&webview_id);
+        self.reader_articles.borrow_mut().remove(&webview_id);
 
         self.set_needs_update();
         self.set_needs_repaint();
@@ -664,6 +587,90 @@ impl BrowserWindow {
     pub(crate) fn take_pending_favicon_loads(&self) -> Vec<WebViewId> {
         std::mem::take(&mut *self.pending_favicon_loads.borrow_mut())
     }
+
+    pub(crate) fn set_advertised_search_engines(
+        &self,
+        webview_id: WebViewId,
+        search_engines: Vec<AdvertisedSearchEngine>,
+    ) {
+        self.advertised_search_engines
+            .borrow_mut()
+            .insert(webview_id, search_engines);
+        self.set_needs_update();
+    }
+
+    /// Start counting the blocked requests again, for a new page.
+    pub(crate) fn reset_blocked_request_count(&self, webview_id: WebViewId) {
+        self.blocked_request_counts.borrow_mut().remove(&webview_id);
+        self.set_needs_update();
+    }
+
+    pub(crate) fn count_blocked_request(&self, webview_id: WebViewId) {
+        *self
+            .blocked_request_counts
+            .borrow_mut()
+            .entry(webview_id)
+            .or_default() += 1;
+        self.set_needs_update();
+    }
+
+    /// How many requests have been blocked for the page in the active [`WebView`].
+    pub(crate) fn active_blocked_request_count(&self) -> usize {
+        let Some(active_id) = self.webview_collection.borrow().active_id() else {
+            return 0;
+        };
+        self.blocked_request_counts
+            .borrow()
+            .get(&active_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_reader_article(&self, webview_id: WebViewId, article: Option<Article>) {
+        let mut reader_articles = self.reader_articles.borrow_mut();
+        match article {
+            Some(article) => reader_articles.insert(webview_id, article),
+            None => reader_articles.remove(&webview_id),
+        };
+        self.set_needs_update();
+    }
+
+    /// Start looking for the article in the page shown in the given [`WebView`].
+    pub(crate) fn find_reader_article(&self, webview: &WebView) {
+        self.article_finder.find_article(webview);
+    }
+
+    /// Keep the articles that have been found, unless their [`WebView`] has moved on to
+    /// another page since.
+    fn receive_reader_articles(&self) {
+        for result in self.article_finder.results() {
+            let is_shown = self
+                .webview_by_id(result.webview_id)
+                .and_then(|webview| webview.url())
+                .is_some_and(|url| url == result.url);
+            if is_shown {
+                self.set_reader_article(result.webview_id, result.article);
+            }
+        }
+    }
+
+    /// The article in the page in the active [`WebView`], if it has one.
+    pub(crate) fn active_reader_article(&self) -> Option<Article> {
+        let active_id = self.webview_collection.borrow().active_id()?;
+        self.reader_articles.borrow().get(&active_id).cloned()
+    }
+
+    /// The search engines advertised by the page in the active [`WebView`].
+    pub(crate) fn active_advertised_search_engines(&self) -> Vec<AdvertisedSearchEngine> {
+        let Some(active_id) = self.webview_collection.borrow().active_id() else {
+            return vec![];
+        };
+        self.advertised_search_engines
+            .borrow()
+            .get(&active_id)
+            .cloned()
+            .unwrap_or_default()
+    }
 }
 
 impl BrowserWindow {
@@ -923,6 +930,9 @@ impl BrowserWindow {
         } else if let AppEvent::UpdateTheme { theme, .. } = app_event {
             self.winit_window.set_theme(Some(theme));
             self.gui.borrow_mut().notify_new_theme(theme);
+        } else if let AppEvent::RedrawWindow { .. } = app_event {
+            self.receive_reader_articles();
+            self.winit_window.request_redraw();
         }
     }
 
@@ -1000,7 +1010,7 @@ impl BrowserWindow {
         self.fullscreen.set(state);
     }
 
-    fn get_fullscreen(&self) -> bool {
+    pub(crate) fn get_fullscreen(&self) -> bool {
         self.fullscreen.get()
     }
 
@@ -1096,6 +1106,7 @@ impl BrowserWindow {
     /// Handle servoshell key bindings that may have been prevented by the page in the active webview.
     pub(crate) fn notify_input_event_handled(
         &self,
+        keymap: &Keymap,
         webview: &WebView,
         id: InputEventId,
         result: InputEventResult,
@@ -1107,19 +1118,15 @@ impl BrowserWindow {
             return;
         }
 
-        ShortcutMatcher::from_event(keyboard_event.event)
-            .shortcut(CMD_OR_CONTROL, '=', || {
-                webview.set_page_zoom(webview.page_zoom() + 0.1);
-            })
-            .shortcut(CMD_OR_CONTROL, '+', || {
-                webview.set_page_zoom(webview.page_zoom() + 0.1);
-            })
-            .shortcut(CMD_OR_CONTROL, '-', || {
-                webview.set_page_zoom(webview.page_zoom() - 0.1);
-            })
-            .shortcut(CMD_OR_CONTROL, '0', || {
-                webview.set_page_zoom(1.0);
-            });
+        // The event was sent to this webview, which need not be the active one by now, so the
+        // zoom actions are applied to it rather than queued.
+        match keymap.action_for_event(&keyboard_event.event, true) {
+            Some(BrowserAction::ZoomIn) => webview.set_page_zoom(webview.page_zoom() + 0.1),
+            Some(BrowserAction::ZoomOut) => webview.set_page_zoom(webview.page_zoom() - 0.1),
+            Some(BrowserAction::ResetZoom) => webview.set_page_zoom(1.0),
+            Some(action) => self.queue_user_interface_command(action.user_interface_command()),
+            None => {}
+        }
     }
 
     pub(crate) fn focused(&self) -> bool {
@@ -1163,7 +1170,22 @@ impl BrowserWindow {
             }
             EmbedderControl::ContextMenu(prompt) => {
                 let offset = self.gui.borrow().toolbar_height();
-                self.add_dialog(webview_id, Dialog::new_context_menu(prompt, offset));
+                let context = ContextMenuContext::new(&prompt);
+                if let Some(webview) = self.webview_by_id(webview_id) {
+                    let selection = context.selection.clone();
+                    webview.evaluate_javascript(
+                        "window.getSelection().toString()",
+                        move |result| {
+                            if let Ok(JSValue::String(text)) = result {
+                                *selection.borrow_mut() = Some(text);
+                            }
+                        },
+                    );
+                }
+                self.add_dialog(
+                    webview_id,
+                    Dialog::new_context_menu(prompt, context, offset),
+                );
             }
         }
         self.set_needs_update();
@@ -1224,6 +1246,89 @@ impl BrowserWindow {
         );
     }
 
+    /// Ask the user where to save a file produced by the browser for the given [`WebView`].
+    pub(crate) fn show_save_file_dialog(
+        &self,
+        webview_id: WebViewId,
+        default_file_name: &str,
+        on_save: impl FnOnce(PathBuf) + 'static,
+    ) {
+        self.add_dialog(
+            webview_id,
+            Dialog::new_save_file_dialog(default_file_name, on_save),
+        );
+        self.set_needs_update();
+        self.set_needs_repaint();
+    }
+
+    pub(crate) fn show_message_dialog(
+        &self,
+        webview_id: WebViewId,
+        title: String,
+        message: String,
+    ) {
+        self.add_dialog(webview_id, Dialog::new_message_dialog(title, message));
+        self.set_needs_update();
+        self.set_needs_repaint();
+    }
+
+    /// Ask the user a question, queueing the command if they accept.
+    pub(crate) fn show_question_dialog(
+        &self,
+        webview_id: WebViewId,
+        title: String,
+        message: String,
+        accept_label: String,
+        command: UserInterfaceCommand,
+    ) {
+        self.add_dialog(
+            webview_id,
+            Dialog::new_question_dialog(title, message, accept_label, command),
+        );
+        self.set_needs_update();
+        self.set_needs_repaint();
+    }
+
+    pub(crate) fn open_command_palette(&self) {
+        self.gui.borrow_mut().open_command_palette();
+        self.set_needs_update();
+        self.set_needs_repaint();
+    }
+
+    /// Activate the tab showing the internal page with the given URL, opening one if there is
+    /// none.
+    pub(crate) fn show_internal_page(&self, state: Rc<RunningAppState>, url: &str) {
+        let existing_webview = self.webviews().into_iter().find(|(_, webview)| {
+            webview
+                .url()
+                .is_some_and(|webview_url| webview_url.as_str() == url)
+        });
+        match existing_webview {
+            Some((id, _)) => self.activate_webview(id),
+            None => {
+                let url = Url::parse(url).expect("Should always be able to parse");
+                self.create_and_activate_toplevel_webview(state, url);
+            }
+        }
+        self.set_needs_repaint();
+    }
+
+    pub(crate) fn show_settings_page(&self) {
+        self.gui.borrow_mut().show_settings_page();
+        self.set_needs_update();
+        self.set_needs_repaint();
+    }
+
+    pub(crate) fn copy_text_to_clipboard(&self, text: String) {
+        self.gui.borrow_mut().copy_text_to_clipboard(text);
+        self.set_needs_repaint();
+    }
+
+    pub(crate) fn copy_image_to_clipboard(&self, image: egui::ColorImage) {
+        self.gui.borrow_mut().copy_image_to_clipboard(image);
+        self.set_needs_repaint();
+    }
+
     pub(crate) fn dismiss_embedder_controls_for_webview(&self, webview_id: WebViewId) {
         self.dialogs.borrow_mut().remove(&webview_id);
     }
@@ -1231,6 +1336,10 @@ impl BrowserWindow {
     pub(crate) fn take_user_interface_commands(&self) -> Vec<UserInterfaceCommand> {
         self.gui.borrow_mut().take_user_interface_commands()
     }
+
+    pub(crate) fn queue_user_interface_command(&self, command: UserInterfaceCommand) {
+        self.gui.borrow_mut().queue_user_interface_command(command);
+    }
     pub(crate) fn notify_media_session_event(&self, _: MediaSessionEvent) {}
     pub(crate) fn notify_crashed(&self, _: WebView, _reason: String, _backtrace: Option<String>) {}
 }
@@ -0,0 +1,8 @@
+use std::path::PathBuf;
+
+/// A file or directory of certificate authorities to trust, as added in the settings.
+#[derive(Debug)]
+pub struct CaCertificatePath {
+    pub id: i32,
+    pub path: PathBuf,
+}
@@ -0,0 +1,9 @@
+/// A certificate that is accepted for a host even though it couldn't be verified.
+#[derive(Debug)]
+pub struct CertificateException {
+    pub id: i32,
+    pub host: String,
+    /// The SHA-256 fingerprint of the certificate, as colon separated hex.
+    pub fingerprint: String,
+    pub time_added: chrono::NaiveDateTime,
+}
@@ -0,0 +1,7 @@
+/// A site on which content blocking has been turned off.
+#[derive(Debug)]
+pub struct ContentBlockingException {
+    pub id: i32,
+    pub host: String,
+    pub time_added: chrono::NaiveDateTime,
+}
@@ -0,0 +1,7 @@
+/// A site that is never darkened by the forced dark mode.
+#[derive(Debug)]
+pub struct DarkModeException {
+    pub id: i32,
+    pub host: String,
+    pub time_added: chrono::NaiveDateTime,
+}
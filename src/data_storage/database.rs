@@ -35,6 +35,106 @@ pub fn init_db(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
         )",
         (),
     )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS search_engines (
+            id   INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            search_url TEXT NOT NULL,
+            keyword TEXT,
+            icon_url TEXT,
+            suggestions_url TEXT,
+            suggestions_enabled INTEGER NOT NULL DEFAULT 1,
+            is_default INTEGER NOT NULL DEFAULT 0
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pinned_sites (
+            id   INTEGER PRIMARY KEY,
+            title TEXT NOT NULL,
+            url TEXT NOT NULL,
+            position INTEGER NOT NULL
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS certificate_exceptions (
+            id   INTEGER PRIMARY KEY,
+            host TEXT NOT NULL,
+            fingerprint TEXT NOT NULL,
+            certificate BLOB NOT NULL,
+            time_added TEXT NOT NULL,
+            UNIQUE (host, fingerprint)
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS https_exceptions (
+            id   INTEGER PRIMARY KEY,
+            host TEXT NOT NULL UNIQUE,
+            time_added TEXT NOT NULL
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS content_blocking_exceptions (
+            id   INTEGER PRIMARY KEY,
+            host TEXT NOT NULL UNIQUE,
+            time_added TEXT NOT NULL
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS dark_mode_exceptions (
+            id   INTEGER PRIMARY KEY,
+            host TEXT NOT NULL UNIQUE,
+            time_added TEXT NOT NULL
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ca_certificate_paths (
+            id   INTEGER PRIMARY KEY,
+            path TEXT NOT NULL UNIQUE
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS redirect_rules (
+            id   INTEGER PRIMARY KEY,
+            kind TEXT NOT NULL,
+            pattern TEXT NOT NULL,
+            replacement TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS user_scripts (
+            id   INTEGER PRIMARY KEY,
+            source TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            download_url TEXT
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS user_stylesheets (
+            id   INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            url_patterns TEXT NOT NULL,
+            css TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1
+        )",
+        (),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            name TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        (),
+    )?;
     Ok(())
 }
 
@@ -5,3 +5,12 @@ pub struct HistoryEntry {
     pub url: String,
     pub time_accessed: chrono::NaiveDateTime,
 }
+
+/// A page from the browsing history, with all visits to its URL combined.
+#[derive(Debug)]
+pub struct VisitedPage {
+    pub title: String,
+    pub url: String,
+    pub visit_count: u32,
+    pub last_visited: chrono::NaiveDateTime,
+}
@@ -0,0 +1,7 @@
+/// A site that is loaded over HTTP even though HTTPS-only mode is enabled.
+#[derive(Debug)]
+pub struct HttpsException {
+    pub id: i32,
+    pub host: String,
+    pub time_added: chrono::NaiveDateTime,
+}
@@ -1,14 +1,38 @@
+use std::path::{Path, PathBuf};
+
+use bookmarks::BookmarkEntry;
+use ca_certificates::CaCertificatePath;
+use certificate_exceptions::CertificateException;
+use content_blocking_exceptions::ContentBlockingException;
+use dark_mode_exceptions::DarkModeException;
 use database::init_db;
-use history::HistoryEntry;
+use downloads::DownloadEntry;
+use history::{HistoryEntry, VisitedPage};
+use https_exceptions::HttpsException;
+use pinned_sites::PinnedSite;
+use redirect_rules::{RedirectRule, RedirectRuleKind};
+use search_engines::SearchEngine;
 use tabs::OpenTab;
+use user_scripts::UserScript;
+use user_stylesheets::UserStylesheet;
 
 use crate::prefs::default_config_dir;
 
 pub mod bookmarks;
+pub mod ca_certificates;
+pub mod certificate_exceptions;
+pub mod content_blocking_exceptions;
+pub mod dark_mode_exceptions;
 pub mod database;
 pub mod downloads;
 pub mod history;
+pub mod https_exceptions;
+pub mod pinned_sites;
+pub mod redirect_rules;
+pub mod search_engines;
 pub mod tabs;
+pub mod user_scripts;
+pub mod user_stylesheets;
 
 #[derive(Debug)]
 pub struct BrowserDataConnection {
@@ -18,8 +42,13 @@ pub struct BrowserDataConnection {
 impl BrowserDataConnection {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let connection =
-            rusqlite::Connection::open(default_config_dir().join("browser_data.db")).unwrap();
+        Self::open(&default_config_dir().join("browser_data.db"))
+    }
+
+    /// Open the database at the given path. Connections to the same database don't cache
+    /// anything, so each of them sees what the others have written as soon as it is written.
+    pub fn open(path: &Path) -> Self {
+        let connection = rusqlite::Connection::open(path).unwrap();
         init_db(&connection).unwrap();
         Self { connection }
     }
@@ -49,6 +78,574 @@ impl BrowserDataConnection {
             .collect()
     }
 
+    /// Find pages in the history whose title or URL contains `text`, most visited first.
+    pub fn search_browser_history(&self, text: &str, limit: usize) -> Vec<VisitedPage> {
+        // SQLite takes the title from the row with the latest visit, as that is the aggregate.
+        self.connection
+            .prepare(
+                "SELECT title, url, COUNT(*), MAX(time_accessed) FROM browser_history
+                WHERE url LIKE ?1 ESCAPE '\\' OR title LIKE ?1 ESCAPE '\\'
+                GROUP BY url ORDER BY COUNT(*) DESC, MAX(time_accessed) DESC LIMIT ?2",
+            )
+            .unwrap()
+            .query_map((like_pattern(text), limit as i64), |row| {
+                Ok(VisitedPage {
+                    title: row.get(0).unwrap(),
+                    url: row.get(1).unwrap(),
+                    visit_count: row.get(2).unwrap(),
+                    last_visited: row.get(3).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    /// The most visited pages, most visited first.
+    pub fn top_sites(&self, limit: usize) -> Vec<VisitedPage> {
+        self.search_browser_history("", limit)
+    }
+
+    pub fn delete_from_browser_history(&self, url: &str) {
+        self.connection
+            .execute("DELETE FROM browser_history WHERE url = ?1", (url,))
+            .unwrap();
+    }
+
+    pub fn clear_browser_history(&self) {
+        self.connection
+            .execute("DELETE FROM browser_history;", ())
+            .unwrap();
+    }
+
+    pub fn add_bookmark(&self, title: String, url: String) {
+        self.connection
+            .execute(
+                "INSERT INTO bookmarks (title, url, time_modified) VALUES (?1, ?2, ?3)",
+                (&title, &url, &chrono::Utc::now().naive_utc()),
+            )
+            .unwrap();
+    }
+
+    pub fn delete_bookmark(&self, id: i32) {
+        self.connection
+            .execute("DELETE FROM bookmarks WHERE id = ?1", (id,))
+            .unwrap();
+    }
+
+    /// Find bookmarks whose title or URL contains `text`.
+    pub fn search_bookmarks(&self, text: &str, limit: usize) -> Vec<BookmarkEntry> {
+        self.connection
+            .prepare(
+                "SELECT id, title, url, time_modified FROM bookmarks
+                WHERE url LIKE ?1 ESCAPE '\\' OR title LIKE ?1 ESCAPE '\\' LIMIT ?2",
+            )
+            .unwrap()
+            .query_map((like_pattern(text), limit as i64), |row| {
+                Ok(BookmarkEntry {
+                    id: row.get(0).unwrap(),
+                    title: row.get(1).unwrap(),
+                    url: row.get(2).unwrap(),
+                    time_modified: row.get(3).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn add_to_download_history(
+        &self,
+        title: String,
+        url: String,
+        save_path: &Path,
+        file_size_in_bytes: u32,
+    ) {
+        self.connection
+            .execute(
+                "INSERT INTO download_history (title, url, save_path, file_size_in_bytes, time_downloaded) VALUES (?1, ?2, ?3, ?4, ?5)",
+                (
+                    &title,
+                    &url,
+                    &save_path.to_string_lossy(),
+                    &file_size_in_bytes,
+                    &chrono::Utc::now().naive_utc(),
+                ),
+            )
+            .unwrap();
+    }
+
+    /// The downloads, most recent first.
+    pub fn get_download_history(&self) -> Vec<DownloadEntry> {
+        self.connection
+            .prepare(
+                "SELECT id, title, url, save_path, file_size_in_bytes, time_downloaded FROM download_history
+                ORDER BY time_downloaded DESC",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok(DownloadEntry {
+                    id: row.get(0).unwrap(),
+                    title: row.get(1).unwrap(),
+                    url: row.get(2).unwrap(),
+                    save_path: PathBuf::from(row.get::<usize, String>(3).unwrap()),
+                    file_size_in_bytes: row.get(4).unwrap(),
+                    time_downloaded: row.get(5).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn load_search_engines(&self) -> Vec<SearchEngine> {
+        self.connection
+            .prepare(
+                "SELECT id, name, search_url, keyword, icon_url, suggestions_url, suggestions_enabled, is_default FROM search_engines",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok(SearchEngine {
+                    id: row.get(0).unwrap(),
+                    name: row.get(1).unwrap(),
+                    search_url: row.get(2).unwrap(),
+                    keyword: row.get(3).unwrap(),
+                    icon_url: row.get(4).unwrap(),
+                    suggestions_url: row.get(5).unwrap(),
+                    suggestions_enabled: row.get(6).unwrap(),
+                    is_default: row.get(7).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    /// Add a search engine, ignoring its `id`. Making it the default is done separately.
+    pub fn add_search_engine(&self, search_engine: &SearchEngine) {
+        self.connection
+            .execute(
+                "INSERT INTO search_engines (name, search_url, keyword, icon_url, suggestions_url, suggestions_enabled, is_default) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                (
+                    &search_engine.name,
+                    &search_engine.search_url,
+                    &search_engine.keyword,
+                    &search_engine.icon_url,
+                    &search_engine.suggestions_url,
+                    &search_engine.suggestions_enabled,
+                    &search_engine.is_default,
+                ),
+            )
+            .unwrap();
+    }
+
+    pub fn remove_search_engine(&self, id: i32) {
+        self.connection
+            .execute("DELETE FROM search_engines WHERE id = ?1", (id,))
+            .unwrap();
+    }
+
+    pub fn set_default_search_engine(&self, id: i32) {
+        self.connection
+            .execute("UPDATE search_engines SET is_default = (id = ?1)", (id,))
+            .unwrap();
+    }
+
+    pub fn set_search_engine_suggestions_enabled(&self, id: i32, enabled: bool) {
+        self.connection
+            .execute(
+                "UPDATE search_engines SET suggestions_enabled = ?2 WHERE id = ?1",
+                (id, enabled),
+            )
+            .unwrap();
+    }
+
+    /// The stored value of a browser setting, or `None` if it has never been changed.
+    pub fn get_setting(&self, name: &str) -> Option<String> {
+        self.connection
+            .query_row(
+                "SELECT value FROM settings WHERE name = ?1",
+                (name,),
+                |row| row.get(0),
+            )
+            .ok()
+    }
+
+    pub fn set_setting(&self, name: &str, value: &str) {
+        self.connection
+            .execute(
+                "INSERT INTO settings (name, value) VALUES (?1, ?2)
+                 ON CONFLICT(name) DO UPDATE SET value = excluded.value",
+                (name, value),
+            )
+            .unwrap();
+    }
+
+    pub fn load_pinned_sites(&self) -> Vec<PinnedSite> {
+        self.connection
+            .prepare("SELECT title, url FROM pinned_sites ORDER BY position")
+            .unwrap()
+            .query_map([], |row| {
+                Ok(PinnedSite {
+                    title: row.get(0).unwrap(),
+                    url: row.get(1).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    /// Replace the pinned sites, keeping them in the given order.
+    pub fn save_pinned_sites(&self, pinned_sites: &[PinnedSite]) {
+        self.connection
+            .execute("DELETE FROM pinned_sites;", ())
+            .unwrap();
+        for (position, pinned_site) in pinned_sites.iter().enumerate() {
+            self.connection
+                .execute(
+                    "INSERT INTO pinned_sites (title, url, position) VALUES (?1, ?2, ?3)",
+                    (&pinned_site.title, &pinned_site.url, position as i64),
+                )
+                .unwrap();
+        }
+    }
+
+    pub fn load_certificate_exceptions(&self) -> Vec<CertificateException> {
+        self.connection
+            .prepare("SELECT id, host, fingerprint, time_added FROM certificate_exceptions ORDER BY host")
+            .unwrap()
+            .query_map([], |row| {
+                Ok(CertificateException {
+                    id: row.get(0).unwrap(),
+                    host: row.get(1).unwrap(),
+                    fingerprint: row.get(2).unwrap(),
+                    time_added: row.get(3).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn has_certificate_exception(&self, host: &str, fingerprint: &str) -> bool {
+        self.connection
+            .prepare("SELECT 1 FROM certificate_exceptions WHERE host = ?1 AND fingerprint = ?2")
+            .unwrap()
+            .exists((host, fingerprint))
+            .unwrap()
+    }
+
+    /// The DER encoded certificates that exceptions were made for, to hand to the network layer.
+    pub fn load_exception_certificates(&self) -> Vec<Vec<u8>> {
+        self.connection
+            .prepare("SELECT DISTINCT certificate FROM certificate_exceptions")
+            .unwrap()
+            .query_map([], |row| Ok(row.get(0).unwrap()))
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn add_certificate_exception(&self, host: &str, fingerprint: &str, certificate: &[u8]) {
+        self.connection
+            .execute(
+                "INSERT OR IGNORE INTO certificate_exceptions (host, fingerprint, certificate, time_added) VALUES (?1, ?2, ?3, ?4)",
+                (host, fingerprint, certificate, &chrono::Utc::now().naive_utc()),
+            )
+            .unwrap();
+    }
+
+    pub fn remove_certificate_exception(&self, id: i32) {
+        self.connection
+            .execute("DELETE FROM certificate_exceptions WHERE id = ?1", (id,))
+            .unwrap();
+    }
+
+    pub fn load_https_exceptions(&self) -> Vec<HttpsException> {
+        self.connection
+            .prepare("SELECT id, host, time_added FROM https_exceptions ORDER BY host")
+            .unwrap()
+            .query_map([], |row| {
+                Ok(HttpsException {
+                    id: row.get(0).unwrap(),
+                    host: row.get(1).unwrap(),
+                    time_added: row.get(2).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn has_https_exception(&self, host: &str) -> bool {
+        self.connection
+            .prepare("SELECT 1 FROM https_exceptions WHERE host = ?1")
+            .unwrap()
+            .exists((host,))
+            .unwrap()
+    }
+
+    pub fn add_https_exception(&self, host: &str) {
+        self.connection
+            .execute(
+                "INSERT OR IGNORE INTO https_exceptions (host, time_added) VALUES (?1, ?2)",
+                (host, &chrono::Utc::now().naive_utc()),
+            )
+            .unwrap();
+    }
+
+    pub fn remove_https_exception(&self, id: i32) {
+        self.connection
+            .execute("DELETE FROM https_exceptions WHERE id = ?1", (id,))
+            .unwrap();
+    }
+
+    pub fn load_content_blocking_exceptions(&self) -> Vec<ContentBlockingException> {
+        self.connection
+            .prepare("SELECT id, host, time_added FROM content_blocking_exceptions ORDER BY host")
+            .unwrap()
+            .query_map([], |row| {
+                Ok(ContentBlockingException {
+                    id: row.get(0).unwrap(),
+                    host: row.get(1).unwrap(),
+                    time_added: row.get(2).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn add_content_blocking_exception(&self, host: &str) {
+        self.connection
+            .execute(
+                "INSERT OR IGNORE INTO content_blocking_exceptions (host, time_added) VALUES (?1, ?2)",
+                (host, &chrono::Utc::now().naive_utc()),
+            )
+            .unwrap();
+    }
+
+    pub fn remove_content_blocking_exception(&self, host: &str) {
+        self.connection
+            .execute(
+                "DELETE FROM content_blocking_exceptions WHERE host = ?1",
+                (host,),
+            )
+            .unwrap();
+    }
+
+    pub fn load_dark_mode_exceptions(&self) -> Vec<DarkModeException> {
+        self.connection
+            .prepare("SELECT id, host, time_added FROM dark_mode_exceptions ORDER BY host")
+            .unwrap()
+            .query_map([], |row| {
+                Ok(DarkModeException {
+                    id: row.get(0).unwrap(),
+                    host: row.get(1).unwrap(),
+                    time_added: row.get(2).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn add_dark_mode_exception(&self, host: &str) {
+        self.connection
+            .execute(
+                "INSERT OR IGNORE INTO dark_mode_exceptions (host, time_added) VALUES (?1, ?2)",
+                (host, &chrono::Utc::now().naive_utc()),
+            )
+            .unwrap();
+    }
+
+    pub fn remove_dark_mode_exception(&self, host: &str) {
+        self.connection
+            .execute("DELETE FROM dark_mode_exceptions WHERE host = ?1", (host,))
+            .unwrap();
+    }
+
+    pub fn load_ca_certificate_paths(&self) -> Vec<CaCertificatePath> {
+        self.connection
+            .prepare("SELECT id, path FROM ca_certificate_paths ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                Ok(CaCertificatePath {
+                    id: row.get(0).unwrap(),
+                    path: PathBuf::from(row.get::<usize, String>(1).unwrap()),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn add_ca_certificate_path(&self, path: &Path) {
+        self.connection
+            .execute(
+                "INSERT OR IGNORE INTO ca_certificate_paths (path) VALUES (?1)",
+                (&path.to_string_lossy(),),
+            )
+            .unwrap();
+    }
+
+    pub fn remove_ca_certificate_path(&self, id: i32) {
+        self.connection
+            .execute("DELETE FROM ca_certificate_paths WHERE id = ?1", (id,))
+            .unwrap();
+    }
+
+    pub fn load_redirect_rules(&self) -> Vec<RedirectRule> {
+        self.connection
+            .prepare(
+                "SELECT id, kind, pattern, replacement, enabled FROM redirect_rules ORDER BY id",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok(RedirectRule {
+                    id: row.get(0).unwrap(),
+                    kind: RedirectRuleKind::from_name(&row.get::<usize, String>(1).unwrap())
+                        .unwrap_or_default(),
+                    pattern: row.get(2).unwrap(),
+                    replacement: row.get(3).unwrap(),
+                    enabled: row.get(4).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn add_redirect_rule(&self, rule: &RedirectRule) {
+        self.connection
+            .execute(
+                "INSERT INTO redirect_rules (kind, pattern, replacement, enabled) VALUES (?1, ?2, ?3, ?4)",
+                (rule.kind.as_str(), &rule.pattern, &rule.replacement, rule.enabled),
+            )
+            .unwrap();
+    }
+
+    pub fn update_redirect_rule(&self, rule: &RedirectRule) {
+        self.connection
+            .execute(
+                "UPDATE redirect_rules SET kind = ?2, pattern = ?3, replacement = ?4, enabled = ?5 WHERE id = ?1",
+                (rule.id, rule.kind.as_str(), &rule.pattern, &rule.replacement, rule.enabled),
+            )
+            .unwrap();
+    }
+
+    pub fn remove_redirect_rule(&self, id: i32) {
+        self.connection
+            .execute("DELETE FROM redirect_rules WHERE id = ?1", (id,))
+            .unwrap();
+    }
+
+    pub fn load_user_scripts(&self) -> Vec<UserScript> {
+        self.connection
+            .prepare("SELECT id, source, enabled, download_url FROM user_scripts ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                Ok(UserScript {
+                    id: row.get(0).unwrap(),
+                    source: row.get(1).unwrap(),
+                    enabled: row.get(2).unwrap(),
+                    download_url: row.get(3).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn add_user_script(&self, user_script: &UserScript) {
+        self.connection
+            .execute(
+                "INSERT INTO user_scripts (source, enabled, download_url) VALUES (?1, ?2, ?3)",
+                (
+                    &user_script.source,
+                    user_script.enabled,
+                    &user_script.download_url,
+                ),
+            )
+            .unwrap();
+    }
+
+    pub fn update_user_script(&self, user_script: &UserScript) {
+        self.connection
+            .execute(
+                "UPDATE user_scripts SET source = ?2, enabled = ?3, download_url = ?4 WHERE id = ?1",
+                (
+                    user_script.id,
+                    &user_script.source,
+                    user_script.enabled,
+                    &user_script.download_url,
+                ),
+            )
+            .unwrap();
+    }
+
+    pub fn remove_user_script(&self, id: i32) {
+        self.connection
+            .execute("DELETE FROM user_scripts WHERE id = ?1", (id,))
+            .unwrap();
+    }
+
+    pub fn load_user_stylesheets(&self) -> Vec<UserStylesheet> {
+        self.connection
+            .prepare(
+                "SELECT id, name, url_patterns, css, enabled FROM user_stylesheets ORDER BY id",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok(UserStylesheet {
+                    id: row.get(0).unwrap(),
+                    name: row.get(1).unwrap(),
+                    url_patterns: row.get(2).unwrap(),
+                    css: row.get(3).unwrap(),
+                    enabled: row.get(4).unwrap(),
+                })
+            })
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+    }
+
+    pub fn add_user_stylesheet(&self, stylesheet: &UserStylesheet) {
+        self.connection
+            .execute(
+                "INSERT INTO user_stylesheets (name, url_patterns, css, enabled) VALUES (?1, ?2, ?3, ?4)",
+                (
+                    &stylesheet.name,
+                    &stylesheet.url_patterns,
+                    &stylesheet.css,
+                    stylesheet.enabled,
+                ),
+            )
+            .unwrap();
+    }
+
+    pub fn update_user_stylesheet(&self, stylesheet: &UserStylesheet) {
+        self.connection
+            .execute(
+                "UPDATE user_stylesheets SET name = ?2, url_patterns = ?3, css = ?4, enabled = ?5 WHERE id = ?1",
+                (
+                    stylesheet.id,
+                    &stylesheet.name,
+                    &stylesheet.url_patterns,
+                    &stylesheet.css,
+                    stylesheet.enabled,
+                ),
+            )
+            .unwrap();
+    }
+
+    pub fn remove_user_stylesheet(&self, id: i32) {
+        self.connection
+            .execute("DELETE FROM user_stylesheets WHERE id = ?1", (id,))
+            .unwrap();
+    }
+
     pub fn save_open_tabs(&self, open_tabs: &[String]) {
         self.connection
             .execute("DELETE FROM open_tabs;", ())
@@ -75,3 +672,12 @@ impl BrowserDataConnection {
             .collect()
     }
 }
+
+/// A `LIKE` pattern that matches any text containing `text`, with `\` as the escape character.
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
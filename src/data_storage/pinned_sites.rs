@@ -0,0 +1,6 @@
+/// A site pinned to the new tab page, in the order they are shown.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PinnedSite {
+    pub title: String,
+    pub url: String,
+}
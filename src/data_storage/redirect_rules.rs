@@ -0,0 +1,54 @@
+/// How the pattern of a [`RedirectRule`] is matched against the URL being loaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RedirectRuleKind {
+    /// The pattern is a host, which matches the host itself and its `www.` subdomain. The
+    /// replacement is the host to load the same page from instead.
+    #[default]
+    Host,
+    /// The pattern is a whole URL in which `*` matches any text. The replacement is a URL in
+    /// which `$1`, `$2` and so on are replaced by the text matched by each `*`.
+    Wildcard,
+    /// The pattern is a regular expression, which can match any part of the URL. The
+    /// replacement is a URL in which `$1` or `${name}` is replaced by the text of a group.
+    Regex,
+}
+
+impl RedirectRuleKind {
+    pub const ALL: &[RedirectRuleKind] = &[
+        RedirectRuleKind::Host,
+        RedirectRuleKind::Wildcard,
+        RedirectRuleKind::Regex,
+    ];
+
+    /// The name the kind is stored under in the profile.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RedirectRuleKind::Host => "host",
+            RedirectRuleKind::Wildcard => "wildcard",
+            RedirectRuleKind::Regex => "regex",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.as_str() == name)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RedirectRuleKind::Host => "Host",
+            RedirectRuleKind::Wildcard => "Wildcard",
+            RedirectRuleKind::Regex => "Regular expression",
+        }
+    }
+}
+
+/// A rule that loads a different URL in place of the ones it matches, for example
+/// `reddit.com` from `old.reddit.com`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RedirectRule {
+    pub id: i32,
+    pub kind: RedirectRuleKind,
+    pub pattern: String,
+    pub replacement: String,
+    pub enabled: bool,
+}
@@ -0,0 +1,119 @@
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use servo::ServoUrl;
+use url::Url;
+
+/// Characters that have to be escaped when search terms are put into a query string.
+const SEARCH_TERMS: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchEngine {
+    pub id: i32,
+    pub name: String,
+    /// URL of the search results page with `%s` standing in for the search terms.
+    pub search_url: String,
+    /// Typing the keyword followed by a space and search terms in the location bar searches
+    /// with this engine, for example `w rust` for Wikipedia.
+    pub keyword: Option<String>,
+    pub icon_url: Option<String>,
+    /// URL returning search suggestions as JSON, with `%s` standing in for the search terms.
+    pub suggestions_url: Option<String>,
+    /// Whether what is typed in the location bar may be sent to `suggestions_url`.
+    pub suggestions_enabled: bool,
+    pub is_default: bool,
+}
+
+/// Put the search terms in place of the `%s` in the URL of a search engine, escaping them so
+/// that characters such as `&` and `#` are searched for rather than ending the parameter.
+pub fn fill_in_search_terms(url: &str, terms: &str) -> String {
+    url.replace("%s", &utf8_percent_encode(terms, SEARCH_TERMS).to_string())
+}
+
+impl SearchEngine {
+    pub fn search_url_for(&self, terms: &str) -> Option<ServoUrl> {
+        if terms.is_empty() {
+            return None;
+        }
+        ServoUrl::parse(&fill_in_search_terms(&self.search_url, terms)).ok()
+    }
+
+    /// The URL to fetch suggestions for the given search terms from, if this engine offers
+    /// suggestions and they are enabled.
+    pub fn suggestions_url_for(&self, terms: &str) -> Option<Url> {
+        if !self.suggestions_enabled || terms.is_empty() {
+            return None;
+        }
+        Url::parse(&fill_in_search_terms(self.suggestions_url.as_ref()?, terms)).ok()
+    }
+}
+
+/// The engine to search with when no keyword is used, which is the first one if none has been
+/// picked as the default.
+pub fn default_search_engine(search_engines: &[SearchEngine]) -> Option<&SearchEngine> {
+    search_engines
+        .iter()
+        .find(|engine| engine.is_default)
+        .or_else(|| search_engines.first())
+}
+
+/// The engine that the given keyword belongs to, if any.
+pub fn search_engine_for_keyword<'a>(
+    search_engines: &'a [SearchEngine],
+    keyword: &str,
+) -> Option<&'a SearchEngine> {
+    search_engines.iter().find(|engine| {
+        engine
+            .keyword
+            .as_deref()
+            .is_some_and(|engine_keyword| engine_keyword.eq_ignore_ascii_case(keyword))
+    })
+}
+
+/// The engines that a new profile starts out with, with the given search page as the default.
+pub fn built_in_search_engines(default_search_url: &str) -> Vec<SearchEngine> {
+    let engine = |name: &str, search_url: &str, keyword: &str, icon_url: &str| SearchEngine {
+        id: 0,
+        name: name.into(),
+        search_url: search_url.into(),
+        keyword: Some(keyword.into()),
+        icon_url: Some(icon_url.into()),
+        suggestions_url: None,
+        suggestions_enabled: true,
+        is_default: false,
+    };
+    vec![
+        SearchEngine {
+            suggestions_url: Some("https://duckduckgo.com/ac/?q=%s&type=list".into()),
+            is_default: true,
+            ..engine(
+                "DuckDuckGo",
+                default_search_url,
+                "d",
+                "https://duckduckgo.com/favicon.ico",
+            )
+        },
+        SearchEngine {
+            suggestions_url: Some(
+                "https://en.wikipedia.org/w/api.php?action=opensearch&search=%s".into(),
+            ),
+            ..engine(
+                "Wikipedia",
+                "https://en.wikipedia.org/wiki/Special:Search?search=%s",
+                "w",
+                "https://en.wikipedia.org/favicon.ico",
+            )
+        },
+        SearchEngine {
+            suggestions_url: Some("https://ac.ecosia.org/autocomplete?q=%s&type=list".into()),
+            ..engine(
+                "Ecosia",
+                "https://www.ecosia.org/search?q=%s",
+                "e",
+                "https://www.ecosia.org/favicon.ico",
+            )
+        },
+    ]
+}
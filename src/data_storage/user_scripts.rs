@@ -0,0 +1,10 @@
+/// A user script in the profile. Its name and the pages it runs on are read from the metadata
+/// block at the start of its source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserScript {
+    pub id: i32,
+    pub source: String,
+    pub enabled: bool,
+    /// The `.user.js` URL the script was installed from, if it wasn't written in the browser.
+    pub download_url: Option<String>,
+}
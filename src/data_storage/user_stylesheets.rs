@@ -0,0 +1,11 @@
+/// A style sheet that is added to pages, to change their fonts and colours for example.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserStylesheet {
+    pub id: i32,
+    pub name: String,
+    /// Match patterns such as `https://*.example.com/*`, one per line, for the pages the style
+    /// sheet is added to. It is added to every page if there are none.
+    pub url_patterns: String,
+    pub css: String,
+    pub enabled: bool,
+}
@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use egui::{
@@ -18,6 +18,9 @@ use servo::{
     SimpleDialog,
 };
 
+use crate::running_app_state::UserInterfaceCommand;
+use crate::user_interface::context_menu::{ContextMenuContext, EmbedderContextMenuItem};
+
 /// The minimum width of many UI elements including dialog boxes and menus,
 /// for the sake of consistency.
 const MINIMUM_UI_ELEMENT_WIDTH: f32 = 150.0;
@@ -28,6 +31,12 @@ pub enum Dialog {
         dialog: EguiFileDialog,
         maybe_picker: Option<FilePicker>,
     },
+    /// A dialog for choosing where to save something produced by the browser itself, rather
+    /// than by web content, such as a screenshot.
+    SaveFile {
+        dialog: EguiFileDialog,
+        on_save: Option<Box<dyn FnOnce(PathBuf)>>,
+    },
     Alert(Option<AlertDialog>),
     Confirm(Option<ConfirmDialog>),
     Prompt(Option<PromptDialog>),
@@ -56,8 +65,21 @@ pub enum Dialog {
     },
     ContextMenu {
         menu: Option<ContextMenu>,
+        context: ContextMenuContext,
         toolbar_offset: Length<f32, DeviceIndependentPixel>,
     },
+    /// A message from the browser itself, rather than from web content.
+    Message {
+        title: String,
+        message: String,
+    },
+    /// A question from the browser itself, which queues the command if the user agrees.
+    Question {
+        title: String,
+        message: String,
+        accept_label: String,
+        command: Option<UserInterfaceCommand>,
+    },
 }
 
 impl Dialog {
@@ -86,6 +108,17 @@ impl Dialog {
         }
     }
 
+    pub fn new_save_file_dialog(
+        default_file_name: &str,
+        on_save: impl FnOnce(PathBuf) + 'static,
+    ) -> Self {
+        let dialog = EguiFileDialog::new().default_file_name(default_file_name);
+        Dialog::SaveFile {
+            dialog,
+            on_save: Some(Box::new(on_save)),
+        }
+    }
+
     pub fn new_simple_dialog(dialog: SimpleDialog) -> Self {
         match dialog {
             SimpleDialog::Alert(alert_dialog) => Self::Alert(Some(alert_dialog)),
@@ -149,8 +182,13 @@ impl Dialog {
         }
     }
 
-    /// Returns false if the dialog has been closed, or true otherwise.
-    pub fn update(&mut self, ctx: &egui::Context) -> bool {
+    /// Returns false if the dialog has been closed, or true otherwise. Any commands chosen in
+    /// the dialog are added to the `event_queue`.
+    pub fn update(
+        &mut self,
+        ctx: &egui::Context,
+        event_queue: &mut Vec<UserInterfaceCommand>,
+    ) -> bool {
         enum DialogAction {
             Dismiss,
             Submit,
@@ -205,6 +243,25 @@ impl Dialog {
                 }
                 matches!(action, DialogAction::Continue)
             }
+            Dialog::SaveFile { dialog, on_save } => {
+                if on_save.is_none() {
+                    return false;
+                }
+                if *dialog.state() == DialogState::Closed {
+                    dialog.save_file();
+                }
+
+                match dialog.update(ctx).state() {
+                    DialogState::Open => true,
+                    DialogState::Picked(path) => {
+                        if let Some(on_save) = on_save.take() {
+                            on_save(path.clone());
+                        }
+                        false
+                    }
+                    _ => false,
+                }
+            }
             Dialog::Alert(maybe_alert_dialog) => {
                 let Some(alert_dialog) = maybe_alert_dialog else {
                     return false;
@@ -620,11 +677,13 @@ impl Dialog {
             }
             Dialog::ContextMenu {
                 menu,
+                context,
                 toolbar_offset,
             } => {
                 let mut is_open = true;
                 if let Some(context_menu) = menu {
                     let mut selected_action = None;
+                    let mut selected_embedder_item = None;
                     let mut position = context_menu.position();
                     position.min.y += toolbar_offset.0 as i32;
                     position.max.y += toolbar_offset.0 as i32;
@@ -642,32 +701,7 @@ impl Dialog {
                                             action,
                                             enabled,
                                         } => {
-                                            let (color, sense) = match enabled {
-                                                true => (
-                                                    ui.visuals().strong_text_color(),
-                                                    Sense::click(),
-                                                ),
-                                                false => {
-                                                    (ui.visuals().weak_text_color(), Sense::empty())
-                                                }
-                                            };
-
-                                            ui.style_mut().visuals.widgets.inactive.weak_bg_fill =
-                                                ui.visuals().panel_fill;
-                                            ui.style_mut().visuals.widgets.inactive.bg_fill =
-                                                ui.visuals().panel_fill;
-                                            let button =
-                                                Button::new(RichText::new(label).color(color))
-                                                    .sense(sense)
-                                                    .corner_radius(CornerRadius::ZERO)
-                                                    .stroke(Stroke::NONE)
-                                                    .wrap_mode(egui::TextWrapMode::Extend)
-                                                    .min_size(Vec2 {
-                                                        x: MINIMUM_UI_ELEMENT_WIDTH,
-                                                        y: 0.0,
-                                                    });
-
-                                            if ui.add(button).clicked() {
+                                            if context_menu_button(ui, label, *enabled).clicked() {
                                                 selected_action = Some(*action);
                                                 ui.close();
                                             }
@@ -677,6 +711,21 @@ impl Dialog {
                                         }
                                     }
                                 }
+
+                                // The browser's own items go below the ones supplied by Servo.
+                                let embedder_items = context.items();
+                                if !context_menu.items().is_empty() && !embedder_items.is_empty() {
+                                    ui.separator();
+                                }
+                                for item in embedder_items {
+                                    if matches!(item, EmbedderContextMenuItem::Separator) {
+                                        ui.separator();
+                                    } else if context_menu_button(ui, &item.label(), true).clicked()
+                                    {
+                                        selected_embedder_item = Some(item);
+                                        ui.close();
+                                    }
+                                }
                             })
                         });
 
@@ -690,9 +739,62 @@ impl Dialog {
                             return false;
                         }
                     }
+
+                    if let Some(item) = selected_embedder_item {
+                        event_queue.extend(item.into_user_interface_command());
+                        menu.take();
+                        return false;
+                    }
                 }
                 is_open
             }
+            Dialog::Message { title, message } => {
+                let mut is_open = true;
+                Modal::new(Id::new("message")).show(ctx, |ui| {
+                    ui.heading(title.as_str());
+                    make_dialog_label(message, ui, None);
+                    egui::Sides::new().show(
+                        ui,
+                        |_ui| {},
+                        |ui| {
+                            if ui.button("Close").clicked()
+                                || ui.input(|i| i.key_pressed(egui::Key::Escape))
+                            {
+                                is_open = false;
+                            }
+                        },
+                    );
+                });
+                is_open
+            }
+            Dialog::Question {
+                title,
+                message,
+                accept_label,
+                command,
+            } => {
+                let mut is_open = true;
+                Modal::new(Id::new("question")).show(ctx, |ui| {
+                    ui.heading(title.as_str());
+                    make_dialog_label(message, ui, None);
+                    egui::Sides::new().show(
+                        ui,
+                        |_ui| {},
+                        |ui| {
+                            if ui.button(accept_label.as_str()).clicked() {
+                                event_queue.extend(command.take());
+                                is_open = false;
+                            }
+                            if ui.button("Cancel").clicked()
+                                || ui.input(|i| i.key_pressed(egui::Key::Escape))
+                            {
+                                is_open = false;
+                            }
+                        },
+                    );
+                });
+                is_open
+            }
         }
     }
 
@@ -710,13 +812,54 @@ impl Dialog {
 
     pub(crate) fn new_context_menu(
         menu: ContextMenu,
+        context: ContextMenuContext,
         toolbar_offset: Length<f32, DeviceIndependentPixel>,
     ) -> Dialog {
         Dialog::ContextMenu {
             menu: Some(menu),
+            context,
             toolbar_offset,
         }
     }
+
+    pub(crate) fn new_message_dialog(title: String, message: String) -> Dialog {
+        Dialog::Message { title, message }
+    }
+
+    pub(crate) fn new_question_dialog(
+        title: String,
+        message: String,
+        accept_label: String,
+        command: UserInterfaceCommand,
+    ) -> Dialog {
+        Dialog::Question {
+            title,
+            message,
+            accept_label,
+            command: Some(command),
+        }
+    }
+}
+
+/// A borderless, full width button as used for items in a context menu.
+fn context_menu_button(ui: &mut egui::Ui, label: &str, enabled: bool) -> egui::Response {
+    let (color, sense) = match enabled {
+        true => (ui.visuals().strong_text_color(), Sense::click()),
+        false => (ui.visuals().weak_text_color(), Sense::empty()),
+    };
+
+    ui.style_mut().visuals.widgets.inactive.weak_bg_fill = ui.visuals().panel_fill;
+    ui.style_mut().visuals.widgets.inactive.bg_fill = ui.visuals().panel_fill;
+    let button = Button::new(RichText::new(label).color(color))
+        .sense(sense)
+        .corner_radius(CornerRadius::ZERO)
+        .stroke(Stroke::NONE)
+        .wrap_mode(egui::TextWrapMode::Extend)
+        .min_size(Vec2 {
+            x: MINIMUM_UI_ELEMENT_WIDTH,
+            y: 0.0,
+        });
+    ui.add(button)
 }
 
 fn make_dialog_label(message: &str, ui: &mut egui::Ui, input_text: Option<&mut String>) {
@@ -0,0 +1,72 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The pages that Servo shows in place of a page that couldn't be loaded. Servo reads these
+//! through the [`ResourceReader`](crate::resources) and fills in the `${reason}` itself, the
+//! browser specific placeholders are filled in here.
+
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+
+use crate::data_storage::BrowserDataConnection;
+use crate::data_storage::search_engines::default_search_engine;
+use crate::prism_protocol::generate_token;
+
+/// How many error pages can be open at once before the oldest of them stop working.
+const MAX_ERROR_PAGE_TOKENS: usize = 64;
+
+/// The tokens of the error pages that have been shown, with the origin each of them is used for.
+static ERROR_PAGE_TOKENS: LazyLock<Mutex<VecDeque<(String, Option<String>)>>> =
+    LazyLock::new(Default::default);
+
+/// A new secret for an error page, that it needs to make exceptions for the site it is shown
+/// for at `prism:certificate` and `prism:https-fallback`. Each page gets its own token, which
+/// belongs to the first origin it is used for, so it can't be used to make exceptions for others.
+pub(crate) fn new_error_page_token() -> String {
+    let token = generate_token();
+    let mut tokens = ERROR_PAGE_TOKENS.lock().unwrap();
+    if tokens.len() == MAX_ERROR_PAGE_TOKENS {
+        tokens.pop_front();
+    }
+    tokens.push_back((token.clone(), None));
+    token
+}
+
+/// Whether `token` was given to an error page shown for `origin`. A token that hasn't been used
+/// yet is given to `origin` from now on.
+pub(crate) fn error_page_token_allows(token: &str, origin: &str) -> bool {
+    let mut tokens = ERROR_PAGE_TOKENS.lock().unwrap();
+    let Some((_, token_origin)) = tokens.iter_mut().find(|(known, _)| known == token) else {
+        return false;
+    };
+    token_origin.get_or_insert_with(|| origin.to_owned()) == origin
+}
+
+/// Fill in the network error page, offering to search with the default search engine.
+pub(crate) fn net_error_page(template: &str) -> String {
+    let search_engines = BrowserDataConnection::new().load_search_engines();
+    let search_url = default_search_engine(&search_engines)
+        .map(|search_engine| search_engine.search_url.as_str());
+    fill_in_net_error_page(template, search_url)
+        .replace("${error_page_token}", &new_error_page_token())
+}
+
+/// Fill in `${search_url}` with the given search URL as a JavaScript string, or `null`.
+pub(crate) fn fill_in_net_error_page(template: &str, search_url: Option<&str>) -> String {
+    template.replace(
+        "${search_url}",
+        &script_value(&serde_json::json!(search_url)),
+    )
+}
+
+/// JSON that can be written into a `<script>` element, which can't be ended early by a `</script>`
+/// in a string.
+fn script_value(value: &serde_json::Value) -> String {
+    value.to_string().replace('<', "\\u003c")
+}
+
+/// Fill in the certificate error page, which Servo fills in with the certificate afterwards.
+pub(crate) fn bad_cert_page(template: &str) -> String {
+    template.replace("${error_page_token}", &new_error_page_token())
+}
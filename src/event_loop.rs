@@ -20,6 +20,11 @@ pub enum AppEvent {
         theme: winit::window::Theme,
         window_id: winit::window::WindowId,
     },
+    /// Work on another thread has produced something for the user interface of this window to
+    /// show, such as location bar suggestions.
+    RedrawWindow {
+        window_id: winit::window::WindowId,
+    },
 }
 
 impl From<egui_winit::accesskit_winit::Event> for AppEvent {
@@ -34,6 +39,7 @@ impl AppEvent {
             AppEvent::Waker => None,
             AppEvent::Accessibility(event) => Some(event.window_id),
             AppEvent::UpdateTheme { window_id, .. } => Some(*window_id),
+            AppEvent::RedrawWindow { window_id } => Some(*window_id),
         }
     }
 }
@@ -0,0 +1,646 @@
+//! A registry of named browser actions, and the keymap that binds keyboard shortcuts to them.
+//!
+//! The keymap is read from `keymap.conf` in the config directory, with one binding per line:
+//!
+//! ```text
+//! # <shortcut> = <action> [overridable]
+//! cmdorctrl+shift+t = new-tab
+//! ctrl+w = none
+//! cmdorctrl+plus = zoom-in overridable
+//! ```
+//!
+//! A `#` starts a comment at the start of a line or after whitespace, so `shift+#` can still be
+//! bound. A binding for a shortcut replaces the default binding for that shortcut, and binding it
+//! to `none` removes it. Bindings marked as `overridable` are only run if the page did not handle
+//! the key event itself.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use keyboard_types::{Key, Modifiers, NamedKey, ShortcutMatcher};
+use log::warn;
+use servo::{EditingActionEvent, WebRenderDebugOption};
+
+use crate::keyutils::{CMD_OR_ALT, CMD_OR_CONTROL};
+use crate::misc_utils::save_page::SavePageMode;
+use crate::misc_utils::screenshot::{ScreenshotArea, ScreenshotTarget};
+use crate::prefs::default_config_dir;
+use crate::running_app_state::UserInterfaceCommand;
+
+const KEYMAP_FILE_NAME: &str = "keymap.conf";
+
+const DEFAULT_KEYMAP: &str = "
+cmdorctrl+r = reload
+cmdorctrl+w = close-tab
+cmdorctrl+t = new-tab
+cmdorctrl+q = quit
+cmdorctrl+p = toggle-sampling-profiler
+cmdorctrl+x = cut
+cmdorctrl+c = copy
+cmdorctrl+v = paste
+ctrl+F9 = capture-webrender
+ctrl+F10 = toggle-render-target-debug
+ctrl+F11 = toggle-texture-cache-debug
+ctrl+F12 = toggle-webrender-profiler
+cmdoralt+ArrowRight = forward
+cmdoralt+ArrowLeft = back
+Escape = exit-fullscreen
+cmdorctrl+1 = select-tab-1
+cmdorctrl+2 = select-tab-2
+cmdorctrl+3 = select-tab-3
+cmdorctrl+4 = select-tab-4
+cmdorctrl+5 = select-tab-5
+cmdorctrl+6 = select-tab-6
+cmdorctrl+7 = select-tab-7
+cmdorctrl+8 = select-tab-8
+cmdorctrl+9 = select-last-tab
+ctrl+PageDown = next-tab
+ctrl+PageUp = previous-tab
+cmdorctrl+= = zoom-in overridable
+cmdorctrl+plus = zoom-in overridable
+cmdorctrl+minus = zoom-out overridable
+cmdorctrl+0 = reset-zoom overridable
+cmdorctrl+shift+p = command-palette
+cmdorctrl+h = show-history
+cmdorctrl+shift+o = show-bookmarks
+cmdorctrl+shift+y = show-downloads
+F11 = toggle-fullscreen
+";
+
+/// On Windows these are used to type characters on some keyboard layouts.
+#[cfg(not(target_os = "windows"))]
+const NON_WINDOWS_DEFAULT_KEYMAP: &str = "
+cmdorctrl+] = forward
+cmdorctrl+[ = back
+";
+#[cfg(target_os = "windows")]
+const NON_WINDOWS_DEFAULT_KEYMAP: &str = "";
+
+/// Shortcuts that are handled by the user interface itself, rather than through the keymap.
+const RESERVED_SHORTCUTS: &[(&str, &str)] = &[
+    ("cmdorctrl+l", "Focus the location bar"),
+    ("alt+d", "Focus the location bar"),
+];
+
+/// A named action that the browser can perform in response to a keyboard shortcut.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum BrowserAction {
+    NewTab,
+    CloseTab,
+    Reload,
+    Back,
+    Forward,
+    NextTab,
+    PreviousTab,
+    /// Select the tab with the given zero based index.
+    SelectTab(usize),
+    SelectLastTab,
+    Cut,
+    Copy,
+    Paste,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    ExitFullscreen,
+    ToggleFullscreen,
+    CommandPalette,
+    ShowHistory,
+    ShowBookmarks,
+    ShowDownloads,
+    ShowSettings,
+    ClearHistory,
+    CopyCleanLink,
+    SavePageHtmlOnly,
+    SavePageComplete,
+    SaveVisibleScreenshot,
+    SaveFullPageScreenshot,
+    CopyVisibleScreenshot,
+    CopyFullPageScreenshot,
+    ToggleReaderMode,
+    Inspect,
+    ToggleSamplingProfiler,
+    CaptureWebRender,
+    ToggleRenderTargetDebug,
+    ToggleTextureCacheDebug,
+    ToggleWebRenderProfiler,
+    Quit,
+}
+
+impl BrowserAction {
+    /// Every action, in the order they are shown to the user.
+    pub(crate) const ALL: &[BrowserAction] = &[
+        BrowserAction::NewTab,
+        BrowserAction::CloseTab,
+        BrowserAction::Reload,
+        BrowserAction::Back,
+        BrowserAction::Forward,
+        BrowserAction::NextTab,
+        BrowserAction::PreviousTab,
+        BrowserAction::SelectTab(0),
+        BrowserAction::SelectTab(1),
+        BrowserAction::SelectTab(2),
+        BrowserAction::SelectTab(3),
+        BrowserAction::SelectTab(4),
+        BrowserAction::SelectTab(5),
+        BrowserAction::SelectTab(6),
+        BrowserAction::SelectTab(7),
+        BrowserAction::SelectLastTab,
+        BrowserAction::Cut,
+        BrowserAction::Copy,
+        BrowserAction::Paste,
+        BrowserAction::ZoomIn,
+        BrowserAction::ZoomOut,
+        BrowserAction::ResetZoom,
+        BrowserAction::ExitFullscreen,
+        BrowserAction::ToggleFullscreen,
+        BrowserAction::CommandPalette,
+        BrowserAction::ShowHistory,
+        BrowserAction::ShowBookmarks,
+        BrowserAction::ShowDownloads,
+        BrowserAction::ShowSettings,
+        BrowserAction::ClearHistory,
+        BrowserAction::CopyCleanLink,
+        BrowserAction::SavePageHtmlOnly,
+        BrowserAction::SavePageComplete,
+        BrowserAction::SaveVisibleScreenshot,
+        BrowserAction::SaveFullPageScreenshot,
+        BrowserAction::CopyVisibleScreenshot,
+        BrowserAction::CopyFullPageScreenshot,
+        BrowserAction::ToggleReaderMode,
+        BrowserAction::Inspect,
+        BrowserAction::ToggleSamplingProfiler,
+        BrowserAction::CaptureWebRender,
+        BrowserAction::ToggleRenderTargetDebug,
+        BrowserAction::ToggleTextureCacheDebug,
+        BrowserAction::ToggleWebRenderProfiler,
+        BrowserAction::Quit,
+    ];
+
+    /// The name used for this action in the keymap file.
+    pub(crate) fn name(&self) -> String {
+        match self {
+            BrowserAction::NewTab => "new-tab".into(),
+            BrowserAction::CloseTab => "close-tab".into(),
+            BrowserAction::Reload => "reload".into(),
+            BrowserAction::Back => "back".into(),
+            BrowserAction::Forward => "forward".into(),
+            BrowserAction::NextTab => "next-tab".into(),
+            BrowserAction::PreviousTab => "previous-tab".into(),
+            BrowserAction::SelectTab(index) => format!("select-tab-{}", index + 1),
+            BrowserAction::SelectLastTab => "select-last-tab".into(),
+            BrowserAction::Cut => "cut".into(),
+            BrowserAction::Copy => "copy".into(),
+            BrowserAction::Paste => "paste".into(),
+            BrowserAction::ZoomIn => "zoom-in".into(),
+            BrowserAction::ZoomOut => "zoom-out".into(),
+            BrowserAction::ResetZoom => "reset-zoom".into(),
+            BrowserAction::ExitFullscreen => "exit-fullscreen".into(),
+            BrowserAction::ToggleFullscreen => "toggle-fullscreen".into(),
+            BrowserAction::CommandPalette => "command-palette".into(),
+            BrowserAction::ShowHistory => "show-history".into(),
+            BrowserAction::ShowBookmarks => "show-bookmarks".into(),
+            BrowserAction::ShowDownloads => "show-downloads".into(),
+            BrowserAction::ShowSettings => "show-settings".into(),
+            BrowserAction::ClearHistory => "clear-history".into(),
+            BrowserAction::CopyCleanLink => "copy-clean-link".into(),
+            BrowserAction::SavePageHtmlOnly => "save-page-html-only".into(),
+            BrowserAction::SavePageComplete => "save-page-complete".into(),
+            BrowserAction::SaveVisibleScreenshot => "save-visible-screenshot".into(),
+            BrowserAction::SaveFullPageScreenshot => "save-full-page-screenshot".into(),
+            BrowserAction::CopyVisibleScreenshot => "copy-visible-screenshot".into(),
+            BrowserAction::CopyFullPageScreenshot => "copy-full-page-screenshot".into(),
+            BrowserAction::ToggleReaderMode => "toggle-reader-mode".into(),
+            BrowserAction::Inspect => "inspect".into(),
+            BrowserAction::ToggleSamplingProfiler => "toggle-sampling-profiler".into(),
+            BrowserAction::CaptureWebRender => "capture-webrender".into(),
+            BrowserAction::ToggleRenderTargetDebug => "toggle-render-target-debug".into(),
+            BrowserAction::ToggleTextureCacheDebug => "toggle-texture-cache-debug".into(),
+            BrowserAction::ToggleWebRenderProfiler => "toggle-webrender-profiler".into(),
+            BrowserAction::Quit => "quit".into(),
+        }
+    }
+
+    /// A human readable description of this action.
+    pub(crate) fn description(&self) -> String {
+        match self {
+            BrowserAction::NewTab => "New tab".into(),
+            BrowserAction::CloseTab => "Close tab".into(),
+            BrowserAction::Reload => "Reload page".into(),
+            BrowserAction::Back => "Go back".into(),
+            BrowserAction::Forward => "Go forward".into(),
+            BrowserAction::NextTab => "Next tab".into(),
+            BrowserAction::PreviousTab => "Previous tab".into(),
+            BrowserAction::SelectTab(index) => format!("Select tab {}", index + 1),
+            BrowserAction::SelectLastTab => "Select last tab".into(),
+            BrowserAction::Cut => "Cut".into(),
+            BrowserAction::Copy => "Copy".into(),
+            BrowserAction::Paste => "Paste".into(),
+            BrowserAction::ZoomIn => "Zoom in".into(),
+            BrowserAction::ZoomOut => "Zoom out".into(),
+            BrowserAction::ResetZoom => "Reset zoom".into(),
+            BrowserAction::ExitFullscreen => "Exit fullscreen".into(),
+            BrowserAction::ToggleFullscreen => "Toggle fullscreen".into(),
+            BrowserAction::CommandPalette => "Open the command palette".into(),
+            BrowserAction::ShowHistory => "Show history".into(),
+            BrowserAction::ShowBookmarks => "Show bookmarks".into(),
+            BrowserAction::ShowDownloads => "Show downloads".into(),
+            BrowserAction::ShowSettings => "Show settings".into(),
+            BrowserAction::ClearHistory => "Clear browsing history".into(),
+            BrowserAction::CopyCleanLink => "Copy clean link".into(),
+            BrowserAction::SavePageHtmlOnly => "Save page as HTML only…".into(),
+            BrowserAction::SavePageComplete => "Save page as complete…".into(),
+            BrowserAction::SaveVisibleScreenshot => "Save screenshot of visible area…".into(),
+            BrowserAction::SaveFullPageScreenshot => "Save screenshot of full page…".into(),
+            BrowserAction::CopyVisibleScreenshot => "Copy screenshot of visible area".into(),
+            BrowserAction::CopyFullPageScreenshot => "Copy screenshot of full page".into(),
+            BrowserAction::ToggleReaderMode => "Enter or leave reader mode".into(),
+            BrowserAction::Inspect => "Inspect page".into(),
+            BrowserAction::ToggleSamplingProfiler => "Start or stop the sampling profiler".into(),
+            BrowserAction::CaptureWebRender => "Capture WebRender state".into(),
+            BrowserAction::ToggleRenderTargetDebug => "Toggle WebRender render target debug".into(),
+            BrowserAction::ToggleTextureCacheDebug => "Toggle WebRender texture cache debug".into(),
+            BrowserAction::ToggleWebRenderProfiler => "Toggle WebRender profiler".into(),
+            BrowserAction::Quit => "Quit".into(),
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        BrowserAction::ALL
+            .iter()
+            .find(|action| action.name() == name)
+            .copied()
+    }
+
+    /// The [`UserInterfaceCommand`] that performs this action.
+    pub(crate) fn user_interface_command(&self) -> UserInterfaceCommand {
+        match self {
+            BrowserAction::NewTab => UserInterfaceCommand::NewWebView,
+            BrowserAction::CloseTab => UserInterfaceCommand::CloseActiveWebView,
+            BrowserAction::Reload => UserInterfaceCommand::Reload,
+            BrowserAction::Back => UserInterfaceCommand::Back,
+            BrowserAction::Forward => UserInterfaceCommand::Forward,
+            BrowserAction::NextTab => UserInterfaceCommand::ActivateNextWebView,
+            BrowserAction::PreviousTab => UserInterfaceCommand::ActivatePreviousWebView,
+            BrowserAction::SelectTab(index) => UserInterfaceCommand::ActivateWebViewByIndex(*index),
+            BrowserAction::SelectLastTab => UserInterfaceCommand::ActivateLastWebView,
+            BrowserAction::Cut => UserInterfaceCommand::EditingAction(EditingActionEvent::Cut),
+            BrowserAction::Copy => UserInterfaceCommand::EditingAction(EditingActionEvent::Copy),
+            BrowserAction::Paste => UserInterfaceCommand::EditingAction(EditingActionEvent::Paste),
+            BrowserAction::ZoomIn => UserInterfaceCommand::ZoomIn,
+            BrowserAction::ZoomOut => UserInterfaceCommand::ZoomOut,
+            BrowserAction::ResetZoom => UserInterfaceCommand::ResetZoom,
+            BrowserAction::ExitFullscreen => UserInterfaceCommand::ExitFullscreen,
+            BrowserAction::ToggleFullscreen => UserInterfaceCommand::ToggleFullscreen,
+            BrowserAction::CommandPalette => UserInterfaceCommand::OpenCommandPalette,
+            BrowserAction::ShowHistory => UserInterfaceCommand::ShowHistory,
+            BrowserAction::ShowBookmarks => UserInterfaceCommand::ShowBookmarks,
+            BrowserAction::ShowDownloads => UserInterfaceCommand::ShowDownloads,
+            BrowserAction::ShowSettings => UserInterfaceCommand::ShowSettings,
+            BrowserAction::ClearHistory => UserInterfaceCommand::ConfirmClearHistory,
+            BrowserAction::CopyCleanLink => UserInterfaceCommand::CopyCleanLink(None),
+            BrowserAction::SavePageHtmlOnly => {
+                UserInterfaceCommand::SavePage(SavePageMode::HtmlOnly)
+            }
+            BrowserAction::SavePageComplete => {
+                UserInterfaceCommand::SavePage(SavePageMode::Complete)
+            }
+            BrowserAction::SaveVisibleScreenshot => {
+                UserInterfaceCommand::Screenshot(ScreenshotArea::Visible, ScreenshotTarget::File)
+            }
+            BrowserAction::SaveFullPageScreenshot => {
+                UserInterfaceCommand::Screenshot(ScreenshotArea::FullPage, ScreenshotTarget::File)
+            }
+            BrowserAction::CopyVisibleScreenshot => UserInterfaceCommand::Screenshot(
+                ScreenshotArea::Visible,
+                ScreenshotTarget::Clipboard,
+            ),
+            BrowserAction::CopyFullPageScreenshot => UserInterfaceCommand::Screenshot(
+                ScreenshotArea::FullPage,
+                ScreenshotTarget::Clipboard,
+            ),
+            BrowserAction::ToggleReaderMode => UserInterfaceCommand::ToggleReaderMode,
+            BrowserAction::Inspect => UserInterfaceCommand::Inspect,
+            BrowserAction::ToggleSamplingProfiler => UserInterfaceCommand::ToggleSamplingProfiler,
+            BrowserAction::CaptureWebRender => UserInterfaceCommand::CaptureWebRender,
+            BrowserAction::ToggleRenderTargetDebug => {
+                UserInterfaceCommand::ToggleWebRenderDebugging(
+                    WebRenderDebugOption::RenderTargetDebug,
+                )
+            }
+            BrowserAction::ToggleTextureCacheDebug => {
+                UserInterfaceCommand::ToggleWebRenderDebugging(
+                    WebRenderDebugOption::TextureCacheDebug,
+                )
+            }
+            BrowserAction::ToggleWebRenderProfiler => {
+                UserInterfaceCommand::ToggleWebRenderDebugging(WebRenderDebugOption::Profiler)
+            }
+            BrowserAction::Quit => UserInterfaceCommand::Quit,
+        }
+    }
+}
+
+/// A combination of modifiers and a key, such as `Ctrl+Shift+T`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Shortcut {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl FromStr for Shortcut {
+    type Err = String;
+
+    fn from_str(shortcut: &str) -> Result<Self, Self::Err> {
+        let shortcut = shortcut.trim();
+        // Split on '+', but allow binding the '+' key itself as the last part, eg `ctrl++`.
+        let (modifier_part, key_part) = match shortcut.strip_suffix("++") {
+            Some(modifiers) => (Some(modifiers), "+"),
+            None => match shortcut.rsplit_once('+') {
+                Some((modifiers, key)) => (Some(modifiers), key),
+                None => (None, shortcut),
+            },
+        };
+
+        let mut modifiers = Modifiers::empty();
+        for modifier in modifier_part.into_iter().flat_map(|part| part.split('+')) {
+            modifiers |= match modifier.trim().to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CONTROL,
+                "shift" => Modifiers::SHIFT,
+                "alt" | "option" => Modifiers::ALT,
+                "meta" | "cmd" | "super" => Modifiers::META,
+                "cmdorctrl" => CMD_OR_CONTROL,
+                "cmdoralt" => CMD_OR_ALT,
+                _ => return Err(format!("Unknown modifier \"{modifier}\" in \"{shortcut}\"")),
+            };
+        }
+
+        let key = match key_part.trim() {
+            "" => return Err(format!("Missing key in \"{shortcut}\"")),
+            key if key.eq_ignore_ascii_case("plus") => Key::Character("+".into()),
+            key if key.eq_ignore_ascii_case("minus") => Key::Character("-".into()),
+            key if key.eq_ignore_ascii_case("space") => Key::Character(" ".into()),
+            key if key.chars().count() == 1 => Key::Character(key.to_uppercase()),
+            key => match NamedKey::from_str(key) {
+                Ok(named_key) if named_key != NamedKey::Unidentified => Key::Named(named_key),
+                _ => return Err(format!("Unknown key \"{key}\" in \"{shortcut}\"")),
+            },
+        };
+
+        Ok(Shortcut { modifiers, key })
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (Modifiers::CONTROL, "Ctrl"),
+            (
+                Modifiers::META,
+                if cfg!(target_os = "macos") {
+                    "Cmd"
+                } else {
+                    "Super"
+                },
+            ),
+            (Modifiers::ALT, "Alt"),
+            (Modifiers::SHIFT, "Shift"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+        match &self.key {
+            Key::Character(character) if character == " " => write!(f, "Space"),
+            Key::Character(character) => write!(f, "{character}"),
+            Key::Named(named_key) => write!(f, "{named_key}"),
+        }
+    }
+}
+
+/// A [`Shortcut`] bound to a [`BrowserAction`].
+#[derive(Clone, Debug)]
+pub(crate) struct Binding {
+    pub shortcut: Shortcut,
+    pub action: BrowserAction,
+    /// Whether the page gets to handle the key event first, and can prevent this binding from
+    /// running by preventing the default action of the event.
+    pub overridable_by_page: bool,
+}
+
+/// Two or more uses of the same shortcut, of which only the first will ever run.
+#[derive(Debug)]
+pub(crate) struct Conflict {
+    pub shortcut: Shortcut,
+    pub uses: Vec<String>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Keymap {
+    bindings: Vec<Binding>,
+    /// Problems found while reading the keymap file, to be shown in the settings.
+    pub errors: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Keymap {
+            bindings: vec![],
+            errors: vec![],
+        };
+        keymap.apply(DEFAULT_KEYMAP);
+        keymap.apply(NON_WINDOWS_DEFAULT_KEYMAP);
+        keymap
+    }
+}
+
+impl Keymap {
+    pub(crate) fn keymap_file_path() -> PathBuf {
+        default_config_dir().join(KEYMAP_FILE_NAME)
+    }
+
+    /// Load the default keymap, with the bindings from the keymap file in the config directory
+    /// applied on top of it.
+    pub(crate) fn load() -> Self {
+        Self::load_from(&Self::keymap_file_path())
+    }
+
+    pub(crate) fn load_from(path: &Path) -> Self {
+        let mut keymap = Keymap::default();
+        if let Ok(contents) = std::fs::read_to_string(path) {
+            keymap.apply_user_bindings(&contents);
+        }
+        for error in &keymap.errors {
+            warn!("{}: {error}", path.display());
+        }
+        keymap
+    }
+
+    /// Apply user bindings, which replace any default bindings for the same shortcuts.
+    pub(crate) fn apply_user_bindings(&mut self, contents: &str) {
+        let mut user_keymap = Keymap {
+            bindings: vec![],
+            errors: vec![],
+        };
+        let unbound = user_keymap.apply(contents);
+        self.bindings.retain(|binding| {
+            !user_keymap
+                .bindings
+                .iter()
+                .map(|user_binding| &user_binding.shortcut)
+                .chain(unbound.iter())
+                .any(|shortcut| *shortcut == binding.shortcut)
+        });
+        // User bindings come first, so that they win over any remaining defaults.
+        user_keymap.bindings.append(&mut self.bindings);
+        self.bindings = user_keymap.bindings;
+        self.errors.append(&mut user_keymap.errors);
+    }
+
+    /// Parse `contents` and add its bindings to this keymap, returning the shortcuts that were
+    /// bound to `none`.
+    fn apply(&mut self, contents: &str) -> Vec<Shortcut> {
+        let mut unbound = vec![];
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((shortcut, action)) = line.rsplit_once(" = ") else {
+                self.errors.push(format!(
+                    "Line {}: expected \"<shortcut> = <action>\"",
+                    line_number + 1
+                ));
+                continue;
+            };
+            let shortcut = match shortcut.parse::<Shortcut>() {
+                Ok(shortcut) => shortcut,
+                Err(error) => {
+                    self.errors
+                        .push(format!("Line {}: {error}", line_number + 1));
+                    continue;
+                }
+            };
+
+            let mut words = action.split_whitespace();
+            let action_name = words.next().unwrap_or_default();
+            let overridable_by_page = words.any(|word| word == "overridable");
+            if action_name == "none" {
+                unbound.push(shortcut);
+                continue;
+            }
+            let Some(action) = BrowserAction::from_name(action_name) else {
+                self.errors.push(format!(
+                    "Line {}: unknown action \"{action_name}\"",
+                    line_number + 1
+                ));
+                continue;
+            };
+            self.bindings.push(Binding {
+                shortcut,
+                action,
+                overridable_by_page,
+            });
+        }
+        unbound
+    }
+
+    pub(crate) fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// The shortcuts bound to the given action, formatted for display.
+    pub(crate) fn shortcuts_for(&self, action: BrowserAction) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.action == action)
+            .map(|binding| binding.shortcut.to_string())
+            .collect()
+    }
+
+    /// Find the action bound to the given key event. When `overridable_by_page` is false this
+    /// only considers bindings that are handled before the page sees the event, otherwise only
+    /// those that are handled after the page has declined to handle it.
+    pub(crate) fn action_for_event(
+        &self,
+        event: &keyboard_types::KeyboardEvent,
+        overridable_by_page: bool,
+    ) -> Option<BrowserAction> {
+        self.enabled_action_for_event(event, overridable_by_page, |_| true)
+    }
+
+    /// Like [`Self::action_for_event`], but skips the bindings for actions that are not
+    /// `enabled`, so that a later binding for the same shortcut can match instead.
+    pub(crate) fn enabled_action_for_event(
+        &self,
+        event: &keyboard_types::KeyboardEvent,
+        overridable_by_page: bool,
+        enabled: impl Fn(BrowserAction) -> bool,
+    ) -> Option<BrowserAction> {
+        let mut matched_action = None;
+        let _ = self
+            .bindings
+            .iter()
+            .filter(|binding| {
+                binding.overridable_by_page == overridable_by_page && enabled(binding.action)
+            })
+            .fold(
+                ShortcutMatcher::from_event(event.clone()),
+                |matcher, binding| {
+                    matcher.shortcut(
+                        binding.shortcut.modifiers,
+                        binding.shortcut.key.clone(),
+                        || matched_action = Some(binding.action),
+                    )
+                },
+            );
+        matched_action
+    }
+
+    /// Shortcuts that are used more than once, either in the keymap or by the user interface.
+    pub(crate) fn conflicts(&self) -> Vec<Conflict> {
+        let mut uses: HashMap<Shortcut, Vec<String>> = HashMap::new();
+        let mut order = vec![];
+        let reserved = RESERVED_SHORTCUTS.iter().filter_map(|(shortcut, use_)| {
+            Some((
+                shortcut.parse::<Shortcut>().ok()?,
+                format!("{use_} (built in)"),
+            ))
+        });
+        let bound = self
+            .bindings
+            .iter()
+            .map(|binding| (binding.shortcut.clone(), binding.action.description()));
+        for (shortcut, use_) in reserved.chain(bound) {
+            let entry = uses.entry(shortcut.clone()).or_default();
+            if entry.is_empty() {
+                order.push(shortcut);
+            }
+            entry.push(use_);
+        }
+        order
+            .into_iter()
+            .filter_map(|shortcut| {
+                let uses = uses.remove(&shortcut)?;
+                (uses.len() > 1).then_some(Conflict { shortcut, uses })
+            })
+            .collect()
+    }
+}
+
+/// The line without its comment, which starts at a `#` at the start of the line or after
+/// whitespace.
+fn strip_comment(line: &str) -> &str {
+    let comment_start = line.char_indices().find(|(index, character)| {
+        *character == '#'
+            && line[..*index]
+                .chars()
+                .next_back()
+                .is_none_or(char::is_whitespace)
+    });
+    match comment_start {
+        Some((index, _)) => &line[..index],
+        None => line,
+    }
+}
@@ -15,16 +15,20 @@ pub(crate) mod app;
 pub mod browser_window;
 pub mod data_storage;
 pub(crate) mod dialog;
+mod error_pages;
 pub(crate) mod event_loop;
 pub mod geometry;
+mod keymap;
 mod keyutils;
 pub mod misc_utils;
 pub mod panic_utils;
 mod parser;
 mod prefs;
+mod prism_protocol;
 mod resource_protocol;
 mod resources;
 mod running_app_state;
+mod url_cleaner;
 pub mod user_interface;
 
 const NEW_TAB_PAGE_URL: &str = "resource:///newtab.html";
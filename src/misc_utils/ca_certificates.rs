@@ -0,0 +1,160 @@
+//! Extra certificate authorities to trust, for networks that inspect TLS traffic with a root
+//! certificate of their own.
+//!
+//! The certificates are read once at startup from the `certificates` directory in the config
+//! directory, from the files and directories configured in the settings, and optionally from
+//! the certificates installed on the system. Servo can only be given a single file of trusted
+//! certificates, which replaces its built-in ones, so when there are extra certificates they
+//! are written to a bundle together with the built-in ones.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use base64::Engine;
+use log::warn;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::pem::PemObject;
+use rustls::{ClientConfig, RootCertStore};
+
+use crate::data_storage::BrowserDataConnection;
+use crate::prefs::default_config_dir;
+
+/// The setting that decides whether the certificates installed on the system are trusted.
+pub const SYSTEM_CERTIFICATES_SETTING: &str = "system_ca_certificates";
+
+const PROFILE_CERTIFICATES_DIR: &str = "certificates";
+const BUNDLE_FILE_NAME: &str = "ca-bundle.pem";
+
+static EXTRA_CERTIFICATES: OnceLock<Vec<CertificateDer<'static>>> = OnceLock::new();
+
+/// The directory in the config directory whose certificates are always trusted.
+pub fn profile_certificates_dir() -> PathBuf {
+    default_config_dir().join(PROFILE_CERTIFICATES_DIR)
+}
+
+/// Parse the certificates in a PEM file, ignoring anything else in it, such as keys.
+pub fn parse_pem_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certificates = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| format!("Invalid PEM: {error}"))?;
+    if certificates.is_empty() {
+        return Err("No certificates found".into());
+    }
+    Ok(certificates)
+}
+
+/// Load the certificates in a PEM file, or in all `.pem` and `.crt` files in a directory.
+pub fn load_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    if !path.is_dir() {
+        let pem = std::fs::read(path).map_err(|error| error.to_string())?;
+        return parse_pem_certificates(&pem);
+    }
+
+    let mut files: Vec<_> = std::fs::read_dir(path)
+        .map_err(|error| error.to_string())?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|file| {
+            file.extension()
+                .is_some_and(|extension| extension == "pem" || extension == "crt")
+        })
+        .collect();
+    files.sort();
+    // Certificate directories such as /etc/ssl/certs contain other files as well, so files
+    // without certificates are skipped rather than failing the whole directory.
+    Ok(files
+        .iter()
+        .filter_map(|file| std::fs::read(file).ok())
+        .filter_map(|pem| parse_pem_certificates(&pem).ok())
+        .flatten()
+        .collect())
+}
+
+/// The extra certificates to trust, which are loaded on first use.
+pub fn extra_certificates() -> &'static [CertificateDer<'static>] {
+    EXTRA_CERTIFICATES.get_or_init(|| {
+        let connection = BrowserDataConnection::new();
+        let mut paths = vec![profile_certificates_dir()];
+        paths.extend(
+            connection
+                .load_ca_certificate_paths()
+                .into_iter()
+                .map(|ca_certificate_path| ca_certificate_path.path),
+        );
+
+        let mut certificates = Vec::new();
+        for path in paths.iter().filter(|path| path.exists()) {
+            match load_certificates(path) {
+                Ok(loaded) => certificates.extend(loaded),
+                Err(error) => warn!(
+                    "Could not load certificates from {}: {error}",
+                    path.display()
+                ),
+            }
+        }
+        if connection
+            .get_setting(SYSTEM_CERTIFICATES_SETTING)
+            .is_some_and(|value| value == "true")
+        {
+            let system = rustls_native_certs::load_native_certs();
+            for error in system.errors {
+                warn!("Could not load system certificates: {error}");
+            }
+            certificates.extend(system.certs);
+        }
+        certificates
+    })
+}
+
+/// The built-in roots together with the given extra certificates.
+pub fn root_cert_store(extra: &[CertificateDer<'static>]) -> RootCertStore {
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let (_, ignored) = roots.add_parsable_certificates(extra.iter().cloned());
+    if ignored > 0 {
+        warn!("Ignored {ignored} extra certificates that could not be parsed");
+    }
+    roots
+}
+
+/// A TLS configuration that trusts the built-in roots and the given extra certificates.
+pub fn client_config(extra: &[CertificateDer<'static>]) -> Arc<ClientConfig> {
+    Arc::new(
+        ClientConfig::builder()
+            .with_root_certificates(root_cert_store(extra))
+            .with_no_client_auth(),
+    )
+}
+
+/// Encode certificates as PEM.
+pub fn pem_bundle<'a>(certificates: impl IntoIterator<Item = &'a CertificateDer<'a>>) -> String {
+    let mut pem = String::new();
+    for certificate in certificates {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(certificate);
+        pem.push_str("-----BEGIN CERTIFICATE-----\n");
+        for line in encoded.as_bytes().chunks(64) {
+            pem.push_str(&String::from_utf8_lossy(line));
+            pem.push('\n');
+        }
+        pem.push_str("-----END CERTIFICATE-----\n");
+    }
+    pem
+}
+
+/// Write the built-in roots and the extra certificates to a bundle for Servo, returning its
+/// path, or `None` if there are no extra certificates and Servo can use its own roots.
+pub fn write_certificate_bundle() -> Option<PathBuf> {
+    let extra = extra_certificates();
+    if extra.is_empty() {
+        return None;
+    }
+    let path = default_config_dir().join(BUNDLE_FILE_NAME);
+    let bundle = pem_bundle(webpki_root_certs::TLS_SERVER_ROOT_CERTS.iter().chain(extra));
+    match std::fs::write(&path, bundle) {
+        Ok(()) => Some(path),
+        Err(error) => {
+            warn!("Could not write the certificate bundle: {error}");
+            None
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Describing the certificates that Servo couldn't verify, for the certificate error page, and
+//! handing the certificates that exceptions were made for to Servo.
+
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme};
+use servo::protocol_handler::FetchContext;
+use sha2::{Digest, Sha256};
+use x509_parser::prelude::{FromDer, X509Certificate, X509Name};
+
+use crate::data_storage::BrowserDataConnection;
+
+/// How long to wait for a server to connect and present its certificate.
+const SERVER_CERTIFICATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The network states that the certificate exceptions have been handed to, by address. Normal and
+/// private browsing each have their own.
+static STATES_WITH_EXCEPTIONS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// What the certificate error page shows about a certificate.
+#[derive(Debug, PartialEq)]
+pub struct CertificateDetails {
+    pub subject: String,
+    pub issuer: String,
+    /// The start of the validity period, as an RFC 3339 timestamp.
+    pub not_before: String,
+    /// The end of the validity period, as an RFC 3339 timestamp.
+    pub not_after: String,
+    /// The SHA-256 fingerprint, as colon separated hex.
+    pub fingerprint: String,
+}
+
+/// Describe the DER encoded certificate.
+pub fn certificate_details(der: &[u8]) -> Result<CertificateDetails, String> {
+    let (_, certificate) =
+        X509Certificate::from_der(der).map_err(|error| format!("Invalid certificate: {error}"))?;
+    let validity = certificate.validity();
+    let timestamp = |time: &x509_parser::time::ASN1Time| {
+        chrono::DateTime::from_timestamp(time.timestamp(), 0)
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default()
+    };
+    Ok(CertificateDetails {
+        subject: describe_name(certificate.subject()),
+        issuer: describe_name(certificate.issuer()),
+        not_before: timestamp(&validity.not_before),
+        not_after: timestamp(&validity.not_after),
+        fingerprint: certificate_fingerprint(der),
+    })
+}
+
+/// The SHA-256 fingerprint of the DER encoded certificate, as colon separated hex.
+pub fn certificate_fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// The common name and organization of a name, which is what people recognize a certificate
+/// by, falling back to the whole name if it has neither.
+fn describe_name(name: &X509Name) -> String {
+    let common_name = name
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok());
+    let organization = name
+        .iter_organization()
+        .next()
+        .and_then(|organization| organization.as_str().ok());
+    match (common_name, organization) {
+        (Some(common_name), Some(organization)) => format!("{common_name} ({organization})"),
+        (Some(name), None) | (None, Some(name)) => name.to_owned(),
+        (None, None) => name.to_string(),
+    }
+}
+
+/// Hand the certificates that exceptions were made for to the network state of `context`, once,
+/// so that Servo accepts them without showing the certificate error page. This happens on the
+/// first load from an internal scheme, which is the new tab page when the browser starts.
+pub(crate) fn apply_certificate_exceptions(
+    context: &FetchContext,
+    connection: &BrowserDataConnection,
+) {
+    let state = Arc::as_ptr(&context.state) as usize;
+    let mut states = STATES_WITH_EXCEPTIONS.lock().unwrap();
+    if states.contains(&state) {
+        return;
+    }
+    states.push(state);
+    for certificate in connection.load_exception_certificates() {
+        context
+            .state
+            .override_manager
+            .add_override(&CertificateDer::from(certificate));
+    }
+}
+
+/// Connect to a server and return the DER encoded certificate that it presents, without
+/// verifying it. Exceptions are made for this certificate rather than one that a page claims
+/// the server has.
+pub fn fetch_server_certificate(host: &str, port: u16) -> Result<Vec<u8>, String> {
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|error| error.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(host.to_owned()).map_err(|error| error.to_string())?;
+    let mut connection =
+        ClientConnection::new(Arc::new(config), server_name).map_err(|error| error.to_string())?;
+
+    let mut last_error = format!("Could not resolve {host}");
+    let addresses = (host, port)
+        .to_socket_addrs()
+        .map_err(|error| error.to_string())?;
+    let mut stream = None;
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, SERVER_CERTIFICATE_TIMEOUT) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(error) => last_error = error.to_string(),
+        }
+    }
+    let mut stream = stream.ok_or(last_error)?;
+    stream
+        .set_read_timeout(Some(SERVER_CERTIFICATE_TIMEOUT))
+        .and_then(|()| stream.set_write_timeout(Some(SERVER_CERTIFICATE_TIMEOUT)))
+        .map_err(|error| error.to_string())?;
+
+    while connection.is_handshaking() {
+        connection
+            .complete_io(&mut stream)
+            .map_err(|error| error.to_string())?;
+    }
+    connection
+        .peer_certificates()
+        .and_then(|certificates| certificates.first())
+        .map(|certificate| certificate.to_vec())
+        .ok_or_else(|| "The server did not present a certificate".into())
+}
+
+/// Accepts every certificate, so that the certificate of a server can be read even though it
+/// couldn't be verified. Signatures are still checked, so that the server has the key of the
+/// certificate.
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            certificate,
+            signature,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            certificate,
+            signature,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,561 @@
+//! A content blocker for filter lists in the Adblock Plus format, such as EasyList.
+//!
+//! Network filters block the requests for the matching URLs, and element hiding filters (`##`)
+//! hide the matching elements with a style sheet. Servo doesn't tell what kind of resource a
+//! request is for, so filters that only apply to some kinds, such as `$script`, are skipped
+//! rather than blocking every kind. Regular expression filters and the extended syntaxes for
+//! scriptlets and procedural element hiding are skipped as well.
+//!
+//! The lists are loaded from the `filter_lists` directory in the config directory.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+use log::warn;
+use url::{Position, Url};
+
+use crate::prefs::default_config_dir;
+
+/// The directory in the config directory that the filter lists are loaded from.
+pub const FILTER_LISTS_DIR: &str = "filter_lists";
+
+/// The setting that decides whether content is blocked at all.
+pub const CONTENT_BLOCKING_SETTING: &str = "content_blocking";
+
+/// A filter list that has been loaded, for showing in the settings.
+#[derive(Debug)]
+pub struct FilterList {
+    pub name: String,
+    pub network_filters: usize,
+    pub cosmetic_filters: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct ContentBlocker {
+    lists: Vec<FilterList>,
+    filters: Vec<NetworkFilter>,
+    /// The indices of the filters in `filters` by a token that every URL they match contains,
+    /// so that only a few filters have to be tried for each URL.
+    filters_by_token: HashMap<String, Vec<usize>>,
+    /// The indices of the filters that have no such token, which are tried for every URL.
+    untokenized_filters: Vec<usize>,
+    /// `@@` filters with `$document`, for pages on which nothing is blocked.
+    document_exceptions: Vec<NetworkFilter>,
+    /// `@@` filters with `$elemhide`, for pages on which no elements are hidden.
+    elemhide_exceptions: Vec<NetworkFilter>,
+    /// `@@` filters with `$generichide`, for pages on which only site specific elements are
+    /// hidden.
+    generichide_exceptions: Vec<NetworkFilter>,
+    cosmetic_filters: Vec<CosmeticFilter>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Anchor {
+    /// The pattern can match anywhere in the URL.
+    None,
+    /// `|`, the pattern matches at the start of the URL.
+    Start,
+    /// `||`, the pattern matches at the start of the host or one of its subdomains.
+    Domain,
+}
+
+#[derive(Debug)]
+struct NetworkFilter {
+    /// The lowercase pattern, where `*` matches anything and `^` matches a separator.
+    pattern: Vec<u8>,
+    anchor: Anchor,
+    /// `|` at the end, the pattern matches at the end of the URL.
+    end_anchor: bool,
+    exception: bool,
+    /// `Some(true)` for `$third-party`, `Some(false)` for `$~third-party`.
+    third_party: Option<bool>,
+    domains: DomainRestriction,
+}
+
+/// The sites that a filter applies to, from `$domain=` or the part before `##`.
+#[derive(Debug, Default)]
+struct DomainRestriction {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+#[derive(Debug)]
+struct CosmeticFilter {
+    selector: String,
+    exception: bool,
+    domains: DomainRestriction,
+}
+
+/// What a line of a filter list is, if it is something the content blocker supports.
+enum Filter {
+    Network(NetworkFilter, Option<ExceptionKind>),
+    Cosmetic(CosmeticFilter),
+}
+
+/// The kinds of `@@` filters that apply to whole pages rather than requests.
+enum ExceptionKind {
+    Document,
+    ElemHide,
+    GenericHide,
+}
+
+impl ContentBlocker {
+    /// Load the filter lists in the profile.
+    pub fn load() -> Self {
+        let mut blocker = Self::default();
+        let dir = default_config_dir().join(FILTER_LISTS_DIR);
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return blocker;
+        };
+        let mut files: Vec<_> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|file| file.extension().is_some_and(|extension| extension == "txt"))
+            .collect();
+        files.sort();
+        for file in files {
+            match std::fs::read_to_string(&file) {
+                Ok(list) => blocker.add_filter_list(&list_name(&file), &list),
+                Err(error) => warn!("Could not read filter list {}: {error}", file.display()),
+            }
+        }
+        blocker
+    }
+
+    /// Add the filters of a list, skipping the ones that aren't supported.
+    pub fn add_filter_list(&mut self, name: &str, list: &str) {
+        let mut network_filters = 0;
+        let mut cosmetic_filters = 0;
+        for line in list.lines() {
+            match parse_filter(line.trim()) {
+                Some(Filter::Network(filter, None)) => {
+                    network_filters += 1;
+                    self.add_network_filter(filter);
+                }
+                Some(Filter::Network(filter, Some(kind))) => {
+                    network_filters += 1;
+                    match kind {
+                        ExceptionKind::Document => self.document_exceptions.push(filter),
+                        ExceptionKind::ElemHide => self.elemhide_exceptions.push(filter),
+                        ExceptionKind::GenericHide => self.generichide_exceptions.push(filter),
+                    }
+                }
+                Some(Filter::Cosmetic(filter)) => {
+                    cosmetic_filters += 1;
+                    self.cosmetic_filters.push(filter);
+                }
+                None => {}
+            }
+        }
+        self.lists.push(FilterList {
+            name: name.to_owned(),
+            network_filters,
+            cosmetic_filters,
+        });
+    }
+
+    fn add_network_filter(&mut self, filter: NetworkFilter) {
+        let index = self.filters.len();
+        match filter_token(&filter) {
+            Some(token) => self.filters_by_token.entry(token).or_default().push(index),
+            None => self.untokenized_filters.push(index),
+        }
+        self.filters.push(filter);
+    }
+
+    /// The filter lists that have been loaded.
+    pub fn filter_lists(&self) -> &[FilterList] {
+        &self.lists
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+
+    /// Whether a request for the URL, made by the page at `page_url`, should be blocked.
+    pub fn should_block(&self, url: &Url, page_url: Option<&Url>) -> bool {
+        if !matches!(url.scheme(), "http" | "https" | "ws" | "wss") {
+            return false;
+        }
+        if let Some(page_url) = page_url
+            && self
+                .document_exceptions
+                .iter()
+                .any(|filter| filter.matches(page_url, None))
+        {
+            return false;
+        }
+
+        let lowercase_url = url.as_str().to_lowercase();
+        let mut blocked = false;
+        for index in self.candidate_filters(&lowercase_url) {
+            let filter = &self.filters[index];
+            if filter.matches_lowercase(url, &lowercase_url, page_url) {
+                if filter.exception {
+                    return false;
+                }
+                blocked = true;
+            }
+        }
+        blocked
+    }
+
+    /// The indices of the filters that could match the lowercase URL.
+    fn candidate_filters<'a>(&'a self, lowercase_url: &'a str) -> impl Iterator<Item = usize> + 'a {
+        lowercase_url
+            .split(|character: char| !character.is_ascii_alphanumeric())
+            .filter(|token| !token.is_empty())
+            .filter_map(|token| self.filters_by_token.get(token))
+            .flatten()
+            .chain(&self.untokenized_filters)
+            .copied()
+    }
+
+    /// A style sheet that hides the elements that the element hiding filters match on the page
+    /// at the URL, or an empty string if there are none.
+    pub fn hiding_css(&self, page_url: &Url) -> String {
+        let applies =
+            |filters: &[NetworkFilter]| filters.iter().any(|filter| filter.matches(page_url, None));
+        if applies(&self.elemhide_exceptions) {
+            return String::new();
+        }
+        let generic_hidden = !applies(&self.generichide_exceptions);
+        let host = page_url.host_str().unwrap_or_default();
+
+        let exceptions: Vec<&str> = self
+            .cosmetic_filters
+            .iter()
+            .filter(|filter| filter.exception && filter.domains.applies_to(host))
+            .map(|filter| filter.selector.as_str())
+            .collect();
+        let mut css = String::new();
+        for filter in &self.cosmetic_filters {
+            if filter.exception
+                || !filter.domains.applies_to(host)
+                || (filter.domains.include.is_empty() && !generic_hidden)
+                || exceptions.contains(&filter.selector.as_str())
+            {
+                continue;
+            }
+            // Each selector gets its own rule, as a selector that isn't supported would make the
+            // whole rule invalid.
+            css.push_str(&filter.selector);
+            css.push_str(" { display: none !important; }\n");
+        }
+        css
+    }
+}
+
+impl NetworkFilter {
+    fn matches(&self, url: &Url, page_url: Option<&Url>) -> bool {
+        self.matches_lowercase(url, &url.as_str().to_lowercase(), page_url)
+    }
+
+    fn matches_lowercase(&self, url: &Url, lowercase_url: &str, page_url: Option<&Url>) -> bool {
+        let page_host = page_url.and_then(Url::host_str);
+        if let Some(third_party) = self.third_party {
+            let Some(page_host) = page_host else {
+                return false;
+            };
+            if is_third_party(url.host_str().unwrap_or_default(), page_host) != third_party {
+                return false;
+            }
+        }
+        if !self.domains.include.is_empty() || !self.domains.exclude.is_empty() {
+            match page_host {
+                Some(page_host) if self.domains.applies_to(page_host) => {}
+                _ => return false,
+            }
+        }
+
+        let text = lowercase_url.as_bytes();
+        match self.anchor {
+            Anchor::None | Anchor::Start => glob_match(&self.pattern, text, self.end_anchor),
+            Anchor::Domain => {
+                // Serialized URLs are ASCII, so lowercasing doesn't move the host.
+                let host = host_range(url);
+                // The pattern can start at the host or after any of its dots.
+                std::iter::once(host.start)
+                    .chain(
+                        host.filter(|index| text[*index] == b'.')
+                            .map(|index| index + 1),
+                    )
+                    .any(|start| glob_match(&self.pattern, &text[start..], self.end_anchor))
+            }
+        }
+    }
+}
+
+impl DomainRestriction {
+    /// Parse domains separated by `separator`, where domains starting with `~` are excluded.
+    fn parse(domains: &str, separator: char) -> Self {
+        let mut restriction = Self::default();
+        for domain in domains.split(separator).map(str::trim) {
+            match domain.strip_prefix('~') {
+                Some(domain) if !domain.is_empty() => {
+                    restriction.exclude.push(domain.to_lowercase())
+                }
+                None if !domain.is_empty() => restriction.include.push(domain.to_lowercase()),
+                _ => {}
+            }
+        }
+        restriction
+    }
+
+    fn applies_to(&self, host: &str) -> bool {
+        let matches = |domain: &String| {
+            host == domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|subdomain| subdomain.ends_with('.'))
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// The name of a filter list, from its file name.
+fn list_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Parse a line of a filter list, returning `None` for comments and unsupported filters.
+fn parse_filter(line: &str) -> Option<Filter> {
+    if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+        return None;
+    }
+    if let Some(filter) = parse_cosmetic_filter(line) {
+        return filter.map(Filter::Cosmetic);
+    }
+    parse_network_filter(line)
+}
+
+/// Parse an element hiding filter, returning `None` if the line isn't one, and `Some(None)` if
+/// it is one that isn't supported.
+fn parse_cosmetic_filter(line: &str) -> Option<Option<CosmeticFilter>> {
+    let (domains, separator, selector) = ["#@#", "##", "#?#", "#@?#", "#$#", "#@$#", "#%#"]
+        .iter()
+        .filter_map(|separator| {
+            let index = line.find(separator)?;
+            Some((&line[..index], *separator, &line[index + separator.len()..]))
+        })
+        .min_by_key(|(domains, ..)| domains.len())?;
+    let supported = matches!(separator, "##" | "#@#")
+        && !selector.is_empty()
+        && !selector.starts_with('+')
+        && !selector.starts_with('^')
+        && !selector.contains(":-abp-")
+        && !selector.contains(['{', '}']);
+    Some(supported.then(|| CosmeticFilter {
+        selector: selector.to_owned(),
+        exception: separator == "#@#",
+        domains: DomainRestriction::parse(domains, ','),
+    }))
+}
+
+fn parse_network_filter(line: &str) -> Option<Filter> {
+    let (exception, line) = match line.strip_prefix("@@") {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    // Regular expression filters.
+    if line.starts_with('/') && line.ends_with('/') && line.len() > 1 {
+        return None;
+    }
+
+    let (pattern, options) = split_options(line);
+    let mut third_party = None;
+    let mut domains = DomainRestriction::default();
+    let mut kind = None;
+    for option in options.into_iter().flat_map(|options| options.split(',')) {
+        match option.trim().to_ascii_lowercase().as_str() {
+            "third-party" | "3p" => third_party = Some(true),
+            "~third-party" | "first-party" | "1p" => third_party = Some(false),
+            "document" | "doc" if exception => kind = Some(ExceptionKind::Document),
+            "elemhide" | "ehide" if exception => kind = Some(ExceptionKind::ElemHide),
+            "generichide" | "ghide" if exception => kind = Some(ExceptionKind::GenericHide),
+            option => match option.strip_prefix("domain=") {
+                Some(value) => domains = DomainRestriction::parse(value, '|'),
+                None => return None,
+            },
+        }
+    }
+
+    let mut pattern = pattern.to_lowercase();
+    let anchor = if let Some(rest) = pattern.strip_prefix("||") {
+        pattern = rest.to_owned();
+        Anchor::Domain
+    } else if let Some(rest) = pattern.strip_prefix('|') {
+        pattern = rest.to_owned();
+        Anchor::Start
+    } else {
+        // An unanchored pattern can start anywhere.
+        pattern.insert(0, '*');
+        Anchor::None
+    };
+    let end_anchor = pattern.ends_with('|');
+    if end_anchor {
+        pattern.pop();
+    }
+    // A filter that matches every URL is almost certainly a mistake.
+    if pattern.bytes().all(|byte| byte == b'*') && domains.include.is_empty() && kind.is_none() {
+        return None;
+    }
+
+    let filter = NetworkFilter {
+        pattern: pattern.into_bytes(),
+        anchor,
+        end_anchor,
+        exception,
+        third_party,
+        domains,
+    };
+    Some(Filter::Network(filter, kind))
+}
+
+/// Split a network filter into its pattern and the options after its last `$`. A `$` that isn't
+/// followed by options, as in `/count$visit.php`, is part of the pattern.
+fn split_options(line: &str) -> (&str, Option<&str>) {
+    match line.rsplit_once('$') {
+        Some((pattern, options)) if options.split(',').all(is_option) => (pattern, Some(options)),
+        _ => (line, None),
+    }
+}
+
+/// Whether the text looks like a filter option, such as `script`, `~third-party` or
+/// `domain=example.com`, whether or not we support it.
+fn is_option(option: &str) -> bool {
+    let name = option
+        .split_once('=')
+        .map_or(option, |(name, _)| name)
+        .trim();
+    let name = name.strip_prefix('~').unwrap_or(name);
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+/// A token that every URL the filter matches contains, surrounded by separators: a run of
+/// letters and digits in the pattern that can't be extended by a wildcard.
+fn filter_token(filter: &NetworkFilter) -> Option<String> {
+    let pattern = &filter.pattern;
+    let mut best: Option<&[u8]> = None;
+    let mut start = 0;
+    while start < pattern.len() {
+        if !pattern[start].is_ascii_alphanumeric() {
+            start += 1;
+            continue;
+        }
+        let end = start
+            + pattern[start..]
+                .iter()
+                .position(|byte| !byte.is_ascii_alphanumeric())
+                .unwrap_or(pattern.len() - start);
+        let bounded_before = if start == 0 {
+            filter.anchor != Anchor::None
+        } else {
+            pattern[start - 1] != b'*'
+        };
+        let bounded_after = if end == pattern.len() {
+            filter.end_anchor
+        } else {
+            pattern[end] != b'*'
+        };
+        if bounded_before && bounded_after && best.is_none_or(|best| end - start > best.len()) {
+            best = Some(&pattern[start..end]);
+        }
+        start = end;
+    }
+    best.map(|token| String::from_utf8_lossy(token).into_owned())
+}
+
+/// Match the text against the pattern, from the start of the text, and up to its end if
+/// `end_anchor` is set.
+fn glob_match(pattern: &[u8], text: &[u8], end_anchor: bool) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Where to continue after the last `*` if the rest doesn't match.
+    let mut backtrack = None;
+    loop {
+        if p == pattern.len() && (!end_anchor || t == text.len()) {
+            return true;
+        }
+        if p < pattern.len() {
+            if pattern[p] == b'*' {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            if t < text.len() && byte_matches(pattern[p], text[t]) {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            // A separator also matches the end of the URL.
+            if t == text.len() && pattern[p] == b'^' {
+                p += 1;
+                continue;
+            }
+        }
+        match backtrack {
+            Some((star, start)) if start < text.len() => {
+                backtrack = Some((star, start + 1));
+                p = star + 1;
+                t = start + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+fn byte_matches(pattern: u8, byte: u8) -> bool {
+    if pattern == b'^' {
+        !(byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-' | b'.' | b'%'))
+    } else {
+        pattern == byte
+    }
+}
+
+/// Whether a request to `host` from a page on `page_host` goes to another site.
+fn is_third_party(host: &str, page_host: &str) -> bool {
+    site(host) != site(page_host)
+}
+
+/// The part of a host that identifies the site, such as `example.co.uk` for `www.example.co.uk`.
+/// This is an approximation that doesn't use the public suffix list.
+fn site(host: &str) -> &str {
+    let host = host.trim_end_matches('.');
+    if host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok() {
+        return host;
+    }
+    let labels: Vec<&str> = host.rsplitn(4, '.').collect();
+    // Country code domains often have a second level such as `co.uk`.
+    let suffix_labels = match labels.as_slice() {
+        [tld, second, ..]
+            if tld.len() == 2
+                && matches!(
+                    *second,
+                    "co" | "com" | "org" | "net" | "gov" | "edu" | "ac" | "or" | "ne"
+                ) =>
+        {
+            2
+        }
+        _ => 1,
+    };
+    if labels.len() <= suffix_labels + 1 {
+        return host;
+    }
+    let site_length: usize = labels[..=suffix_labels]
+        .iter()
+        .map(|label| label.len() + 1)
+        .sum::<usize>()
+        - 1;
+    &host[host.len() - site_length..]
+}
+
+/// The position of the host in a URL, for the `||` anchor.
+fn host_range(url: &Url) -> std::ops::Range<usize> {
+    url[..Position::BeforeHost].len()..url[..Position::AfterHost].len()
+}
@@ -0,0 +1,62 @@
+//! Forcing a dark theme onto pages that don't have one, by inverting their colours.
+//!
+//! The page is inverted with a CSS filter, and images and videos are inverted back so that
+//! they look as they should. Pages that are dark already, because they follow
+//! `prefers-color-scheme` or simply have a dark background, are left alone. This is checked
+//! when the head of the page has been parsed and again once it has loaded, as its style sheets
+//! may not have been applied the first time.
+
+/// The setting that decides whether pages are darkened at all.
+pub const FORCED_DARK_MODE_SETTING: &str = "forced_dark_mode";
+
+const FORCED_DARK_MODE_SCRIPT: &str = r#"(() => {
+    const id = "prism-forced-dark-mode";
+    let style = document.getElementById(id);
+    style?.remove();
+    if (!ENABLED) {
+        return;
+    }
+
+    // The luminance of an element's background, or null if it is transparent.
+    const luminance = (element) => {
+        if (!element) {
+            return null;
+        }
+        const channels = getComputedStyle(element).backgroundColor.match(/[\d.]+/g);
+        if (!channels || (channels.length > 3 && Number(channels[3]) === 0)) {
+            return null;
+        }
+        const [red, green, blue] = channels.map(Number);
+        return (0.2126 * red + 0.7152 * green + 0.0722 * blue) / 255;
+    };
+    let background = luminance(document.body) ?? luminance(document.documentElement);
+    if (background === null) {
+        // The page shows the canvas, which is dark if the page asks for a dark colour scheme.
+        const colorScheme = getComputedStyle(document.documentElement).colorScheme ?? "";
+        const prefersDark = matchMedia("(prefers-color-scheme: dark)").matches;
+        background = colorScheme.includes("dark") && prefersDark ? 0 : 1;
+    }
+    if (background < 0.5) {
+        return;
+    }
+
+    if (!style) {
+        style = document.createElement("style");
+        style.id = id;
+        style.textContent = `
+            html {
+                filter: invert(1) hue-rotate(180deg) !important;
+                background-color: white !important;
+            }
+            img, picture, video, canvas, embed, object, iframe, svg image,
+            [style*="background-image"] {
+                filter: invert(1) hue-rotate(180deg) !important;
+            }`;
+    }
+    document.documentElement.appendChild(style);
+})()"#;
+
+/// A script that darkens the page if `enabled` and the page is light, or undoes that if not.
+pub fn forced_dark_mode_script(enabled: bool) -> String {
+    FORCED_DARK_MODE_SCRIPT.replace("ENABLED", &enabled.to_string())
+}
@@ -0,0 +1,48 @@
+//! HTTPS-only mode, which loads sites over HTTPS even when they are linked to over HTTP.
+//!
+//! Sites that can't be loaded over HTTPS show the network error page, which offers to load the
+//! site over HTTP after all if it was upgraded, making an exception for it.
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::{LazyLock, Mutex};
+
+use url::Url;
+
+/// The setting that decides whether HTTPS-only mode is enabled.
+pub const HTTPS_ONLY_SETTING: &str = "https_only";
+
+/// The hosts that have been upgraded to HTTPS in this session.
+static UPGRADED_HOSTS: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// The HTTPS URL to load instead of the given HTTP URL, or `None` if it shouldn't be upgraded.
+///
+/// Local servers rarely support HTTPS, so loopback addresses and URLs with an explicit port
+/// other than 80, which are mostly development servers, are left alone.
+pub fn upgrade_url(url: &Url) -> Option<Url> {
+    if url.scheme() != "http" || url.port().is_some() {
+        return None;
+    }
+    let host = url.host_str()?;
+    if host == "localhost" || host.ends_with(".localhost") {
+        return None;
+    }
+    if let Ok(address) = host.trim_matches(['[', ']']).parse::<IpAddr>()
+        && address.is_loopback()
+    {
+        return None;
+    }
+    let mut upgraded = url.clone();
+    upgraded.set_scheme("https").ok()?;
+    Some(upgraded)
+}
+
+/// Remember that the host was upgraded, so that the error page can offer to go back to HTTP.
+pub fn record_upgrade(host: &str) {
+    UPGRADED_HOSTS.lock().unwrap().insert(host.to_owned());
+}
+
+/// Whether the host was upgraded to HTTPS in this session.
+pub fn was_upgraded(host: &str) -> bool {
+    UPGRADED_HOSTS.lock().unwrap().contains(host)
+}
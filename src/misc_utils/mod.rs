@@ -1,4 +1,17 @@
 pub mod accelerated_gl_media;
+pub mod ca_certificates;
+pub mod certificates;
+pub mod content_blocker;
+pub mod dark_mode;
 pub mod gamepad;
+pub mod https_only;
+pub mod opensearch;
+pub mod reader_mode;
+pub mod redirects;
+pub mod save_page;
+pub mod screenshot;
+pub mod search_suggestions;
+pub mod user_scripts;
+pub mod user_stylesheets;
 #[cfg(feature = "webxr")]
 pub mod webxr;
@@ -0,0 +1,257 @@
+//! Discovery and parsing of OpenSearch descriptions, which sites use to advertise their search
+//! engine. See <https://github.com/dewitt/opensearch/blob/master/opensearch-1-1-draft-6.md>.
+
+use std::fmt;
+
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use servo::{JSValue, WebView};
+use url::Url;
+
+/// Finds the `<link rel="search">` elements of the page, returning their absolute URLs and
+/// titles as `[url, title]` pairs.
+const DISCOVER_SEARCH_ENGINES_SCRIPT: &str = r#"Array.from(
+    document.querySelectorAll("link[rel~='search'][type='application/opensearchdescription+xml'][href]"),
+    (link) => [link.href, link.title || document.title]
+)"#;
+
+const SEARCH_RESULTS_TYPE: &str = "text/html";
+const SUGGESTIONS_TYPE: &str = "application/x-suggestions+json";
+
+/// A search engine that a page advertises, before its description has been fetched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdvertisedSearchEngine {
+    pub title: String,
+    pub description_url: Url,
+}
+
+/// The parts of an OpenSearch description that we use, with URL templates converted to the
+/// `%s` form used by the search engine registry.
+#[derive(Debug, PartialEq)]
+pub struct OpenSearchDescription {
+    pub short_name: String,
+    pub description: Option<String>,
+    pub search_url: String,
+    pub suggestions_url: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OpenSearchError {
+    Xml(String),
+    NotOpenSearch,
+    MissingShortName,
+    /// There is no `text/html` URL template that can be searched with a GET request.
+    MissingSearchUrl,
+    InvalidTemplate(String),
+}
+
+impl fmt::Display for OpenSearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenSearchError::Xml(error) => write!(f, "The description is not valid XML: {error}"),
+            OpenSearchError::NotOpenSearch => write!(f, "This is not an OpenSearch description"),
+            OpenSearchError::MissingShortName => write!(f, "The search engine has no name"),
+            OpenSearchError::MissingSearchUrl => {
+                write!(f, "The search engine has no search results page")
+            }
+            OpenSearchError::InvalidTemplate(template) => {
+                write!(f, "Unsupported URL template \"{template}\"")
+            }
+        }
+    }
+}
+
+/// Find the search engines advertised by the page shown in the given [`WebView`].
+pub fn discover_search_engines(
+    webview: &WebView,
+    callback: impl FnOnce(Vec<AdvertisedSearchEngine>) + 'static,
+) {
+    webview.evaluate_javascript(DISCOVER_SEARCH_ENGINES_SCRIPT, move |result| {
+        let Ok(JSValue::Array(links)) = result else {
+            return callback(vec![]);
+        };
+        let search_engines = links
+            .into_iter()
+            .filter_map(|link| {
+                let JSValue::Array(parts) = link else {
+                    return None;
+                };
+                let [JSValue::String(url), JSValue::String(title)] = &parts[..] else {
+                    return None;
+                };
+                Some(AdvertisedSearchEngine {
+                    title: title.clone(),
+                    description_url: Url::parse(url).ok()?,
+                })
+            })
+            .collect();
+        callback(search_engines)
+    });
+}
+
+/// Parse an OpenSearch description, resolving relative URLs against the URL it was loaded from.
+pub fn parse_opensearch_description(
+    xml: &str,
+    description_url: &Url,
+) -> Result<OpenSearchDescription, OpenSearchError> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut found_root = false;
+    let mut current_element: Option<String> = None;
+    let mut short_name = None;
+    let mut description = None;
+    let mut search_url = None;
+    let mut suggestions_url = None;
+    let mut icons: Vec<(u32, String)> = vec![];
+    let mut icon_size = None;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|error| OpenSearchError::Xml(error.to_string()))?;
+        match event {
+            Event::Start(ref element) | Event::Empty(ref element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                if !found_root {
+                    if name != "OpenSearchDescription" {
+                        return Err(OpenSearchError::NotOpenSearch);
+                    }
+                    found_root = true;
+                    continue;
+                }
+                match name.as_str() {
+                    "Url" => {
+                        let mime_type = attribute(element, "type").unwrap_or_default();
+                        let method = attribute(element, "method").unwrap_or_else(|| "get".into());
+                        let rel = attribute(element, "rel").unwrap_or_else(|| "results".into());
+                        let Some(template) = attribute(element, "template") else {
+                            continue;
+                        };
+                        if !method.eq_ignore_ascii_case("get") {
+                            continue;
+                        }
+                        if mime_type == SEARCH_RESULTS_TYPE && rel == "results" {
+                            if search_url.is_none() {
+                                search_url = Some(convert_template(&template, description_url)?);
+                            }
+                        } else if mime_type == SUGGESTIONS_TYPE && suggestions_url.is_none() {
+                            suggestions_url = convert_template(&template, description_url).ok();
+                        }
+                    }
+                    "Image" => {
+                        let width =
+                            attribute(element, "width").and_then(|width| width.parse().ok());
+                        icon_size = Some(width.unwrap_or(0));
+                    }
+                    _ => {}
+                }
+                if matches!(event, Event::Start(_)) {
+                    current_element = Some(name);
+                }
+            }
+            Event::Text(ref text) => {
+                let text = text
+                    .unescape()
+                    .map_err(|error| OpenSearchError::Xml(error.to_string()))?
+                    .trim()
+                    .to_owned();
+                match current_element.as_deref() {
+                    Some("ShortName") => short_name = Some(text),
+                    Some("Description") => description = Some(text),
+                    Some("Image") => {
+                        if let Ok(icon_url) = description_url.join(&text) {
+                            icons.push((icon_size.unwrap_or(0), icon_url.into()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(_) => {
+                current_element = None;
+                icon_size = None;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !found_root {
+        return Err(OpenSearchError::NotOpenSearch);
+    }
+    let short_name = short_name
+        .filter(|short_name| !short_name.is_empty())
+        .ok_or(OpenSearchError::MissingShortName)?;
+    let search_url = search_url.ok_or(OpenSearchError::MissingSearchUrl)?;
+
+    // Prefer the icon closest to the 16 pixels they are shown at, then the first one listed.
+    icons.sort_by_key(|(width, _)| {
+        if *width == 0 {
+            u32::MAX
+        } else {
+            width.abs_diff(16)
+        }
+    });
+    let icon_url = icons.into_iter().next().map(|(_, icon_url)| icon_url);
+
+    Ok(OpenSearchDescription {
+        short_name,
+        description,
+        search_url,
+        suggestions_url,
+        icon_url,
+    })
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .attributes()
+        .filter_map(Result::ok)
+        .find(|attribute| attribute.key.local_name().as_ref() == name.as_bytes())
+        .and_then(|attribute| attribute.unescape_value().ok())
+        .map(|value| value.into_owned())
+}
+
+/// Convert an OpenSearch URL template to the `%s` form, filling in the parameters that we can
+/// and dropping the optional ones that we can't.
+fn convert_template(template: &str, description_url: &Url) -> Result<String, OpenSearchError> {
+    let invalid = || OpenSearchError::InvalidTemplate(template.to_owned());
+    let mut converted = String::new();
+    let mut has_search_terms = false;
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        converted.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(invalid)? + start;
+        let parameter = &rest[start + 1..end];
+        let (parameter, optional) = match parameter.strip_suffix('?') {
+            Some(parameter) => (parameter, true),
+            None => (parameter, false),
+        };
+        // Parameters can have a namespace prefix, which we don't need to tell them apart.
+        let parameter = parameter.rsplit(':').next().unwrap_or(parameter);
+        match parameter {
+            "searchTerms" => {
+                converted.push_str("%s");
+                has_search_terms = true;
+            }
+            "inputEncoding" | "outputEncoding" => converted.push_str("UTF-8"),
+            "language" => converted.push('*'),
+            "startIndex" | "startPage" => converted.push('1'),
+            "count" => converted.push_str("20"),
+            _ if optional => {}
+            _ => return Err(invalid()),
+        }
+        rest = &rest[end + 1..];
+    }
+    converted.push_str(rest);
+
+    if !has_search_terms {
+        return Err(invalid());
+    }
+    // Resolve relative templates, taking care that the placeholder survives URL parsing.
+    let resolved = description_url
+        .join(&converted.replace("%s", "SEARCHTERMS"))
+        .map_err(|_| invalid())?;
+    Ok(resolved.as_str().replace("SEARCHTERMS", "%s"))
+}
@@ -0,0 +1,935 @@
+//! Reader mode, which shows the text of an article on a plain page of its own.
+//!
+//! The article is found much as Mozilla's Readability finds it. Paragraphs are scored by their
+//! length and their number of commas, and the scores are added to the elements that contain
+//! them, less the share of each element's text that is links. The element with the highest
+//! score is taken to be the article, along with those of its siblings that look like part of
+//! it. The content is then written out again using only a few elements and attributes, so that
+//! nothing from the page but its text, links and images ends up on the reader page.
+//!
+//! Whether a page has enough text to be an article at all is checked in the page itself, and
+//! only those that do are serialized and searched for the article, on a worker thread.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use ego_tree::NodeId;
+use log::warn;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+use servo::{JSValue, WebView, WebViewId};
+use url::Url;
+use winit::event_loop::EventLoopProxy;
+use winit::window::WindowId;
+
+use crate::event_loop::AppEvent;
+use crate::prism_protocol::READER_PAGE_URL;
+
+/// The setting that holds the font, width and theme of the reader page, as JSON.
+pub const READER_SETTINGS_SETTING: &str = "reader_mode";
+
+/// Articles with less text than this, in characters, are not worth reading in reader mode.
+const MIN_ARTICLE_LENGTH: usize = 500;
+
+/// Paragraphs shorter than this don't add to the score of the elements that contain them.
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// Paragraphs shorter than this don't count towards a page being probably an article.
+const MIN_READERABLE_PARAGRAPH_LENGTH: usize = 140;
+
+/// The score that the paragraphs of a page need to make it probably an article.
+const MIN_READERABLE_SCORE: f64 = 20.0;
+
+/// How many of the articles opened in reader mode are kept for the reader page to show.
+const MAX_OPENED_ARTICLES: usize = 16;
+
+/// How many words a minute the reading time is worked out for.
+const WORDS_PER_MINUTE: usize = 220;
+
+/// Elements that are never part of an article.
+const REMOVED_ELEMENTS: &[&str] = &[
+    "aside", "button", "canvas", "dialog", "embed", "footer", "form", "head", "iframe", "input",
+    "link", "meta", "nav", "noscript", "object", "script", "select", "style", "svg", "template",
+    "textarea",
+];
+
+/// Elements that are written out as they are, without any attributes but those that
+/// [`allowed_attributes`] keeps.
+const KEPT_ELEMENTS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "caption",
+    "cite",
+    "code",
+    "dd",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "small",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+
+/// Elements that are written out as a `<div>`, or as a `<p>` if they only hold text.
+const CONTAINER_ELEMENTS: &[&str] = &["article", "center", "div", "header", "main", "section"];
+
+const VOID_ELEMENTS: &[&str] = &["br", "hr", "img"];
+
+/// Elements that start a new block, which a `<div>` that acts as a paragraph doesn't have.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Elements that are never taken for something other than content.
+const LIKELY_CANDIDATE_ELEMENTS: &[&str] = &["a", "article", "body", "html", "main"];
+
+/// The roles of elements that are not part of the content.
+const UNLIKELY_ROLES: &[&str] = &[
+    "alert",
+    "alertdialog",
+    "complementary",
+    "dialog",
+    "menu",
+    "menubar",
+    "navigation",
+];
+
+/// Classes and IDs of elements that are not part of the content, unless they also match
+/// [`MAYBE_CANDIDATE_PATTERN`]. These are matched without regard to case, both here and by
+/// [`READERABLE_SCRIPT`].
+const UNLIKELY_CANDIDATE_PATTERN: &str = "-ad-|ad-break|agegate|banner|breadcrumbs|combx|comment|community|cover-wrap|disqus|extra|footer|gdpr|header|legends|menu|related|remark|replies|rss|shoutbox|sidebar|skyscraper|social|sponsor|supplemental|pager|pagination|popup|yom-remote";
+
+const MAYBE_CANDIDATE_PATTERN: &str = "and|article|body|column|content|main|shadow";
+
+static UNLIKELY_CANDIDATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!("(?i){UNLIKELY_CANDIDATE_PATTERN}")).unwrap());
+
+static MAYBE_CANDIDATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!("(?i){MAYBE_CANDIDATE_PATTERN}")).unwrap());
+
+static POSITIVE_CLASS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)article|body|content|entry|hentry|h-entry|main|page|post|text|blog|story")
+        .unwrap()
+});
+
+static NEGATIVE_CLASS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)-ad-|hidden|^hid$|banner|combx|comment|com-|contact|footer|gdpr|masthead|media|meta|outbrain|promo|related|scroll|share|shoutbox|sidebar|skyscraper|sponsor|shopping|tags|widget",
+    )
+    .unwrap()
+});
+
+static BYLINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)byline|author|dateline|writtenby|p-author").unwrap());
+
+/// The articles opened in reader mode, most recent last, which the reader page asks for by URL.
+static OPENED_ARTICLES: LazyLock<Mutex<VecDeque<Article>>> = LazyLock::new(Default::default);
+
+/// The main content of a page, ready to be shown in reader mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Article {
+    pub url: Url,
+    pub title: String,
+    pub byline: Option<String>,
+    pub site_name: Option<String>,
+    /// The content as HTML, which only uses a few elements and attributes, with the URLs of its
+    /// links and images made absolute.
+    pub content: String,
+    pub word_count: usize,
+}
+
+impl Article {
+    /// Find the article in the HTML of the page at the given URL, or `None` if the page doesn't
+    /// look like an article.
+    pub fn extract(html: &str, url: &Url) -> Option<Article> {
+        let document = Html::parse_document(html);
+        if !is_probably_readerable(&document) {
+            return None;
+        }
+        let base_url = select_first(&document, "base[href]")
+            .and_then(|base| url.join(base.attr("href")?).ok())
+            .unwrap_or_else(|| url.clone());
+        let body = select_first(&document, "body")?;
+        let byline_element = find_byline_element(body);
+
+        let scores = score_candidates(body);
+        let (top_candidate, top_score) = scores
+            .values()
+            .copied()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        let top_candidate = widen_candidate(top_candidate, body);
+
+        let title = article_title(&document).unwrap_or_else(|| url.to_string());
+        let mut writer = ContentWriter {
+            base_url: &base_url,
+            title: &title,
+            skipped: byline_element.map(|element| element.id()),
+            content: String::new(),
+            text: String::new(),
+        };
+        for part in article_parts(top_candidate, top_score, &scores) {
+            writer.write_element(part, part == top_candidate);
+        }
+        let ContentWriter { content, text, .. } = writer;
+        if text.chars().count() < MIN_ARTICLE_LENGTH {
+            return None;
+        }
+
+        let byline = meta_content(&document, &["author", "article:author"])
+            .filter(|author| Url::parse(author).is_err())
+            .or_else(|| byline_element.map(inner_text));
+        Some(Article {
+            url: url.clone(),
+            title,
+            byline,
+            site_name: meta_content(&document, &["og:site_name"]),
+            word_count: text.split_whitespace().count(),
+            content,
+        })
+    }
+
+    /// How long the article takes to read, in minutes.
+    pub fn reading_minutes(&self) -> usize {
+        self.word_count.div_ceil(WORDS_PER_MINUTE).max(1)
+    }
+}
+
+/// The same check as [`is_probably_readerable`], made in the page itself. It returns the HTML
+/// of the page if it is probably an article, and `null` if it isn't, so that most pages don't
+/// have to be serialized at all.
+const READERABLE_SCRIPT: &str = r#"(() => {
+    const removedElements = new Set(REMOVED_ELEMENTS);
+    const blockElements = new Set(BLOCK_ELEMENTS);
+    const likelyCandidateElements = new Set(LIKELY_CANDIDATE_ELEMENTS);
+    const unlikelyRoles = new Set(UNLIKELY_ROLES);
+    const unlikelyCandidate = new RegExp(UNLIKELY_CANDIDATE_PATTERN, "i");
+    const maybeCandidate = new RegExp(MAYBE_CANDIDATE_PATTERN, "i");
+
+    const isExcluded = (element) => {
+        for (let node = element; node; node = node.parentElement) {
+            if (removedElements.has(node.localName) || node.hasAttribute("hidden") ||
+                node.getAttribute("aria-hidden") === "true") {
+                return true;
+            }
+            const style = (node.getAttribute("style") ?? "").replaceAll(" ", "").toLowerCase();
+            if (style.includes("display:none") || style.includes("visibility:hidden")) {
+                return true;
+            }
+            if (likelyCandidateElements.has(node.localName)) {
+                continue;
+            }
+            if (unlikelyRoles.has(node.getAttribute("role"))) {
+                return true;
+            }
+            const classAndId = `${node.getAttribute("class") ?? ""} ${node.id}`;
+            if (unlikelyCandidate.test(classAndId) && !maybeCandidate.test(classAndId)) {
+                return true;
+            }
+        }
+        return false;
+    };
+
+    let score = 0;
+    for (const element of document.querySelectorAll("p, pre, article, div")) {
+        if (element.localName === "div" &&
+            [...element.children].some((child) => blockElements.has(child.localName))) {
+            continue;
+        }
+        if (isExcluded(element)) {
+            continue;
+        }
+        const length = [...element.textContent.trim().replace(/\s+/g, " ")].length;
+        if (length < MIN_READERABLE_PARAGRAPH_LENGTH) {
+            continue;
+        }
+        score += Math.sqrt(length - MIN_READERABLE_PARAGRAPH_LENGTH);
+        if (score > MIN_READERABLE_SCORE) {
+            return document.documentElement.outerHTML;
+        }
+    }
+    return null;
+})()"#;
+
+static FILLED_IN_READERABLE_SCRIPT: LazyLock<String> = LazyLock::new(|| {
+    let json = |value: serde_json::Value| value.to_string();
+    READERABLE_SCRIPT
+        .replace("REMOVED_ELEMENTS", &json(REMOVED_ELEMENTS.into()))
+        .replace("BLOCK_ELEMENTS", &json(BLOCK_ELEMENTS.into()))
+        .replace(
+            "LIKELY_CANDIDATE_ELEMENTS",
+            &json(LIKELY_CANDIDATE_ELEMENTS.into()),
+        )
+        .replace("UNLIKELY_ROLES", &json(UNLIKELY_ROLES.into()))
+        .replace(
+            "UNLIKELY_CANDIDATE_PATTERN",
+            &json(UNLIKELY_CANDIDATE_PATTERN.into()),
+        )
+        .replace(
+            "MAYBE_CANDIDATE_PATTERN",
+            &json(MAYBE_CANDIDATE_PATTERN.into()),
+        )
+        .replace(
+            "MIN_READERABLE_PARAGRAPH_LENGTH",
+            &MIN_READERABLE_PARAGRAPH_LENGTH.to_string(),
+        )
+        .replace("MIN_READERABLE_SCORE", &MIN_READERABLE_SCORE.to_string())
+});
+
+/// A page whose article is to be found on the worker thread.
+struct ArticleRequest {
+    webview_id: WebViewId,
+    url: Url,
+    html: String,
+}
+
+/// The article found in the page at `url` in a [`WebView`], if it has one.
+pub struct ArticleResult {
+    pub webview_id: WebViewId,
+    pub url: Url,
+    pub article: Option<Article>,
+}
+
+/// Finds the articles in pages on a worker thread, as parsing and scoring a large page would
+/// make the user interface stutter. Only pages that [`READERABLE_SCRIPT`] takes for articles
+/// are sent there.
+pub struct ArticleFinder {
+    requests: Sender<ArticleRequest>,
+    results: Receiver<ArticleResult>,
+}
+
+impl ArticleFinder {
+    pub fn new(event_loop_proxy: EventLoopProxy<AppEvent>, window_id: WindowId) -> Self {
+        let (requests, request_receiver) = unbounded::<ArticleRequest>();
+        let (result_sender, results) = unbounded();
+        thread::Builder::new()
+            .name("ReaderMode".into())
+            .spawn(move || {
+                for request in request_receiver {
+                    let article = Article::extract(&request.html, &request.url);
+                    let result = ArticleResult {
+                        webview_id: request.webview_id,
+                        url: request.url,
+                        article,
+                    };
+                    if result_sender.send(result).is_err() {
+                        break;
+                    }
+                    if let Err(error) =
+                        event_loop_proxy.send_event(AppEvent::RedrawWindow { window_id })
+                    {
+                        warn!("Could not wake the event loop for reader mode: {error}");
+                    }
+                }
+            })
+            .expect("Could not start reader mode thread");
+        Self { requests, results }
+    }
+
+    /// Start looking for the article in the page shown in the given [`WebView`]. The result
+    /// is among [`Self::results`] once it is found.
+    pub fn find_article(&self, webview: &WebView) {
+        let Some(url) = webview
+            .url()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+        else {
+            return;
+        };
+        let webview_id = webview.id();
+        let requests = self.requests.clone();
+        webview.evaluate_javascript(FILLED_IN_READERABLE_SCRIPT.as_str(), move |result| {
+            if let Ok(JSValue::String(html)) = result {
+                let _ = requests.send(ArticleRequest {
+                    webview_id,
+                    url,
+                    html,
+                });
+            }
+        });
+    }
+
+    /// The articles that have been found since this was last called.
+    pub fn results(&self) -> impl Iterator<Item = ArticleResult> + '_ {
+        self.results.try_iter()
+    }
+}
+
+/// Keep the article for the reader page, returning the URL of the reader page that shows it.
+pub fn open_article(article: Article) -> Url {
+    let url = reader_page_url(&article.url);
+    let mut articles = OPENED_ARTICLES.lock().unwrap();
+    articles.retain(|opened| opened.url != article.url);
+    if articles.len() == MAX_OPENED_ARTICLES {
+        articles.pop_front();
+    }
+    articles.push_back(article);
+    url
+}
+
+/// The article with the given URL, if it has been opened in reader mode in this session.
+pub fn opened_article(url: &str) -> Option<Article> {
+    OPENED_ARTICLES
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|article| article.url.as_str() == url)
+        .cloned()
+}
+
+/// The URL of the reader page that shows the article at the given URL.
+pub fn reader_page_url(article_url: &Url) -> Url {
+    Url::parse_with_params(READER_PAGE_URL, [("url", article_url.as_str())]).unwrap()
+}
+
+/// The URL of the article shown on a reader page, or `None` if this is not a reader page.
+/// Articles are only taken from web pages, so other schemes are never returned.
+pub fn original_url(url: &Url) -> Option<Url> {
+    if url.as_str().split('?').next() != Some(READER_PAGE_URL) {
+        return None;
+    }
+    url.query_pairs()
+        .find(|(name, _)| name == "url")
+        .and_then(|(_, article_url)| Url::parse(&article_url).ok())
+        .filter(|article_url| matches!(article_url.scheme(), "http" | "https"))
+}
+
+/// Whether the page has enough paragraphs of text to be an article, which is much quicker to
+/// tell than finding the article itself.
+pub fn is_probably_readerable(document: &Html) -> bool {
+    let paragraphs = document
+        .root_element()
+        .descendent_elements()
+        .filter(|element| match element.value().name() {
+            "p" | "pre" | "article" => true,
+            "div" => !has_block_children(*element),
+            _ => false,
+        });
+    let mut score = 0.0;
+    for element in paragraphs {
+        if is_excluded(element) {
+            continue;
+        }
+        let length = inner_text(element).chars().count();
+        if length < MIN_READERABLE_PARAGRAPH_LENGTH {
+            continue;
+        }
+        score += ((length - MIN_READERABLE_PARAGRAPH_LENGTH) as f64).sqrt();
+        if score > MIN_READERABLE_SCORE {
+            return true;
+        }
+    }
+    false
+}
+
+fn select_first<'a>(document: &'a Html, selector: &str) -> Option<ElementRef<'a>> {
+    document.select(&Selector::parse(selector).unwrap()).next()
+}
+
+/// The content of the first `<meta>` element with one of the given names or properties.
+fn meta_content(document: &Html, names: &[&str]) -> Option<String> {
+    let selector = Selector::parse("meta[content]").unwrap();
+    names.iter().find_map(|name| {
+        document
+            .select(&selector)
+            .find(|meta| {
+                meta.attr("name")
+                    .or_else(|| meta.attr("property"))
+                    .is_some_and(|value| value.eq_ignore_ascii_case(name))
+            })
+            .map(|meta| collapse_whitespace(meta.attr("content").unwrap()))
+            .filter(|content| !content.is_empty())
+    })
+}
+
+/// The title of the article, without the name of the site that is often added to the
+/// `<title>` of a page.
+fn article_title(document: &Html) -> Option<String> {
+    if let Some(title) = meta_content(document, &["og:title", "twitter:title"]) {
+        return Some(title);
+    }
+    let Some(title) = select_first(document, "title")
+        .map(inner_text)
+        .filter(|title| !title.is_empty())
+    else {
+        return select_first(document, "h1").map(inner_text);
+    };
+    let without_site_name = [" | ", " - ", " – ", " — ", " :: ", " / "]
+        .iter()
+        .filter_map(|separator| title.rfind(separator))
+        .max()
+        .map(|position| title[..position].trim());
+    match without_site_name {
+        Some(shorter) if shorter.split_whitespace().count() >= 3 => Some(shorter.to_owned()),
+        _ => Some(title),
+    }
+}
+
+/// The element that names the author of the article, which is shown above the article rather
+/// than in it.
+fn find_byline_element(body: ElementRef) -> Option<ElementRef> {
+    body.descendent_elements().find(|element| {
+        let is_byline = element.attr("rel") == Some("author")
+            || element
+                .attr("itemprop")
+                .is_some_and(|prop| prop.contains("author"))
+            || BYLINE.is_match(&class_and_id(*element));
+        is_byline && !is_excluded(*element) && {
+            let length = inner_text(*element).chars().count();
+            length > 0 && length < 100
+        }
+    })
+}
+
+/// Score the elements that contain paragraphs, returning them with their scores by node.
+fn score_candidates(body: ElementRef) -> HashMap<NodeId, (ElementRef, f64)> {
+    let mut scores = HashMap::new();
+    for paragraph in body
+        .descendent_elements()
+        .filter(|element| is_paragraph(*element))
+    {
+        if is_excluded(paragraph) {
+            continue;
+        }
+        let text = inner_text(paragraph);
+        let length = text.chars().count();
+        if length < MIN_PARAGRAPH_LENGTH {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (length / 100).min(3) as f64;
+        let ancestors = paragraph
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .take_while(|ancestor| ancestor.value().name() != "html")
+            .take(5);
+        for (level, ancestor) in ancestors.enumerate() {
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                level => level as f64 * 3.0,
+            };
+            scores
+                .entry(ancestor.id())
+                .or_insert_with(|| (ancestor, initial_score(ancestor)))
+                .1 += score / divider;
+        }
+    }
+    for (element, score) in scores.values_mut() {
+        *score *= 1.0 - link_density(*element);
+    }
+    scores
+}
+
+/// The score an element starts with, from what kind of element it is and its class and ID.
+fn initial_score(element: ElementRef) -> f64 {
+    let score = match element.value().name() {
+        "div" | "article" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    score + class_weight(element)
+}
+
+fn class_weight(element: ElementRef) -> f64 {
+    [element.attr("class"), element.attr("id")]
+        .into_iter()
+        .flatten()
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            let mut weight = 0.0;
+            if NEGATIVE_CLASS.is_match(value) {
+                weight -= 25.0;
+            }
+            if POSITIVE_CLASS.is_match(value) {
+                weight += 25.0;
+            }
+            weight
+        })
+        .sum()
+}
+
+/// Whether the element is a paragraph of text, including a `<div>` that holds text without
+/// any blocks in it.
+fn is_paragraph(element: ElementRef) -> bool {
+    match element.value().name() {
+        "p" | "pre" | "td" => true,
+        "div" | "section" => !has_block_children(element),
+        _ => false,
+    }
+}
+
+fn has_block_children(element: ElementRef) -> bool {
+    element
+        .child_elements()
+        .any(|child| BLOCK_ELEMENTS.contains(&child.value().name()))
+}
+
+/// Use the parent of the best candidate instead while the candidate is all there is in it, so
+/// that the siblings of the article that are part of it can be found.
+fn widen_candidate<'a>(mut candidate: ElementRef<'a>, body: ElementRef<'a>) -> ElementRef<'a> {
+    while candidate != body {
+        let Some(parent) = candidate.parent().and_then(ElementRef::wrap) else {
+            break;
+        };
+        if parent.child_elements().count() != 1 {
+            break;
+        }
+        candidate = parent;
+    }
+    candidate
+}
+
+/// The best candidate and those of its siblings that look like part of the article, such as
+/// paragraphs that are split off from it and elements that score nearly as well.
+fn article_parts<'a>(
+    top_candidate: ElementRef<'a>,
+    top_score: f64,
+    scores: &HashMap<NodeId, (ElementRef, f64)>,
+) -> Vec<ElementRef<'a>> {
+    let Some(parent) = top_candidate.parent().and_then(ElementRef::wrap) else {
+        return vec![top_candidate];
+    };
+    let threshold = (top_score * 0.2).max(10.0);
+    let top_class = top_candidate
+        .attr("class")
+        .filter(|class| !class.is_empty());
+    parent
+        .child_elements()
+        .filter(|sibling| {
+            if *sibling == top_candidate {
+                return true;
+            }
+            if is_excluded(*sibling) {
+                return false;
+            }
+            let mut score = scores.get(&sibling.id()).map_or(0.0, |(_, score)| *score);
+            if top_class.is_some() && sibling.attr("class") == top_class {
+                score += top_score * 0.2;
+            }
+            if score >= threshold {
+                return true;
+            }
+            if sibling.value().name() != "p" {
+                return false;
+            }
+            let text = inner_text(*sibling);
+            let length = text.chars().count();
+            let density = link_density(*sibling);
+            (length > 80 && density < 0.25)
+                || (length > 0 && density == 0.0 && (text.ends_with('.') || text.contains(". ")))
+        })
+        .collect()
+}
+
+/// Whether the element or one of its ancestors is hidden, or is something other than the
+/// content of the page, such as navigation or comments.
+fn is_excluded(element: ElementRef) -> bool {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .chain([element])
+        .any(|element| {
+            REMOVED_ELEMENTS.contains(&element.value().name())
+                || is_hidden(element)
+                || is_unlikely_candidate(element)
+        })
+}
+
+fn is_hidden(element: ElementRef) -> bool {
+    if element.attr("hidden").is_some() || element.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    element.attr("style").is_some_and(|style| {
+        let style = style.replace(' ', "").to_ascii_lowercase();
+        style.contains("display:none") || style.contains("visibility:hidden")
+    })
+}
+
+/// Whether the class, ID or role of the element says that it is not part of the content.
+fn is_unlikely_candidate(element: ElementRef) -> bool {
+    if LIKELY_CANDIDATE_ELEMENTS.contains(&element.value().name()) {
+        return false;
+    }
+    if element
+        .attr("role")
+        .is_some_and(|role| UNLIKELY_ROLES.contains(&role))
+    {
+        return true;
+    }
+    let class_and_id = class_and_id(element);
+    UNLIKELY_CANDIDATE.is_match(&class_and_id) && !MAYBE_CANDIDATE.is_match(&class_and_id)
+}
+
+fn class_and_id(element: ElementRef) -> String {
+    format!(
+        "{} {}",
+        element.attr("class").unwrap_or_default(),
+        element.attr("id").unwrap_or_default()
+    )
+}
+
+/// The share of the text of the element that is the text of links.
+fn link_density(element: ElementRef) -> f64 {
+    let length = inner_text(element).chars().count();
+    if length == 0 {
+        return 0.0;
+    }
+    let link_length: usize = element
+        .descendent_elements()
+        .filter(|descendant| descendant.value().name() == "a")
+        .map(|link| inner_text(link).chars().count())
+        .sum();
+    link_length as f64 / length as f64
+}
+
+fn inner_text(element: ElementRef) -> String {
+    collapse_whitespace(&element.text().collect::<String>())
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether a list, table or container within the article looks like something other than
+/// content, such as a list of links or a block of share buttons.
+fn is_clutter(element: ElementRef) -> bool {
+    let weight = class_weight(element);
+    if weight < 0.0 {
+        return true;
+    }
+    let text = inner_text(element);
+    if text.matches(',').count() >= 10 {
+        return false;
+    }
+    let count = |name: &str| {
+        element
+            .descendent_elements()
+            .filter(|descendant| descendant.value().name() == name)
+            .count()
+    };
+    let is_list = matches!(element.value().name(), "ul" | "ol");
+    let paragraphs = count("p");
+    let images = count("img");
+    let list_items = count("li").saturating_sub(100);
+    let density = link_density(element);
+    let length = text.chars().count();
+    let in_figure = element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .any(|ancestor| ancestor.value().name() == "figure");
+
+    (images > 1 && (paragraphs as f64) / (images as f64) < 0.5 && !in_figure)
+        || (!is_list && list_items > paragraphs)
+        || (weight < 25.0 && density > 0.2 && !(is_list && length > 0 && density < 0.5))
+        || (weight >= 25.0 && density > 0.5)
+        || (!is_list && length < 25 && (images == 0 || images > 2) && !in_figure)
+}
+
+/// Writes the article out again with only the elements and attributes that reader mode shows.
+struct ContentWriter<'a> {
+    base_url: &'a Url,
+    title: &'a str,
+    /// The byline, which is left out as it is shown above the article.
+    skipped: Option<NodeId>,
+    content: String,
+    /// The text of the content, to tell how long the article is.
+    text: String,
+}
+
+impl ContentWriter<'_> {
+    fn write_element(&mut self, element: ElementRef, is_top_candidate: bool) {
+        let name = element.value().name();
+        if Some(element.id()) == self.skipped
+            || REMOVED_ELEMENTS.contains(&name)
+            || is_hidden(element)
+        {
+            return;
+        }
+        if !is_top_candidate
+            && (is_unlikely_candidate(element)
+                || (matches!(name, "div" | "section" | "header" | "ul" | "ol" | "table")
+                    && !(name == "table" && is_data_table(element))
+                    && is_clutter(element)))
+        {
+            return;
+        }
+
+        let tag = match name {
+            "h1" => {
+                // The title is shown above the article, so it is left out if the article
+                // repeats it.
+                if inner_text(element) == self.title {
+                    self.title = "";
+                    return;
+                }
+                "h2"
+            }
+            "h2" if inner_text(element) == self.title => {
+                self.title = "";
+                return;
+            }
+            "h2" | "h3" | "h4" | "h5" | "h6" if class_weight(element) < 0.0 => return,
+            _ if CONTAINER_ELEMENTS.contains(&name) => {
+                if has_block_children(element) {
+                    "div"
+                } else {
+                    "p"
+                }
+            }
+            _ if KEPT_ELEMENTS.contains(&name) => name,
+            // Anything else, such as a <span> or <font>, is replaced by what it holds.
+            _ => return self.write_children(element),
+        };
+
+        let attributes = match allowed_attributes(element, self.base_url) {
+            Some(attributes) => attributes,
+            // A link that doesn't go anywhere, such as a `javascript:` one, becomes its text,
+            // and an image without a source is left out.
+            None if tag == "a" => return self.write_children(element),
+            None => return,
+        };
+        self.content.push('<');
+        self.content.push_str(tag);
+        for (name, value) in attributes {
+            self.content
+                .push_str(&format!(" {name}=\"{}\"", escape_html(&value, true)));
+        }
+        self.content.push('>');
+        if VOID_ELEMENTS.contains(&tag) {
+            return;
+        }
+        if tag == "p" || tag == "li" || tag.starts_with('h') {
+            self.text.push(' ');
+        }
+        self.write_children(element);
+        self.content.push_str(&format!("</{tag}>"));
+    }
+
+    fn write_children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => {
+                    self.content.push_str(&escape_html(text, false));
+                    self.text.push_str(text);
+                }
+                Node::Element(_) => self.write_element(ElementRef::wrap(child).unwrap(), false),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The attributes that are kept on the element, with URLs made absolute, or `None` if a link
+/// or image has no URL that can be shown.
+fn allowed_attributes(element: ElementRef, base_url: &Url) -> Option<Vec<(&'static str, String)>> {
+    let resolve = |url: &str| {
+        base_url
+            .join(url.trim())
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https" | "mailto"))
+            .map(String::from)
+    };
+    let number = |name: &'static str| {
+        element
+            .attr(name)
+            .filter(|value| value.parse::<u32>().is_ok())
+            .map(|value| (name, value.to_owned()))
+    };
+    Some(match element.value().name() {
+        "a" => vec![("href", resolve(element.attr("href")?)?)],
+        "img" => {
+            // Images that are loaded lazily keep their real source in another attribute until
+            // they are scrolled to.
+            let source = ["src", "data-src", "data-original", "data-lazy-src"]
+                .iter()
+                .filter_map(|name| element.attr(name))
+                .find(|source| !source.trim().is_empty() && !source.starts_with("data:"))?;
+            let mut attributes = vec![("src", resolve(source)?)];
+            if let Some(alt) = element.attr("alt") {
+                attributes.push(("alt", alt.to_owned()));
+            }
+            attributes
+        }
+        "td" | "th" => [number("colspan"), number("rowspan")]
+            .into_iter()
+            .flatten()
+            .collect(),
+        "ol" => number("start").into_iter().collect(),
+        _ => vec![],
+    })
+}
+
+/// Whether a table holds data, rather than being used to lay out the page.
+fn is_data_table(table: ElementRef) -> bool {
+    table.attr("role") != Some("presentation")
+        && table
+            .descendent_elements()
+            .any(|element| matches!(element.value().name(), "caption" | "th" | "thead"))
+}
+
+fn escape_html(text: &str, in_attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if in_attribute => escaped.push_str("&quot;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
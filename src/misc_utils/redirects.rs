@@ -0,0 +1,251 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Loading a different URL in place of the one that was navigated to, according to the
+//! redirect rules in the profile.
+
+use std::fmt;
+
+use log::warn;
+use regex::{Captures, Regex};
+use url::Url;
+
+use crate::data_storage::redirect_rules::{RedirectRule, RedirectRuleKind};
+
+/// The most rules that are applied one after another to a single URL. A longer chain is
+/// almost certainly rules redirecting to each other.
+const MAX_REDIRECT_CHAIN: usize = 10;
+
+enum Matcher {
+    Host(String),
+    Pattern(Regex),
+}
+
+struct CompiledRule {
+    id: i32,
+    matcher: Matcher,
+    replacement: String,
+}
+
+impl CompiledRule {
+    fn compile(rule: &RedirectRule) -> Result<Self, String> {
+        let matcher = match rule.kind {
+            RedirectRuleKind::Host => {
+                let host = rule.pattern.trim().to_ascii_lowercase();
+                if host.is_empty() || host.contains(['/', ':', '*']) {
+                    return Err("The pattern has to be a host, such as example.com".into());
+                }
+                let replacement = rule.replacement.trim();
+                if replacement.is_empty()
+                    || replacement.contains(['/', ':', '*'])
+                    || Url::parse(&format!("https://{replacement}/")).is_err()
+                {
+                    return Err("The replacement has to be a host, such as example.org".into());
+                }
+                Matcher::Host(host)
+            }
+            RedirectRuleKind::Wildcard => {
+                let regex = rule
+                    .pattern
+                    .split('*')
+                    .map(regex::escape)
+                    .collect::<Vec<_>>()
+                    .join("(.*)");
+                let wildcards = rule.pattern.matches('*').count();
+                if let Some(group) = highest_group_reference(&rule.replacement)
+                    && group > wildcards
+                {
+                    return Err(format!(
+                        "The replacement uses ${group}, but the pattern only has {wildcards} *"
+                    ));
+                }
+                Matcher::Pattern(Regex::new(&format!("^{regex}$")).map_err(|e| e.to_string())?)
+            }
+            RedirectRuleKind::Regex => {
+                let regex = Regex::new(&rule.pattern).map_err(|error| error.to_string())?;
+                if let Some(group) = highest_group_reference(&rule.replacement)
+                    && group >= regex.captures_len()
+                {
+                    return Err(format!(
+                        "The replacement uses ${group}, but the pattern only has {} groups",
+                        regex.captures_len() - 1
+                    ));
+                }
+                Matcher::Pattern(regex)
+            }
+        };
+        Ok(CompiledRule {
+            id: rule.id,
+            matcher,
+            replacement: rule.replacement.trim().to_owned(),
+        })
+    }
+
+    /// The URL this rule redirects to, as written by the rule, if it matches the URL.
+    fn apply(&self, url: &Url) -> Option<String> {
+        match &self.matcher {
+            Matcher::Host(host) => {
+                let url_host = url.host_str()?.to_ascii_lowercase();
+                if url_host != *host && url_host.strip_prefix("www.") != Some(host) {
+                    return None;
+                }
+                let mut redirected = url.clone();
+                redirected.set_host(Some(&self.replacement)).ok()?;
+                Some(redirected.into())
+            }
+            Matcher::Pattern(regex) => {
+                let captures = regex.captures(url.as_str())?;
+                Some(expand(&self.replacement, &captures))
+            }
+        }
+    }
+}
+
+/// Why a URL could not be redirected.
+#[derive(Debug, PartialEq)]
+pub enum RedirectError {
+    /// The rules redirect back to a URL they redirected from, or redirect too many times.
+    Loop,
+    /// The rule with the given ID redirects to something that is not a URL.
+    InvalidUrl(i32, String),
+}
+
+impl fmt::Display for RedirectError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedirectError::Loop => write!(formatter, "The rules redirect in a loop"),
+            RedirectError::InvalidUrl(_, url) => write!(formatter, "\"{url}\" is not a valid URL"),
+        }
+    }
+}
+
+/// The result of redirecting a URL.
+#[derive(Debug, PartialEq)]
+pub struct Redirect {
+    pub url: Url,
+    /// The IDs of the rules that were applied, in order.
+    pub rule_ids: Vec<i32>,
+}
+
+/// The enabled redirect rules, ready to be matched against URLs.
+#[derive(Default)]
+pub struct Redirector {
+    rules: Vec<CompiledRule>,
+}
+
+impl Redirector {
+    pub fn new(rules: &[RedirectRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .filter_map(|rule| {
+                CompiledRule::compile(rule)
+                    .inspect_err(|error| warn!("Ignoring redirect rule {}: {error}", rule.pattern))
+                    .ok()
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Check that a rule can be used, returning the reason if it can't.
+    pub fn validate(rule: &RedirectRule) -> Result<(), String> {
+        CompiledRule::compile(rule).map(|_| ())
+    }
+
+    /// The URL to load instead of the given one, or `None` if no rule changes it.
+    ///
+    /// The rules are applied again to the URL they redirect to, so that one rule can build on
+    /// another, until none of them change it.
+    pub fn redirect(&self, url: &Url) -> Result<Option<Redirect>, RedirectError> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Ok(None);
+        }
+        let mut seen = vec![url.clone()];
+        let mut rule_ids = vec![];
+        while let Some((rule_id, redirected)) = self.apply_first(seen.last().unwrap()) {
+            let redirected = Url::parse(&redirected)
+                .map_err(|_| RedirectError::InvalidUrl(rule_id, redirected))?;
+            if seen.contains(&redirected) {
+                // A rule that redirects a URL to itself, such as one whose replacement is the
+                // same as its pattern, is not a loop and simply ends the chain.
+                if seen.last() == Some(&redirected) {
+                    break;
+                }
+                return Err(RedirectError::Loop);
+            }
+            if rule_ids.len() == MAX_REDIRECT_CHAIN {
+                return Err(RedirectError::Loop);
+            }
+            rule_ids.push(rule_id);
+            seen.push(redirected);
+        }
+        if rule_ids.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Redirect {
+            url: seen.pop().unwrap(),
+            rule_ids,
+        }))
+    }
+
+    fn apply_first(&self, url: &Url) -> Option<(i32, String)> {
+        self.rules
+            .iter()
+            .find_map(|rule| Some((rule.id, rule.apply(url)?)))
+    }
+}
+
+/// Replace `$1` and `${name}` in the replacement with the text of the groups they refer to, and
+/// `$$` with `$`. Unlike [`Captures::expand`], `$1abc` refers to group 1 rather than to a group
+/// named `1abc`.
+fn expand(replacement: &str, captures: &Captures) -> String {
+    let mut expanded = String::new();
+    let mut rest = replacement;
+    while let Some(position) = rest.find('$') {
+        expanded.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+        } else if let Some((name, after)) = rest
+            .strip_prefix('{')
+            .and_then(|braced| braced.split_once('}'))
+        {
+            let group = match name.parse::<usize>() {
+                Ok(index) => captures.get(index),
+                Err(_) => captures.name(name),
+            };
+            expanded.push_str(group.map_or("", |group| group.as_str()));
+            rest = after;
+        } else {
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits == 0 {
+                expanded.push('$');
+                continue;
+            }
+            let index: usize = rest[..digits].parse().unwrap_or(usize::MAX);
+            expanded.push_str(captures.get(index).map_or("", |group| group.as_str()));
+            rest = &rest[digits..];
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// The highest group number referred to as `$1` or `${1}` in a replacement.
+fn highest_group_reference(replacement: &str) -> Option<usize> {
+    replacement
+        .replace("$$", "")
+        .split('$')
+        .skip(1)
+        .filter_map(|reference| {
+            let reference = reference.strip_prefix('{').unwrap_or(reference);
+            let digits = reference.len()
+                - reference
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+            reference[..digits].parse().ok()
+        })
+        .max()
+}
@@ -0,0 +1,388 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use base64::Engine;
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use servo::{JSValue, WebView};
+use url::Url;
+
+/// Stands in for the name of the folder that holds the subresources of a complete page until
+/// the user has picked where to save it.
+const FILES_FOLDER_PLACEHOLDER: &str = "__PRISM_SAVED_PAGE_FILES__";
+
+/// The characters that are encoded in the name of the `_files` folder where the saved page
+/// links to it, so that a name such as `50% off #1?_files` still leads to the folder.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/')
+    .add(b'\\')
+    .add(b'&')
+    .add(b'\'');
+
+/// File names that Windows reserves for devices, with or without an extension.
+const RESERVED_FILE_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Serializes the DOM of the page, adding a `<base>` element so that relative links keep
+/// pointing at the original site.
+const SERIALIZE_HTML_ONLY_SCRIPT: &str = r#"(function () {
+    const root = document.documentElement.cloneNode(true);
+    const head = root.querySelector("head");
+    if (head && !head.querySelector("base[href]")) {
+        const base = document.createElement("base");
+        base.href = document.baseURI;
+        head.prepend(base);
+    }
+    return JSON.stringify({ html: "<!DOCTYPE html>\n" + root.outerHTML, resources: [] });
+})()"#;
+
+/// Fetches a resource with the credentials of the page, returning its contents as bytes, or
+/// `null` if the request failed. Pages can only read cross-origin responses that the site allows
+/// with CORS, so others fail as well. `toBase64` encodes the bytes for returning them.
+const READ_BINARY_FUNCTION: &str = r#"async function readBinary(url) {
+    try {
+        const response = await fetch(url);
+        if (!response.ok) {
+            return null;
+        }
+        return new Uint8Array(await response.arrayBuffer());
+    } catch (e) {
+        return null;
+    }
+}
+function toBase64(bytes) {
+    // The bytes are turned into a string a chunk at a time, as spreading all of them at once
+    // overflows the stack for large files.
+    const chunks = [];
+    for (let start = 0; start < bytes.length; start += 8192) {
+        chunks.push(String.fromCharCode(...bytes.subarray(start, start + 8192)));
+    }
+    return btoa(chunks.join(""));
+}
+"#;
+
+/// Serializes the DOM of the page along with its images, stylesheets and icons, and what the
+/// stylesheets refer to, rewriting each of them to point into the `_files` folder. All other
+/// URLs are made absolute, so that they keep pointing at the original site, as do the
+/// subresources that can't be read, which are listed in `failed`. The script returns a promise,
+/// which Servo waits for before handing back the result.
+const SERIALIZE_COMPLETE_SCRIPT: &str = r##"(async function () {
+    const root = document.documentElement.cloneNode(true);
+    const resources = [];
+    const failed = [];
+    const fetched = new Map();
+
+    function absolute(url, base) {
+        try {
+            return new URL(url, base).href;
+        } catch (e) {
+            return url;
+        }
+    }
+
+    // Saves the resource in the `_files` folder, returning its file name, or `null` if it can't
+    // be read. What a stylesheet refers to is saved along with it.
+    async function saveResource(url, isStylesheet) {
+        if (fetched.has(url)) {
+            return fetched.get(url);
+        }
+        const extension = (new URL(url).pathname.match(/\.[a-zA-Z0-9]{1,5}$/) || [""])[0];
+        const name = "resource" + fetched.size + extension;
+        // Stylesheets that import each other refer to the name before they are saved.
+        fetched.set(url, name);
+        let contents = await readBinary(url);
+        if (contents === null) {
+            failed.push(url);
+            fetched.set(url, null);
+            return null;
+        }
+        if (isStylesheet) {
+            const css = await rewriteCss(new TextDecoder().decode(contents), url, "");
+            contents = new TextEncoder().encode(css);
+        }
+        resources.push([name, toBase64(contents)]);
+        return name;
+    }
+
+    // Saves what the `url()`s and `@import`s of the CSS refer to, pointing them at the saved
+    // files with the given prefix, or at absolute URLs when they can't be saved.
+    async function rewriteCss(css, base, prefix) {
+        const pattern =
+            /url\(\s*(?:"([^"]*)"|'([^']*)'|([^)'"\s]*))\s*\)|@import\s*(?:"([^"]*)"|'([^']*)')/g;
+        const parts = [];
+        let end = 0;
+        for (const match of css.matchAll(pattern)) {
+            parts.push(css.slice(end, match.index));
+            end = match.index + match[0].length;
+            const reference = match[1] ?? match[2] ?? match[3] ?? match[4] ?? match[5];
+            if (!reference || reference.startsWith("#") || reference.startsWith("data:")) {
+                parts.push(match[0]);
+                continue;
+            }
+            const url = absolute(reference, base);
+            const isImport = /@import\s*$/.test(css.slice(0, match.index));
+            const name = await saveResource(url, isImport || match[0].startsWith("@import"));
+            const target = name ? prefix + name : url;
+            parts.push(match[0].startsWith("@import") ? `@import "${target}"` : `url("${target}")`);
+        }
+        parts.push(css.slice(end));
+        return parts.join("");
+    }
+
+    // The saved files are linked relative to the page, which a `<base>` would change.
+    for (const base of root.querySelectorAll("base")) {
+        base.remove();
+    }
+    const linked = [
+        ["a[href]", "href"],
+        ["area[href]", "href"],
+        ["link[href]", "href"],
+        ["img[src]", "src"],
+        ["script[src]", "src"],
+        ["iframe[src]", "src"],
+        ["frame[src]", "src"],
+        ["embed[src]", "src"],
+        ["object[data]", "data"],
+        ["audio[src]", "src"],
+        ["video[src]", "src"],
+        ["video[poster]", "poster"],
+        ["source[src]", "src"],
+        ["track[src]", "src"],
+        ["input[src]", "src"],
+        ["form[action]", "action"],
+    ];
+    for (const [selector, attribute] of linked) {
+        for (const element of root.querySelectorAll(selector)) {
+            const value = element.getAttribute(attribute);
+            if (!value.startsWith("#")) {
+                element.setAttribute(attribute, absolute(value, document.baseURI));
+            }
+        }
+    }
+    // Each image candidate is a URL, which may contain commas but ends in whitespace or in
+    // commas that separate it from the next, followed by descriptors up to the next comma.
+    const candidatePattern = /([^\s,]\S*?)(?:,+(?=\s|$)|\s+([^,]*)|$)/g;
+    for (const element of root.querySelectorAll("img[srcset], source[srcset]")) {
+        const candidates = [];
+        for (const [, url, descriptors] of element.getAttribute("srcset").matchAll(candidatePattern)) {
+            const candidate = absolute(url, document.baseURI);
+            candidates.push(descriptors ? candidate + " " + descriptors.trim() : candidate);
+        }
+        element.setAttribute("srcset", candidates.join(", "));
+    }
+
+    const saved = [
+        ["img[src]", "src"],
+        ["link[rel~='stylesheet'][href]", "href"],
+        ["link[rel~='icon'][href]", "href"],
+    ];
+    for (const [selector, attribute] of saved) {
+        for (const element of root.querySelectorAll(selector)) {
+            const url = element.getAttribute(attribute);
+            const isStylesheet = element.matches("link[rel~='stylesheet']");
+            const name = await saveResource(url, isStylesheet);
+            if (name) {
+                element.setAttribute(attribute, "PLACEHOLDER/" + name);
+                element.removeAttribute("srcset");
+            }
+        }
+    }
+    for (const style of root.querySelectorAll("style")) {
+        style.textContent = await rewriteCss(style.textContent, document.baseURI, "PLACEHOLDER/");
+    }
+    for (const element of root.querySelectorAll("[style]")) {
+        const css = element.getAttribute("style");
+        element.setAttribute("style", await rewriteCss(css, document.baseURI, "PLACEHOLDER/"));
+    }
+    return JSON.stringify({ html: "<!DOCTYPE html>\n" + root.outerHTML, resources, failed });
+})()"##;
+
+/// Fetches a single resource with the credentials of the page, returning it base64 encoded.
+const FETCH_RESOURCE_SCRIPT: &str =
+    "readBinary(RESOURCE_URL).then((bytes) => bytes && toBase64(bytes))";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SavePageMode {
+    /// Only the serialized DOM of the page.
+    HtmlOnly,
+    /// The serialized DOM of the page, with its images and stylesheets saved in a sibling
+    /// `_files` folder.
+    Complete,
+}
+
+/// The serialized contents of a page, ready to be written to disk.
+pub struct SavedPage {
+    html: String,
+    /// The subresources of the page, as file names and their contents.
+    resources: Vec<(String, Vec<u8>)>,
+    /// The URLs of the subresources that couldn't be fetched.
+    failed_resources: Vec<String>,
+}
+
+impl SavedPage {
+    /// The URLs of the subresources that couldn't be saved, which the saved page still loads
+    /// from the original site.
+    pub fn failed_resources(&self) -> &[String] {
+        &self.failed_resources
+    }
+
+    /// Write the page to the given path, putting any subresources in a `<name>_files` folder
+    /// next to it. Returns the total number of bytes written.
+    pub fn write_to(&self, path: &Path) -> io::Result<u64> {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "page".into());
+        let folder_name = format!("{stem}_files");
+
+        let mut bytes_written = 0;
+        if !self.resources.is_empty() {
+            let folder = path.with_file_name(&folder_name);
+            fs::create_dir_all(&folder)?;
+            for (name, contents) in &self.resources {
+                fs::write(folder.join(name), contents)?;
+                bytes_written += contents.len() as u64;
+            }
+        }
+
+        let folder_link = utf8_percent_encode(&folder_name, PATH_SEGMENT).to_string();
+        let html = self.html.replace(FILES_FOLDER_PLACEHOLDER, &folder_link);
+        fs::write(path, &html)?;
+        Ok(bytes_written + html.len() as u64)
+    }
+}
+
+/// Serialize the page shown in the given [`WebView`].
+pub fn serialize_page(
+    webview: &WebView,
+    mode: SavePageMode,
+    callback: impl FnOnce(Result<SavedPage, String>) + 'static,
+) {
+    let script = match mode {
+        SavePageMode::HtmlOnly => SERIALIZE_HTML_ONLY_SCRIPT.to_owned(),
+        SavePageMode::Complete => format!(
+            "{READ_BINARY_FUNCTION}{}",
+            SERIALIZE_COMPLETE_SCRIPT.replace("PLACEHOLDER", FILES_FOLDER_PLACEHOLDER)
+        ),
+    };
+    webview.evaluate_javascript(script, move |result| {
+        callback(match result {
+            Ok(JSValue::String(json)) => parse_saved_page(&json),
+            Ok(other) => Err(format!("Unexpected result {other:?}")),
+            Err(error) => Err(format!("{error:?}")),
+        })
+    });
+}
+
+/// Fetch a single resource, such as an image, from within the page shown in the given
+/// [`WebView`], so that it is loaded the same way the page loaded it.
+pub fn fetch_resource(
+    webview: &WebView,
+    url: &Url,
+    callback: impl FnOnce(Result<Vec<u8>, String>) + 'static,
+) {
+    let quoted_url = serde_json::Value::from(url.as_str()).to_string();
+    let script = format!(
+        "{READ_BINARY_FUNCTION}{}",
+        FETCH_RESOURCE_SCRIPT.replace("RESOURCE_URL", &quoted_url)
+    );
+    webview.evaluate_javascript(script, move |result| {
+        callback(match result {
+            Ok(JSValue::String(contents)) => base64::engine::general_purpose::STANDARD
+                .decode(contents)
+                .map_err(|error| error.to_string()),
+            Ok(JSValue::Null) => Err("The resource could not be loaded".into()),
+            Ok(other) => Err(format!("Unexpected result {other:?}")),
+            Err(error) => Err(format!("{error:?}")),
+        })
+    });
+}
+
+/// A default file name for a resource, based on the last segment of its URL.
+pub fn default_resource_file_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(|segment| {
+            percent_encoding::percent_decode_str(segment)
+                .decode_utf8_lossy()
+                .into_owned()
+        })
+        .and_then(|name| safe_file_name(&name))
+        .unwrap_or_else(|| "download".into())
+}
+
+fn parse_saved_page(json: &str) -> Result<SavedPage, String> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|error| error.to_string())?;
+    let html = value["html"]
+        .as_str()
+        .ok_or("Page did not return any HTML")?
+        .to_owned();
+    let resources = value["resources"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|resource| {
+            let name = resource[0].as_str()?;
+            let contents = base64::engine::general_purpose::STANDARD
+                .decode(resource[1].as_str()?)
+                .ok()?;
+            Some((name.to_owned(), contents))
+        })
+        .collect();
+    let failed_resources = value["failed"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|url| Some(url.as_str()?.to_owned()))
+        .collect();
+    Ok(SavedPage {
+        html,
+        resources,
+        failed_resources,
+    })
+}
+
+/// A default file name for a saved page, based on its title.
+pub fn default_saved_page_file_name(title: Option<String>) -> String {
+    let name = safe_file_name(&title.unwrap_or_default()).unwrap_or_else(|| "page".into());
+    format!("{name}.html")
+}
+
+/// The name with the characters that aren't allowed in file names replaced, or `None` if
+/// nothing usable is left. Names that Windows reserves for devices, such as `CON`, get an
+/// underscore in front.
+fn safe_file_name(name: &str) -> Option<String> {
+    let name: String = name
+        .chars()
+        .filter(|character| !character.is_control())
+        .map(|character| match character {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            character => character,
+        })
+        .collect();
+    // Windows drops dots and spaces from the end of a name.
+    let name = name.trim().trim_end_matches('.').trim_end();
+    if name.is_empty() {
+        return None;
+    }
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_FILE_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Some(format!("_{name}"));
+    }
+    Some(name.to_owned())
+}
@@ -0,0 +1,211 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use image::{ImageFormat, RgbaImage};
+use servo::{JSValue, WebView};
+
+/// The tallest full page screenshot we are willing to stitch together, in device pixels.
+/// Anything beyond this is cut off, so that a never ending page doesn't eat all of our memory.
+const MAXIMUM_FULL_PAGE_HEIGHT: u32 = 32_768;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScreenshotArea {
+    /// Only the part of the page that is currently visible in the viewport.
+    Visible,
+    /// The whole scrollable height of the page.
+    FullPage,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScreenshotTarget {
+    File,
+    Clipboard,
+}
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    Capture(String),
+    Script(String),
+}
+
+impl std::fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScreenshotError::Capture(reason) => write!(f, "Could not capture the page: {reason}"),
+            ScreenshotError::Script(reason) => write!(f, "Could not measure the page: {reason}"),
+        }
+    }
+}
+
+type ScreenshotCallback = Box<dyn FnOnce(Result<RgbaImage, ScreenshotError>)>;
+
+/// Captures the given area of a [`WebView`] into an image. This only depends on the [`WebView`]
+/// itself, so it works the same whether or not the `WebView` is shown in a window.
+pub fn capture_screenshot(
+    webview: &WebView,
+    area: ScreenshotArea,
+    callback: impl FnOnce(Result<RgbaImage, ScreenshotError>) + 'static,
+) {
+    match area {
+        ScreenshotArea::Visible => webview.take_screenshot(None, move |result| {
+            callback(result.map_err(|error| ScreenshotError::Capture(format!("{error:?}"))))
+        }),
+        ScreenshotArea::FullPage => FullPageCapture::start(webview.clone(), Box::new(callback)),
+    }
+}
+
+pub fn save_screenshot_as_png(image: &RgbaImage, path: &Path) -> image::ImageResult<()> {
+    image.save_with_format(path, ImageFormat::Png)
+}
+
+pub fn screenshot_to_egui_image(image: &RgbaImage) -> egui::ColorImage {
+    egui::ColorImage::from_rgba_unmultiplied(
+        [image.width() as usize, image.height() as usize],
+        image.as_raw(),
+    )
+}
+
+/// A default file name for a screenshot, based on the current time.
+pub fn default_screenshot_file_name() -> String {
+    format!(
+        "Screenshot {}.png",
+        chrono::Local::now().format("%Y-%m-%d %H-%M-%S")
+    )
+}
+
+/// Captures a full page by scrolling through it one viewport at a time and stitching the
+/// resulting screenshots together, then restores the original scroll position.
+struct FullPageCapture {
+    webview: WebView,
+    original_scroll_position: (f64, f64),
+    /// The height of the scrollable page, in CSS pixels.
+    page_height: f64,
+    /// The height of the viewport, in CSS pixels.
+    viewport_height: f64,
+    /// The scroll offset of the next viewport to capture, in CSS pixels.
+    next_offset: f64,
+    /// The scroll offset of the last viewport that was captured, in CSS pixels.
+    last_offset: Option<f64>,
+    canvas: Option<RgbaImage>,
+    callback: Option<ScreenshotCallback>,
+}
+
+impl FullPageCapture {
+    fn start(webview: WebView, callback: ScreenshotCallback) {
+        let script = "[window.scrollX, window.scrollY, \
+                      document.documentElement.scrollHeight, window.innerHeight]";
+        let measured_webview = webview.clone();
+        webview.evaluate_javascript(script, move |result| {
+            let numbers = match result {
+                Ok(JSValue::Array(values)) => values
+                    .into_iter()
+                    .filter_map(|value| match value {
+                        JSValue::Number(number) => Some(number),
+                        _ => None,
+                    })
+                    .collect::<Vec<f64>>(),
+                Ok(other) => {
+                    return callback(Err(ScreenshotError::Script(format!(
+                        "Unexpected result {other:?}"
+                    ))));
+                }
+                Err(error) => return callback(Err(ScreenshotError::Script(format!("{error:?}")))),
+            };
+            let [scroll_x, scroll_y, page_height, viewport_height] = numbers[..] else {
+                return callback(Err(ScreenshotError::Script(
+                    "Page returned incomplete measurements".into(),
+                )));
+            };
+
+            let capture = Rc::new(RefCell::new(FullPageCapture {
+                webview: measured_webview,
+                original_scroll_position: (scroll_x, scroll_y),
+                page_height: page_height.max(viewport_height),
+                viewport_height: viewport_height.max(1.0),
+                next_offset: 0.0,
+                last_offset: None,
+                canvas: None,
+                callback: Some(callback),
+            }));
+            Self::capture_next(capture);
+        });
+    }
+
+    fn capture_next(capture: Rc<RefCell<Self>>) {
+        let (webview, offset) = {
+            let capture = capture.borrow();
+            (capture.webview.clone(), capture.next_offset)
+        };
+
+        // Scroll to the next offset and ask the page where it actually ended up, as the last
+        // viewport is clamped to the bottom of the page.
+        let script = format!("window.scrollTo(0, {offset}); window.scrollY");
+        let screenshot_webview = webview.clone();
+        webview.evaluate_javascript(script, move |result| {
+            let scrolled_to = match result {
+                Ok(JSValue::Number(scrolled_to)) => scrolled_to,
+                Ok(_) => offset,
+                Err(error) => {
+                    return Self::finish(
+                        capture,
+                        Err(ScreenshotError::Script(format!("{error:?}"))),
+                    );
+                }
+            };
+            screenshot_webview.take_screenshot(None, move |result| match result {
+                Ok(image) => Self::add_viewport_image(capture, image, scrolled_to),
+                Err(error) => {
+                    Self::finish(capture, Err(ScreenshotError::Capture(format!("{error:?}"))))
+                }
+            });
+        });
+    }
+
+    fn add_viewport_image(capture: Rc<RefCell<Self>>, image: RgbaImage, scrolled_to: f64) {
+        let done = 'done: {
+            let mut capture = capture.borrow_mut();
+            // The page can be shorter than it was measured, or refuse to scroll, in which case
+            // there is nothing more to capture.
+            if capture
+                .last_offset
+                .is_some_and(|last_offset| scrolled_to <= last_offset)
+            {
+                break 'done true;
+            }
+            capture.last_offset = Some(scrolled_to);
+
+            let scale = image.height() as f64 / capture.viewport_height;
+            let canvas_height = ((capture.page_height * scale).ceil() as u32)
+                .clamp(image.height(), MAXIMUM_FULL_PAGE_HEIGHT);
+            let width = image.width();
+            let canvas = capture
+                .canvas
+                .get_or_insert_with(|| RgbaImage::new(width, canvas_height));
+            image::imageops::replace(canvas, &image, 0, (scrolled_to * scale).round() as i64);
+
+            capture.next_offset = scrolled_to + capture.viewport_height;
+            capture.next_offset >= capture.page_height
+                || capture.next_offset * scale >= MAXIMUM_FULL_PAGE_HEIGHT as f64
+        };
+
+        if done {
+            let canvas = capture.borrow_mut().canvas.take();
+            let result = canvas.ok_or_else(|| ScreenshotError::Capture("Empty page".into()));
+            Self::finish(capture, result);
+        } else {
+            Self::capture_next(capture);
+        }
+    }
+
+    fn finish(capture: Rc<RefCell<Self>>, result: Result<RgbaImage, ScreenshotError>) {
+        let mut capture = capture.borrow_mut();
+        let (scroll_x, scroll_y) = capture.original_scroll_position;
+        capture
+            .webview
+            .evaluate_javascript(format!("window.scrollTo({scroll_x}, {scroll_y})"), |_| {});
+        if let Some(callback) = capture.callback.take() {
+            callback(result);
+        }
+    }
+}
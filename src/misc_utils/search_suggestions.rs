@@ -0,0 +1,324 @@
+//! Fetching search suggestions from a search engine, as described by
+//! <https://github.com/dewitt/opensearch/blob/master/mozilla/suggestions.md>.
+//!
+//! This runs off the main thread with its own tiny HTTP client, as the requests are not made on
+//! behalf of any page and should not share its cookies or cache. It goes through the proxy
+//! that Servo is configured with, and follows redirects, all within the timeout. A response
+//! only counts once it is known to be complete, from its `Content-Length` or from the server
+//! closing the connection properly, so a cut off response is never taken for a whole one.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::LazyLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Sender, unbounded};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConnection, StreamOwned};
+use servo::pref;
+use url::Url;
+
+use crate::misc_utils::ca_certificates::{client_config, extra_certificates};
+
+/// Suggestion responses are a short JSON array, so anything larger than this is not one.
+const MAXIMUM_RESPONSE_SIZE: usize = 256 * 1024;
+
+/// How many redirects are followed before giving up.
+const MAXIMUM_REDIRECTS: usize = 5;
+
+/// Looking up a host can't be given a timeout, so the lookups happen one after another on a
+/// thread of their own, which the requests stop waiting for once their time is up.
+static HOST_LOOKUPS: LazyLock<Sender<HostLookup>> = LazyLock::new(|| {
+    let (sender, receiver) = unbounded::<HostLookup>();
+    thread::Builder::new()
+        .name("SuggestionsLookup".into())
+        .spawn(move || {
+            for lookup in receiver {
+                // Nobody is waiting for lookups that have timed out any more.
+                if Instant::now() >= lookup.deadline {
+                    continue;
+                }
+                let addresses = (lookup.host.as_str(), lookup.port)
+                    .to_socket_addrs()
+                    .map(|addresses| addresses.collect());
+                let _ = lookup.result_sender.send(addresses);
+            }
+        })
+        .expect("Could not start search suggestions lookup thread");
+    sender
+});
+
+/// A host to look up for a request that gives up at `deadline`.
+struct HostLookup {
+    host: String,
+    port: u16,
+    deadline: Instant,
+    result_sender: Sender<io::Result<Vec<SocketAddr>>>,
+}
+
+/// Fetch the suggestions at the given URL, giving up once `timeout` has passed.
+pub fn fetch_search_suggestions(url: &Url, timeout: Duration) -> Result<Vec<String>, String> {
+    let body = http_get(url, timeout)?;
+    parse_search_suggestions(&body).ok_or_else(|| "Unexpected suggestions format".into())
+}
+
+/// Parse a suggestions response, which looks like `["query", ["suggestion", ...], ...]`.
+pub fn parse_search_suggestions(body: &[u8]) -> Option<Vec<String>> {
+    let json: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let suggestions = json.as_array()?.get(1)?.as_array()?;
+    Some(
+        suggestions
+            .iter()
+            .filter_map(|suggestion| suggestion.as_str())
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+/// The proxy to connect to `url` through, if `proxy` is set to the URL of an HTTP proxy and
+/// the host of `url` isn't one of the comma separated domains in `no_proxy`.
+pub fn proxy_for(url: &Url, proxy: &str, no_proxy: &str) -> Option<Url> {
+    let host = url.host_str()?;
+    let is_excluded = no_proxy
+        .split(',')
+        .map(|domain| domain.trim().trim_start_matches('.'))
+        .filter(|domain| !domain.is_empty())
+        .any(|domain| {
+            domain == "*"
+                || host.eq_ignore_ascii_case(domain)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+        });
+    if is_excluded {
+        return None;
+    }
+    Url::parse(proxy)
+        .ok()
+        .filter(|proxy| proxy.scheme() == "http" && proxy.host_str().is_some())
+}
+
+fn http_get(url: &Url, timeout: Duration) -> Result<Vec<u8>, String> {
+    let deadline = Instant::now() + timeout;
+    let mut url = url.clone();
+    for _ in 0..=MAXIMUM_REDIRECTS {
+        let proxy = match url.scheme() {
+            "https" => pref!(network_https_proxy_uri),
+            _ => pref!(network_http_proxy_uri),
+        };
+        let proxy = proxy_for(&url, &proxy, &pref!(network_http_no_proxy));
+        let response = send_get_request(&url, proxy.as_ref(), deadline)?;
+
+        let header_end = header_end(&response).ok_or("Incomplete response")?;
+        let head = String::from_utf8_lossy(&response[..header_end]);
+        let mut lines = head.split("\r\n");
+        let status_line = lines.next().unwrap_or_default();
+        let status = status_line.split(' ').nth(1).unwrap_or_default();
+        if status.starts_with('2') {
+            return Ok(response[header_end..].to_vec());
+        }
+        let location = lines
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("location"))
+            .and_then(|(_, location)| url.join(location.trim()).ok())
+            .filter(|location| matches!(location.scheme(), "http" | "https"));
+        match location {
+            Some(location) if status.starts_with('3') => url = location,
+            _ => return Err(format!("Server responded with \"{status_line}\"")),
+        }
+    }
+    Err("Too many redirects".into())
+}
+
+/// Send a GET request for `url`, directly or through `proxy`, returning the whole response.
+fn send_get_request(url: &Url, proxy: Option<&Url>, deadline: Instant) -> Result<Vec<u8>, String> {
+    let host = url.host_str().ok_or("URL has no host")?;
+    let port = url.port_or_known_default().ok_or("URL has no port")?;
+    // The Host header only has the port if it isn't the default one of the scheme.
+    let authority = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_owned(),
+    };
+
+    let mut stream = match proxy {
+        Some(proxy) => connect(
+            proxy.host_str().ok_or("Proxy has no host")?,
+            proxy.port_or_known_default().ok_or("Proxy has no port")?,
+            deadline,
+        )?,
+        None => connect(host, port, deadline)?,
+    };
+
+    let mut target = url.path().to_owned();
+    if let Some(query) = url.query() {
+        target = format!("{target}?{query}");
+    }
+    // Plain HTTP requests to a proxy name the whole URL.
+    if proxy.is_some() && url.scheme() == "http" {
+        target = format!("http://{authority}{target}");
+    }
+    // HTTP/1.0 keeps servers from using chunked encoding, so the body is simply the rest of
+    // the response.
+    let request = format!(
+        "GET {target} HTTP/1.0\r\nHost: {authority}\r\nAccept: application/json\r\nUser-Agent: Servo\r\n\r\n"
+    );
+
+    match url.scheme() {
+        "http" => send_request(stream, &request, deadline),
+        "https" => {
+            if proxy.is_some() {
+                open_tunnel(&mut stream, &format!("{host}:{port}"))?;
+            }
+            let server_name = ServerName::try_from(host.trim_matches(['[', ']']).to_owned())
+                .map_err(|error| error.to_string())?;
+            let connection =
+                ClientConnection::new(client_config(extra_certificates()), server_name)
+                    .map_err(|error| error.to_string())?;
+            send_request(StreamOwned::new(connection, stream), &request, deadline)
+        }
+        scheme => Err(format!("Unsupported scheme {scheme}")),
+    }
+}
+
+/// The time left until the deadline, or an error if it has passed.
+fn remaining(deadline: Instant) -> Result<Duration, String> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or_else(|| "Timed out".into())
+}
+
+/// Connect to the host, giving up at the deadline.
+fn connect(host: &str, port: u16, deadline: Instant) -> Result<TcpStream, String> {
+    let (result_sender, receiver) = crossbeam_channel::bounded(1);
+    HOST_LOOKUPS
+        .send(HostLookup {
+            host: host.trim_matches(['[', ']']).to_owned(),
+            port,
+            deadline,
+            result_sender,
+        })
+        .map_err(|_| "Could not look up the host")?;
+    let addresses = receiver
+        .recv_timeout(remaining(deadline)?)
+        .map_err(|_| "Timed out looking up the host")?
+        .map_err(|error| error.to_string())?;
+
+    let mut last_error = "Could not resolve host".to_owned();
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, remaining(deadline)?) {
+            Ok(stream) => {
+                let timeout = remaining(deadline)?;
+                stream
+                    .set_read_timeout(Some(timeout))
+                    .and_then(|_| stream.set_write_timeout(Some(timeout)))
+                    .map_err(|error| error.to_string())?;
+                return Ok(stream);
+            }
+            Err(error) => last_error = error.to_string(),
+        }
+    }
+    Err(last_error)
+}
+
+/// Ask an HTTP proxy to connect the stream to the given `host:port`, for TLS to go over it.
+fn open_tunnel(stream: &mut TcpStream, authority: &str) -> Result<(), String> {
+    let request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|error| error.to_string())?;
+    // The response is read a byte at a time, so that nothing after its head is taken from the
+    // stream.
+    let mut response = Vec::new();
+    let mut byte = [0];
+    while !response.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).map_err(|error| error.to_string())? == 0 {
+            return Err("Proxy closed the connection".into());
+        }
+        response.push(byte[0]);
+        if response.len() > MAXIMUM_RESPONSE_SIZE {
+            return Err("Response is too large".into());
+        }
+    }
+    let status_line = String::from_utf8_lossy(&response);
+    let status_line = status_line.lines().next().unwrap_or_default();
+    if status_line
+        .split(' ')
+        .nth(1)
+        .is_some_and(|status| status.starts_with('2'))
+    {
+        Ok(())
+    } else {
+        Err(format!("Proxy responded with \"{status_line}\""))
+    }
+}
+
+/// Send the request and read the whole response. The response ends where its
+/// `Content-Length` says, or otherwise where the server closes the connection, which over TLS
+/// has to be announced with a `close_notify` alert.
+fn send_request(
+    mut stream: impl Read + Write,
+    request: &str,
+    deadline: Instant,
+) -> Result<Vec<u8>, String> {
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|error| error.to_string())?;
+    let mut response = Vec::new();
+    // Where the response ends, once its head has been read, if it has a `Content-Length`.
+    let mut response_end = None;
+    let mut buffer = [0; 8192];
+    loop {
+        // The socket timeout only applies to each read, so also check the overall deadline in
+        // case the server trickles the response.
+        if Instant::now() > deadline {
+            return Err("Timed out".into());
+        }
+        let read = match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            // A TLS connection that is closed without a `close_notify` may have been cut off,
+            // so it only ends a response that has already been read as far as its length.
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err("The connection was closed before the response ended".into());
+            }
+            Err(error) => return Err(error.to_string()),
+        };
+        response.extend_from_slice(&buffer[..read]);
+        if response.len() > MAXIMUM_RESPONSE_SIZE {
+            return Err("Response is too large".into());
+        }
+        if response_end.is_none() {
+            response_end = header_end(&response).and_then(|header_end| {
+                content_length(&String::from_utf8_lossy(&response[..header_end]))
+                    .map(|length| header_end + length)
+            });
+        }
+        if let Some(end) = response_end.filter(|end| response.len() >= *end) {
+            response.truncate(end);
+            return Ok(response);
+        }
+    }
+    if response_end.is_some() {
+        return Err("The connection was closed before the response ended".into());
+    }
+    Ok(response)
+}
+
+/// The offset of the body of the response, after the blank line that ends its head.
+fn header_end(response: &[u8]) -> Option<usize> {
+    response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|position| position + 4)
+}
+
+/// The length of the body, from the `Content-Length` header of the response head.
+fn content_length(head: &str) -> Option<usize> {
+    head.split("\r\n")
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, length)| length.trim().parse().ok())
+}
@@ -0,0 +1,282 @@
+//! User scripts with Greasemonkey metadata, which are run on the pages they match.
+//!
+//! A script starts with a metadata block such as:
+//!
+//! ```text
+//! // ==UserScript==
+//! // @name     Example
+//! // @match    https://*.example.com/*
+//! // @exclude  https://example.com/admin/*
+//! // @run-at   document-end
+//! // ==/UserScript==
+//! ```
+//!
+//! `@match` takes a match pattern as used by browser extensions, while `@include` and
+//! `@exclude` take a glob in which `*` matches any text, or a regular expression between
+//! slashes. A script without `@match` or `@include` runs on every page. Scripts only ever run on
+//! web pages and local files, never on the browser's internal pages, whatever their patterns
+//! match. The scripts run in the main frame of the page, and the `GM_*` functions are not
+//! provided.
+
+use log::warn;
+use regex::Regex;
+use url::{Position, Url};
+
+use crate::data_storage::user_scripts::UserScript;
+
+const METADATA_START: &str = "==UserScript==";
+const METADATA_END: &str = "==/UserScript==";
+
+/// The schemes of the pages that scripts can run on.
+const USER_SCRIPT_SCHEMES: &[&str] = &["http", "https", "file"];
+
+/// When a script runs, from `@run-at`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RunAt {
+    /// As soon as possible. Servo only reports a new document once its head has been parsed,
+    /// so the script runs then.
+    DocumentStart,
+    /// Once the document has been parsed, when `DOMContentLoaded` is fired.
+    DocumentEnd,
+    /// Once the page and its resources have loaded.
+    #[default]
+    DocumentIdle,
+}
+
+impl RunAt {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RunAt::DocumentStart => "document-start",
+            RunAt::DocumentEnd => "document-end",
+            RunAt::DocumentIdle => "document-idle",
+        }
+    }
+}
+
+/// The metadata block of a user script.
+#[derive(Debug)]
+pub struct UserScriptMetadata {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    /// The `@match` and `@include` patterns, as written.
+    pub matches: Vec<String>,
+    /// The `@exclude` and `@exclude-match` patterns, as written.
+    pub excludes: Vec<String>,
+    pub run_at: RunAt,
+    included_urls: Vec<Regex>,
+    excluded_urls: Vec<Regex>,
+}
+
+impl UserScriptMetadata {
+    /// Parse the metadata block at the start of a user script.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut lines = source
+            .lines()
+            .map(|line| line.trim())
+            .skip_while(|line| line.is_empty());
+        if !lines
+            .next()
+            .is_some_and(|line| is_metadata_line(line, METADATA_START))
+        {
+            return Err(format!("The script doesn't start with // {METADATA_START}"));
+        }
+
+        let mut metadata = UserScriptMetadata {
+            name: String::new(),
+            namespace: None,
+            description: None,
+            version: None,
+            matches: vec![],
+            excludes: vec![],
+            run_at: RunAt::default(),
+            included_urls: vec![],
+            excluded_urls: vec![],
+        };
+        let mut ended = false;
+        for line in lines {
+            if is_metadata_line(line, METADATA_END) {
+                ended = true;
+                break;
+            }
+            let Some(entry) = line
+                .strip_prefix("//")
+                .and_then(|entry| entry.trim().strip_prefix('@'))
+            else {
+                continue;
+            };
+            let (key, value) = entry.split_once(char::is_whitespace).unwrap_or((entry, ""));
+            let value = value.trim();
+            match key {
+                "name" => metadata.name = value.to_owned(),
+                "namespace" => metadata.namespace = Some(value.to_owned()),
+                "description" => metadata.description = Some(value.to_owned()),
+                "version" => metadata.version = Some(value.to_owned()),
+                "match" => {
+                    metadata.included_urls.push(match_pattern_regex(value)?);
+                    metadata.matches.push(value.to_owned());
+                }
+                "include" => {
+                    metadata.included_urls.push(glob_regex(value)?);
+                    metadata.matches.push(value.to_owned());
+                }
+                "exclude-match" => {
+                    metadata.excluded_urls.push(match_pattern_regex(value)?);
+                    metadata.excludes.push(value.to_owned());
+                }
+                "exclude" => {
+                    metadata.excluded_urls.push(glob_regex(value)?);
+                    metadata.excludes.push(value.to_owned());
+                }
+                "run-at" => {
+                    metadata.run_at = match value {
+                        "document-start" => RunAt::DocumentStart,
+                        "document-end" => RunAt::DocumentEnd,
+                        "document-idle" => RunAt::DocumentIdle,
+                        _ => return Err(format!("Unknown @run-at {value}")),
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !ended {
+            return Err(format!("The metadata block has no // {METADATA_END}"));
+        }
+        if metadata.name.is_empty() {
+            return Err("The script has no @name".into());
+        }
+        Ok(metadata)
+    }
+
+    /// Whether the script runs on the page at the given URL.
+    pub fn applies_to(&self, url: &Url) -> bool {
+        // Internal pages can reach browser data, so no pattern lets a script run on them.
+        if !USER_SCRIPT_SCHEMES.contains(&url.scheme()) {
+            return false;
+        }
+        // As in other browsers, the fragment is not part of what is matched.
+        let url = &url[..Position::AfterQuery];
+        if self.excluded_urls.iter().any(|regex| regex.is_match(url)) {
+            return false;
+        }
+        self.included_urls.is_empty() || self.included_urls.iter().any(|regex| regex.is_match(url))
+    }
+
+    /// Whether this is a newer copy of the given script, which it should replace when it is
+    /// installed.
+    pub fn is_same_script(&self, other: &UserScriptMetadata) -> bool {
+        self.name == other.name && self.namespace == other.namespace
+    }
+}
+
+fn is_metadata_line(line: &str, marker: &str) -> bool {
+    line.strip_prefix("//")
+        .is_some_and(|comment| comment.trim() == marker)
+}
+
+/// Convert a glob, in which `*` matches any text, to a regular expression matching the whole
+/// URL. A pattern between slashes is a regular expression already.
+fn glob_regex(pattern: &str) -> Result<Regex, String> {
+    let regex = match pattern
+        .strip_prefix('/')
+        .and_then(|pattern| pattern.strip_suffix('/'))
+    {
+        Some(regex) if !regex.is_empty() => regex.to_owned(),
+        _ => format!("^{}$", wildcard_regex(pattern)),
+    };
+    Regex::new(&regex).map_err(|error| format!("Invalid pattern {pattern}: {error}"))
+}
+
+fn wildcard_regex(pattern: &str) -> String {
+    pattern
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*")
+}
+
+/// Convert a match pattern, such as `https://*.example.com/*` or `<all_urls>`, to a regular
+/// expression matching the whole URL.
+pub fn match_pattern_regex(pattern: &str) -> Result<Regex, String> {
+    let invalid = || format!("Invalid match pattern {pattern}");
+    let regex = if pattern == "<all_urls>" {
+        "^(https?|file)://".to_owned()
+    } else {
+        let (scheme, rest) = pattern.split_once("://").ok_or_else(invalid)?;
+        let (host, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => return Err(invalid()),
+        };
+        let scheme = match scheme {
+            "*" => "https?",
+            "http" | "https" | "file" => scheme,
+            _ => return Err(invalid()),
+        };
+        let host = match host {
+            "*" => "[^/]*".to_owned(),
+            _ if host.contains(['*', ':']) && !host.starts_with("*.") => return Err(invalid()),
+            _ => match host.strip_prefix("*.") {
+                Some(domain) if !domain.contains('*') => {
+                    format!(r"([^/]*\.)?{}(:\d+)?", regex::escape(domain))
+                }
+                Some(_) => return Err(invalid()),
+                None => format!(r"{}(:\d+)?", regex::escape(host)),
+            },
+        };
+        format!("^{scheme}://{host}{}$", wildcard_regex(path))
+    };
+    Regex::new(&regex).map_err(|_| invalid())
+}
+
+/// The user scripts that are enabled, ready to be matched against pages.
+#[derive(Default)]
+pub struct UserScripts {
+    scripts: Vec<(UserScriptMetadata, String)>,
+}
+
+impl UserScripts {
+    pub fn new(scripts: &[UserScript]) -> Self {
+        let scripts = scripts
+            .iter()
+            .filter(|script| script.enabled)
+            .filter_map(|script| match UserScriptMetadata::parse(&script.source) {
+                Ok(metadata) => Some((metadata, script.source.clone())),
+                Err(error) => {
+                    warn!("Ignoring user script {}: {error}", script.id);
+                    None
+                }
+            })
+            .collect();
+        Self { scripts }
+    }
+
+    /// A script that runs the user scripts that match the URL and run at one of the given
+    /// times, in the order they were installed, or `None` if there are none.
+    pub fn injection_script(&self, url: &Url, run_at: &[RunAt]) -> Option<String> {
+        let scripts: Vec<String> = self
+            .scripts
+            .iter()
+            .filter(|(metadata, _)| run_at.contains(&metadata.run_at) && metadata.applies_to(url))
+            .map(|(metadata, source)| wrap_user_script(metadata, source))
+            .collect();
+        (!scripts.is_empty()).then(|| scripts.join("\n"))
+    }
+}
+
+/// Wrap a script in a function of its own, so that its variables don't clash with those of the
+/// page or of other scripts, and so that an error in one script doesn't stop the others.
+fn wrap_user_script(metadata: &UserScriptMetadata, source: &str) -> String {
+    let name = serde_json::Value::from(metadata.name.as_str());
+    let run = format!(
+        "function () {{\ntry {{\n{source}\n}} catch (error) {{ console.error(\"User script \" + {name} + \" failed:\", error); }}\n}}"
+    );
+    match metadata.run_at {
+        RunAt::DocumentEnd => format!(
+            "(() => {{ const run = {run}; if (document.readyState === \"loading\") {{ \
+             document.addEventListener(\"DOMContentLoaded\", run, {{ once: true }}); }} \
+             else {{ run(); }} }})();"
+        ),
+        RunAt::DocumentStart | RunAt::DocumentIdle => format!("({run})();"),
+    }
+}
@@ -0,0 +1,86 @@
+//! Style sheets from the profile that are added to the pages they match, to change the fonts
+//! and colours of a site for example.
+//!
+//! The style sheets of a page are combined into a single `<style>` element at the end of the
+//! document, so that they come after the page's own style sheets, and that element is replaced
+//! whenever the style sheets change.
+
+use log::warn;
+use regex::Regex;
+use url::{Position, Url};
+
+use crate::data_storage::user_stylesheets::UserStylesheet;
+use crate::misc_utils::user_scripts::match_pattern_regex;
+
+/// The ID of the element that holds the user style sheets in a page.
+const STYLE_ELEMENT_ID: &str = "prism-user-stylesheets";
+
+/// Parse the URL patterns of a style sheet, one per line. A style sheet without any is added
+/// to every web page.
+pub fn parse_url_patterns(url_patterns: &str) -> Result<Vec<Regex>, String> {
+    let patterns: Vec<&str> = url_patterns
+        .lines()
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .collect();
+    if patterns.is_empty() {
+        return Ok(vec![match_pattern_regex("<all_urls>")?]);
+    }
+    patterns.into_iter().map(match_pattern_regex).collect()
+}
+
+/// The enabled user style sheets, ready to be matched against pages.
+#[derive(Default)]
+pub struct UserStylesheets {
+    stylesheets: Vec<(Vec<Regex>, String)>,
+}
+
+impl UserStylesheets {
+    pub fn new(stylesheets: &[UserStylesheet]) -> Self {
+        let stylesheets = stylesheets
+            .iter()
+            .filter(|stylesheet| stylesheet.enabled)
+            .filter_map(
+                |stylesheet| match parse_url_patterns(&stylesheet.url_patterns) {
+                    Ok(patterns) => Some((patterns, stylesheet.css.clone())),
+                    Err(error) => {
+                        warn!("Ignoring user style sheet {}: {error}", stylesheet.name);
+                        None
+                    }
+                },
+            )
+            .collect();
+        Self { stylesheets }
+    }
+
+    /// The CSS of the style sheets that match the URL, in the order they were added.
+    pub fn css_for(&self, url: &Url) -> String {
+        let url = &url[..Position::AfterQuery];
+        self.stylesheets
+            .iter()
+            .filter(|(patterns, _)| patterns.iter().any(|regex| regex.is_match(url)))
+            .map(|(_, css)| css.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// A script that replaces the user style sheets of the page with the given CSS, or removes
+/// them if it is empty. The element is moved back to the end of the document each time, in
+/// case the page has added style sheets after it.
+pub fn apply_user_stylesheets_script(css: &str) -> String {
+    format!(
+        "(() => {{ \
+            let style = document.getElementById(\"{STYLE_ELEMENT_ID}\"); \
+            const css = {}; \
+            if (!css) {{ style?.remove(); return; }} \
+            if (!style) {{ \
+                style = document.createElement(\"style\"); \
+                style.id = \"{STYLE_ELEMENT_ID}\"; \
+            }} \
+            style.textContent = css; \
+            document.documentElement.appendChild(style); \
+        }})()",
+        serde_json::Value::from(css)
+    )
+}
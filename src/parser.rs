@@ -4,17 +4,52 @@
 
 use servo::{ServoUrl, is_reg_domain};
 
+use crate::data_storage::search_engines::{
+    SearchEngine, default_search_engine, search_engine_for_keyword,
+};
+
 /// Interpret an input URL.
 ///
-/// If this is not a valid URL, try to "fix" it by adding a scheme or if all else fails,
-/// interpret the string as a search term.
-pub(crate) fn location_bar_input_to_url(request: &str, searchpage: &str) -> Option<ServoUrl> {
+/// If this starts with the keyword of a search engine, search with that engine. If this is not
+/// a valid URL, try to "fix" it by adding a scheme or if all else fails, interpret the string as
+/// a search term for the default search engine.
+pub(crate) fn location_bar_input_to_url(
+    request: &str,
+    search_engines: &[SearchEngine],
+) -> Option<ServoUrl> {
     let request = request.trim();
-    ServoUrl::parse(request)
-        .ok()
+    try_as_keyword_search(request, search_engines)
+        .or_else(|| ServoUrl::parse(request).ok())
         .or_else(|| try_as_file(request))
         .or_else(|| try_as_domain(request))
-        .or_else(|| try_as_search_page(request, searchpage))
+        .or_else(|| default_search_engine(search_engines)?.search_url_for(request))
+}
+
+/// The search engine and search terms that the location bar input will search for, if it is a
+/// search rather than an address.
+pub(crate) fn location_bar_input_to_search<'a, 'b>(
+    request: &'b str,
+    search_engines: &'a [SearchEngine],
+) -> Option<(&'a SearchEngine, &'b str)> {
+    let request = request.trim();
+    if let Some((keyword, terms)) = request.split_once(' ')
+        && let Some(search_engine) = search_engine_for_keyword(search_engines, keyword)
+    {
+        return Some((search_engine, terms.trim()));
+    }
+    if request.is_empty()
+        || ServoUrl::parse(request).is_ok()
+        || try_as_file(request).is_some()
+        || try_as_domain(request).is_some()
+    {
+        return None;
+    }
+    Some((default_search_engine(search_engines)?, request))
+}
+
+fn try_as_keyword_search(request: &str, search_engines: &[SearchEngine]) -> Option<ServoUrl> {
+    let (keyword, terms) = request.split_once(' ')?;
+    search_engine_for_keyword(search_engines, keyword)?.search_url_for(terms.trim())
 }
 
 fn try_as_file(request: &str) -> Option<ServoUrl> {
@@ -35,10 +70,3 @@ fn try_as_domain(request: &str) -> Option<ServoUrl> {
     }
     None
 }
-
-fn try_as_search_page(request: &str, searchpage: &str) -> Option<ServoUrl> {
-    if request.is_empty() {
-        return None;
-    }
-    ServoUrl::parse(&searchpage.replace("%s", request)).ok()
-}
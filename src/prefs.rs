@@ -6,6 +6,8 @@ use std::path::PathBuf;
 
 use servo::{Opts, PrefValue, Preferences};
 
+use crate::misc_utils::ca_certificates::write_certificate_bundle;
+
 pub(crate) static EXPERIMENTAL_PREFS: &[&str] = &[
     "dom_async_clipboard_enabled",
     "dom_fontface_enabled",
@@ -25,7 +27,8 @@ pub(crate) static EXPERIMENTAL_PREFS: &[&str] = &[
 
 #[derive(Clone)]
 pub(crate) struct ServoShellPreferences {
-    /// URL string of the search engine page with '%s' standing in for the search term.
+    /// URL string of the search engine page with '%s' standing in for the search term, used
+    /// for the default search engine of a new profile.
     /// For example <https://duckduckgo.com/html/?q=%s>.
     pub searchpage: String,
 }
@@ -64,6 +67,8 @@ pub(crate) fn get_preferences() -> Preferences {
 pub(crate) fn get_opts() -> Opts {
     Opts {
         config_dir: Some(default_config_dir()),
+        certificate_path: write_certificate_bundle()
+            .map(|path| path.to_string_lossy().into_owned()),
         ..Default::default()
     }
 }
@@ -0,0 +1,460 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The privileged `prism:` scheme, which serves the browser's internal pages such as
+//! `prism:history`, and a JSON bridge under `prism:api/` that lets those pages read and change
+//! the browser data.
+//!
+//! Web content must never reach the bridge, so every API call has to carry a secret token that
+//! is only written into the internal pages when they are loaded as a top-level document. The
+//! pages refuse to be framed, and requests made by web content, which is told by their origin
+//! and referrer, are refused.
+//!
+//! The exceptions are `prism:certificate`, which the certificate error page uses to describe the
+//! certificate of the server and to make exceptions for it, and `prism:https-fallback`, which the
+//! network error page uses to load a site over HTTP when HTTPS-only mode couldn't upgrade it.
+//! Those pages are shown in place of a site, so they get a separate token, and can only make
+//! exceptions for the origin they are shown for.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{BuildHasher, RandomState};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use headers::{ContentType, HeaderMapExt, HeaderName, HeaderValue};
+use rustls::pki_types::CertificateDer;
+use servo::protocol_handler::{
+    DoneChannel, FetchContext, ProtocolHandler, Request, ResourceFetchTiming, Response,
+    ResponseBody,
+};
+use url::Url;
+
+use crate::data_storage::BrowserDataConnection;
+use crate::error_pages::error_page_token_allows;
+use crate::misc_utils::certificates::{
+    apply_certificate_exceptions, certificate_details, fetch_server_certificate,
+};
+use crate::misc_utils::https_only::was_upgraded;
+use crate::misc_utils::reader_mode::{READER_SETTINGS_SETTING, opened_article};
+use crate::resource_protocol::{Requester, json_response, read_request_body};
+use crate::resources::read_resource;
+
+pub(crate) const PRISM_SCHEME: &str = "prism";
+pub(crate) const HISTORY_PAGE_URL: &str = "prism:history";
+pub(crate) const BOOKMARKS_PAGE_URL: &str = "prism:bookmarks";
+pub(crate) const DOWNLOADS_PAGE_URL: &str = "prism:downloads";
+pub(crate) const READER_PAGE_URL: &str = "prism:reader";
+
+/// The internal pages, which are served from `resources/prism_protocol/<page>.html`.
+const PAGES: &[&str] = &["history", "bookmarks", "downloads", "reader"];
+
+/// Files that the pages share. They contain nothing private, so any page may load them.
+const SHARED_FILES: &[&str] = &["prism.css", "prism.js"];
+
+const API_PATH_PREFIX: &str = "api/";
+/// The API calls that change the browser data. They have to be posted, with their parameters
+/// as a JSON object, so that they can't be made by following a link.
+const POST_ENDPOINTS: &[&str] = &[
+    "history/delete",
+    "history/clear",
+    "bookmarks/add",
+    "bookmarks/delete",
+    "reader/settings",
+];
+const CERTIFICATE_ENDPOINTS: &[&str] = &["certificate", "certificate/allow"];
+const HTTPS_FALLBACK_ENDPOINTS: &[&str] = &["https-fallback", "https-fallback/allow"];
+
+/// How many history entries or bookmarks are returned when the page doesn't ask for a number.
+const DEFAULT_LIMIT: usize = 500;
+
+pub struct PrismProtocolHandler {
+    token: String,
+    /// The connection that the API calls read and change the browser data with. It is shared
+    /// with the threads that fetch certificates.
+    connection: Arc<Mutex<BrowserDataConnection>>,
+}
+
+impl Default for PrismProtocolHandler {
+    fn default() -> Self {
+        Self {
+            token: generate_token(),
+            connection: Arc::new(Mutex::new(BrowserDataConnection::new())),
+        }
+    }
+}
+
+impl PrismProtocolHandler {
+    fn response_for_page(&self, request: &Request, page: &str) -> Response {
+        if !request.is_navigation_request() {
+            return Response::network_internal_error("Internal pages can only be navigated to");
+        }
+        let Some(html) = read_resource(&format!("prism_protocol/{page}.html")) else {
+            return Response::network_internal_error("Opening file failed");
+        };
+        let html = String::from_utf8_lossy(&html).replacen(
+            "</head>",
+            &format!(
+                "<meta name=\"prism-token\" content=\"{}\">\n</head>",
+                self.token
+            ),
+            1,
+        );
+
+        let mut response = Response::new(
+            request.current_url(),
+            ResourceFetchTiming::new(request.timing_type()),
+        );
+        response.headers.typed_insert(ContentType::html());
+        response.headers.insert(
+            HeaderName::from_static("content-security-policy"),
+            HeaderValue::from_static("frame-ancestors 'none'"),
+        );
+        *response.body.lock() = ResponseBody::Done(html.into_bytes());
+        response
+    }
+
+    fn response_for_shared_file(request: &Request, file_name: &str) -> Response {
+        let Some(contents) = read_resource(&format!("prism_protocol/{file_name}")) else {
+            return Response::network_internal_error("Opening file failed");
+        };
+        let mut response = Response::new(
+            request.current_url(),
+            ResourceFetchTiming::new(request.timing_type()),
+        );
+        let mime = mime_guess::from_path(file_name).first_or_octet_stream();
+        response.headers.typed_insert(ContentType::from(mime));
+        *response.body.lock() = ResponseBody::Done(contents.into_owned());
+        response
+    }
+
+    fn response_for_api(&self, request: &mut Request, endpoint: &str) -> Response {
+        let url = request.current_url();
+        let mut parameters: HashMap<String, String> =
+            url.as_url().query_pairs().into_owned().collect();
+        if parameters.get("token") != Some(&self.token) {
+            return Response::network_internal_error("Not allowed");
+        }
+        if POST_ENDPOINTS.contains(&endpoint) {
+            if request.method.as_str() != "POST" {
+                return Response::network_internal_error("Not allowed");
+            }
+            let Some(posted) = posted_parameters(request) else {
+                return Response::network_internal_error("Invalid parameters");
+            };
+            parameters.extend(posted);
+        }
+        match api_call(&self.connection.lock().unwrap(), endpoint, &parameters) {
+            Ok(json) => json_response(request, &json),
+            Err(error) => Response::network_internal_error(error),
+        }
+    }
+
+    /// The origin of the site that an error page is shown for, which is the `origin` parameter,
+    /// read from the JSON body of a POST request or from the query otherwise. The request has to
+    /// come from that origin, with the same scheme, host and port, and carry the token of the
+    /// page.
+    fn error_page_origin(request: &mut Request) -> Option<Url> {
+        let parameters = if request.method.as_str() == "POST" {
+            posted_parameters(request)?
+        } else {
+            let url = request.current_url();
+            url.as_url().query_pairs().into_owned().collect()
+        };
+        let origin = Url::parse(parameters.get("origin")?).ok()?;
+        let referrer = request.referrer.to_url()?;
+        if !origin.origin().is_tuple() || referrer.origin() != origin.origin() {
+            return None;
+        }
+        if !error_page_token_allows(
+            parameters.get("token")?,
+            &origin.origin().ascii_serialization(),
+        ) {
+            return None;
+        }
+        Some(origin)
+    }
+
+    /// Describe the certificate that the server at the `origin` parameter presents, or make a
+    /// permanent exception for it. The certificate is fetched from the server, on a separate
+    /// thread, so that the page can't make an exception for a certificate of its choosing.
+    fn response_for_certificate(
+        &self,
+        request: &mut Request,
+        endpoint: &str,
+        context: &FetchContext,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        let allow = endpoint == "certificate/allow";
+        if allow && request.method.as_str() != "POST" {
+            return Box::pin(std::future::ready(Response::network_internal_error(
+                "Not allowed",
+            )));
+        }
+        let Some(origin) = Self::error_page_origin(request) else {
+            return Box::pin(std::future::ready(Response::network_internal_error(
+                "Not allowed",
+            )));
+        };
+        let (Some(host), Some(port)) = (origin.host_str(), origin.port_or_known_default()) else {
+            return Box::pin(std::future::ready(Response::network_internal_error(
+                "Invalid origin",
+            )));
+        };
+
+        let host = host.to_owned();
+        let connection = self.connection.clone();
+        let state = context.state.clone();
+        let mut response = json_response(request, &serde_json::Value::Null);
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let spawned = thread::Builder::new()
+            .name("CertificateFetcher".into())
+            .spawn(move || {
+                let json = fetch_server_certificate(&host, port).and_then(|der| {
+                    let details = certificate_details(&der)?;
+                    let connection = connection.lock().unwrap();
+                    Ok(if allow {
+                        connection.add_certificate_exception(&host, &details.fingerprint, &der);
+                        state
+                            .override_manager
+                            .add_override(&CertificateDer::from(der));
+                        serde_json::json!({ "ok": true })
+                    } else {
+                        serde_json::json!({
+                            "subject": details.subject,
+                            "issuer": details.issuer,
+                            "not_before": details.not_before,
+                            "not_after": details.not_after,
+                            "fingerprint": details.fingerprint,
+                            "exception": connection
+                                .has_certificate_exception(&host, &details.fingerprint),
+                        })
+                    })
+                });
+                let _ = sender.send(json);
+            });
+        if spawned.is_err() {
+            return Box::pin(std::future::ready(Response::network_internal_error(
+                "Could not fetch the certificate",
+            )));
+        }
+        Box::pin(async move {
+            match receiver.await {
+                Ok(Ok(json)) => {
+                    *response.body.lock() = ResponseBody::Done(json.to_string().into_bytes());
+                    response
+                }
+                Ok(Err(error)) => Response::network_internal_error(error),
+                Err(_) => Response::network_internal_error("Could not fetch the certificate"),
+            }
+        })
+    }
+
+    /// Tell whether the host of the `origin` parameter was upgraded by HTTPS-only mode, or make a
+    /// permanent exception for it so that it is loaded over HTTP.
+    ///
+    /// The exception is written with the connection of this handler, while the browser reads the
+    /// exceptions with its own connection. It reads them from the database for every load, so
+    /// the exception applies as soon as the error page loads the site over HTTP.
+    fn response_for_https_fallback(&self, request: &mut Request, endpoint: &str) -> Response {
+        let allow = endpoint == "https-fallback/allow";
+        if allow && request.method.as_str() != "POST" {
+            return Response::network_internal_error("Not allowed");
+        }
+        let Some(origin) = Self::error_page_origin(request) else {
+            return Response::network_internal_error("Not allowed");
+        };
+        let Some(host) = origin.host_str() else {
+            return Response::network_internal_error("Invalid origin");
+        };
+        if !was_upgraded(host) {
+            return json_response(request, &serde_json::json!({ "upgraded": false }));
+        }
+        if allow {
+            self.connection.lock().unwrap().add_https_exception(host);
+        }
+        json_response(request, &serde_json::json!({ "upgraded": true }))
+    }
+}
+
+impl ProtocolHandler for PrismProtocolHandler {
+    /// The internal pages fetch their data from the JSON bridge.
+    fn is_fetchable(&self) -> bool {
+        true
+    }
+
+    /// The error pages of HTTPS sites fetch from here, and need to send their referrer.
+    fn is_secure(&self) -> bool {
+        true
+    }
+
+    fn load(
+        &self,
+        request: &mut Request,
+        _done_chan: &mut DoneChannel,
+        context: &FetchContext,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        apply_certificate_exceptions(context, &self.connection.lock().unwrap());
+        let url = request.current_url();
+        let path = url.path();
+        let requester = Requester::of(request);
+
+        if CERTIFICATE_ENDPOINTS.contains(&path) {
+            return self.response_for_certificate(request, path, context);
+        }
+        let response = if HTTPS_FALLBACK_ENDPOINTS.contains(&path) {
+            self.response_for_https_fallback(request, path)
+        } else if requester == Requester::Web {
+            Response::network_internal_error("Not allowed")
+        } else if let Some(endpoint) = path.strip_prefix(API_PATH_PREFIX) {
+            self.response_for_api(request, endpoint)
+        } else if PAGES.contains(&path) {
+            // The pages are opened by the browser or from each other, never loaded as a
+            // subresource.
+            if requester == Requester::Navigation {
+                self.response_for_page(request, path)
+            } else {
+                Response::network_internal_error("Not allowed")
+            }
+        } else if SHARED_FILES.contains(&path) {
+            Self::response_for_shared_file(request, path)
+        } else {
+            Response::network_internal_error("Invalid path")
+        };
+        Box::pin(std::future::ready(response))
+    }
+}
+
+/// A secret that is hard to guess, as `RandomState` is seeded by the operating system.
+pub(crate) fn generate_token() -> String {
+    let random = || RandomState::new().hash_one(std::time::SystemTime::now());
+    format!("{:016x}{:016x}", random(), random())
+}
+
+/// The parameters posted as a JSON object. Values other than strings are kept as JSON.
+fn posted_parameters(request: &mut Request) -> Option<HashMap<String, String>> {
+    let serde_json::Value::Object(object) =
+        serde_json::from_slice(&read_request_body(request)?).ok()?
+    else {
+        return None;
+    };
+    Some(
+        object
+            .into_iter()
+            .map(|(name, value)| match value {
+                serde_json::Value::String(value) => (name, value),
+                value => (name, value.to_string()),
+            })
+            .collect(),
+    )
+}
+
+/// Run a call to the JSON bridge, returning the JSON to respond with.
+fn api_call(
+    connection: &BrowserDataConnection,
+    endpoint: &str,
+    parameters: &HashMap<String, String>,
+) -> Result<serde_json::Value, &'static str> {
+    let parameter = |name: &str| parameters.get(name).map(String::as_str);
+    let query = parameter("q").unwrap_or("");
+    let limit = parameter("limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_LIMIT);
+    let ok = serde_json::json!({ "ok": true });
+    Ok(match endpoint {
+        "history" => {
+            let pages = connection.search_browser_history(query, limit);
+            pages
+                .into_iter()
+                .map(|page| {
+                    serde_json::json!({
+                        "title": page.title,
+                        "url": page.url,
+                        "visit_count": page.visit_count,
+                        "last_visited": page.last_visited.and_utc().to_rfc3339(),
+                    })
+                })
+                .collect()
+        }
+        "history/delete" => {
+            connection.delete_from_browser_history(parameter("url").ok_or("Missing url")?);
+            ok
+        }
+        "history/clear" => {
+            connection.clear_browser_history();
+            ok
+        }
+        "bookmarks" => connection
+            .search_bookmarks(query, limit)
+            .into_iter()
+            .map(|bookmark| {
+                serde_json::json!({
+                    "id": bookmark.id,
+                    "title": bookmark.title,
+                    "url": bookmark.url,
+                    "time_modified": bookmark.time_modified.and_utc().to_rfc3339(),
+                })
+            })
+            .collect(),
+        "bookmarks/add" => {
+            let url = parameter("url").ok_or("Missing url")?;
+            let title = parameter("title")
+                .filter(|title| !title.is_empty())
+                .unwrap_or(url);
+            connection.add_bookmark(title.to_owned(), url.to_owned());
+            ok
+        }
+        "bookmarks/delete" => {
+            let id = parameter("id")
+                .and_then(|id| id.parse().ok())
+                .ok_or("Missing id")?;
+            connection.delete_bookmark(id);
+            ok
+        }
+        "downloads" => connection
+            .get_download_history()
+            .into_iter()
+            .map(|download| {
+                serde_json::json!({
+                    "title": download.title,
+                    "url": download.url,
+                    "save_path": download.save_path.to_string_lossy(),
+                    "file_size_in_bytes": download.file_size_in_bytes,
+                    "time_downloaded": download.time_downloaded.and_utc().to_rfc3339(),
+                })
+            })
+            .collect(),
+        "reader" => {
+            let url = parameter("url").ok_or("Missing url")?;
+            let settings = connection
+                .get_setting(READER_SETTINGS_SETTING)
+                .and_then(|settings| serde_json::from_str::<serde_json::Value>(&settings).ok())
+                .filter(serde_json::Value::is_object)
+                .unwrap_or_else(|| serde_json::json!({}));
+            // The reader page can still show its settings and a link to the article after a
+            // restart, when the article itself has been forgotten.
+            let article = opened_article(url).map(|article| {
+                serde_json::json!({
+                    "title": article.title,
+                    "byline": article.byline,
+                    "site_name": article.site_name,
+                    "content": article.content,
+                    "reading_minutes": article.reading_minutes(),
+                })
+            });
+            serde_json::json!({ "article": article, "settings": settings })
+        }
+        "reader/settings" => {
+            let settings = parameter("settings").ok_or("Missing settings")?;
+            if !serde_json::from_str::<serde_json::Value>(settings)
+                .is_ok_and(|value| value.is_object())
+            {
+                return Err("Invalid settings");
+            }
+            connection.set_setting(READER_SETTINGS_SETTING, settings);
+            ok
+        }
+        _ => return Err("Invalid endpoint"),
+    })
+}
@@ -2,104 +2,370 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-//! This protocol handler loads files from the <resources_dir_path()>/protocol/resource directory,
+//! This protocol handler loads files from the `resources/resource_protocol` directory,
 //! sanitizing the path to prevent path escape attacks.
-//! For security reasons, loads are only allowed if the referrer has a 'resource' or
-//! 'servo' scheme.
+//! For security reasons, only the files in [`ALLOWED_FILES`] are served, and only to the
+//! requesters their [`Exposure`] allows, so that web content can't embed the internal pages to
+//! fingerprint the browser.
+//!
+//! Paths under `/api/` are not files, but browser data that the pages in the directory need,
+//! served as JSON. These additionally require the token that is written into the pages.
 
-use std::fs::File;
 use std::future::Future;
-use std::io::BufReader;
 use std::pin::Pin;
+use std::sync::Mutex;
 
 use headers::{ContentType, HeaderMapExt};
+use ipc_channel::ipc;
+use percent_encoding::percent_decode_str;
 use servo::protocol_handler::{
-    DoneChannel, FILE_CHUNK_SIZE, FetchContext, ProtocolHandler, RelativePos, Request,
-    ResourceFetchTiming, Response, ResponseBody,
+    BodyChunkRequest, BodyChunkResponse, Destination, DoneChannel, FetchContext, Origin,
+    ProtocolHandler, Request, ResourceFetchTiming, Response, ResponseBody,
 };
-use tokio::sync::mpsc::unbounded_channel;
+use url::Url;
 
-#[derive(Default)]
-pub struct ResourceProtocolHandler {}
+use crate::data_storage::BrowserDataConnection;
+use crate::data_storage::pinned_sites::PinnedSite;
+use crate::data_storage::search_engines::default_search_engine;
+use crate::misc_utils::certificates::apply_certificate_exceptions;
+use crate::prism_protocol::generate_token;
+use crate::resources::read_resource;
 
-impl ResourceProtocolHandler {
-    pub fn response_for_path(
-        request: &mut Request,
-        done_chan: &mut DoneChannel,
-        context: &FetchContext,
-        path: &str,
-    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
-        if path.contains("..") || !path.starts_with("/") {
-            return Box::pin(std::future::ready(Response::network_internal_error(
-                "Invalid path",
-            )));
-        }
+const API_PATH_PREFIX: &str = "/api/";
+
+/// Empty places on the new tab page are filled with the most visited sites up to this number.
+const MINIMUM_NEW_TAB_SITES: usize = 6;
+
+/// The schemes of the browser's own pages, whose loads count as [`Requester::Internal`].
+const INTERNAL_SCHEMES: &[&str] = &["resource", "prism", "servo"];
 
-        let path = if let Some(path) = path.strip_prefix("/") {
-            path
+/// Which requesters may load a file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Exposure {
+    /// A page, which can only be navigated to.
+    Page,
+    /// A file that the pages use, which can only be loaded by them.
+    Internal,
+    /// A file that is safe for any page to load, such as the images of the error pages, which
+    /// are shown in place of web content.
+    Public,
+}
+
+/// The files that are served, as paths relative to the directory.
+const ALLOWED_FILES: &[(&str, Exposure)] = &[
+    ("newtab.html", Exposure::Page),
+    ("license.html", Exposure::Page),
+    ("preferences.html", Exposure::Page),
+    ("newtab.css", Exposure::Internal),
+    ("search.svg", Exposure::Internal),
+    ("servo-color-negative-no-container.svg", Exposure::Public),
+    ("servo-color-positive-no-container.svg", Exposure::Public),
+];
+
+/// Who is loading a resource.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Requester {
+    /// A top-level navigation, such as opening a new tab. Frames don't count, so that web
+    /// content can't embed the pages.
+    Navigation,
+    /// One of the browser's own pages.
+    Internal,
+    /// Web content, or anything else that can't be told to be one of the above.
+    Web,
+}
+
+impl Requester {
+    /// Classify a request by whether it loads a top-level document, by the scheme of the origin
+    /// that made it, and by its referrer.
+    ///
+    /// Web content can leave out the referrer, so a web origin decides on its own. The origin
+    /// has no scheme for loads by the browser and by pages with an opaque origin, which includes
+    /// all pages with an [`INTERNAL_SCHEMES`] scheme but also sandboxed web content. So other
+    /// than top-level navigations, such loads only count as internal if their referrer is one
+    /// of the browser's own pages.
+    pub(crate) fn classify(
+        is_top_level_document: bool,
+        origin_scheme: Option<&str>,
+        referrer: Option<&Url>,
+    ) -> Self {
+        let is_internal = |scheme: &str| INTERNAL_SCHEMES.contains(&scheme);
+        if origin_scheme.is_some_and(|scheme| !is_internal(scheme))
+            || referrer.is_some_and(|url| !is_internal(url.scheme()))
+        {
+            Requester::Web
+        } else if is_top_level_document {
+            Requester::Navigation
+        } else if origin_scheme.is_some() || referrer.is_some() {
+            Requester::Internal
         } else {
-            return Box::pin(std::future::ready(Response::network_internal_error(
-                "Invalid path",
-            )));
+            Requester::Web
+        }
+    }
+
+    /// Classify a request to one of the browser's own protocols.
+    pub(crate) fn of(request: &Request) -> Self {
+        let origin_scheme = match &request.origin {
+            Origin::Origin(origin) => origin.scheme(),
+            Origin::Client => None,
         };
+        Self::classify(
+            request.destination == Destination::Document,
+            origin_scheme,
+            request.referrer.to_url().map(|referrer| referrer.as_url()),
+        )
+    }
+}
 
-        let file_path = crate::resources::resources_dir_path()
-            .join("resource_protocol")
-            .join(path);
+/// Turn the path of a `resource:` URL into a path relative to the directory, or `None` if it
+/// could escape it. Percent-encoded characters are decoded first, so that `%2e%2e` can't be
+/// used to sneak in a `..` segment.
+pub(crate) fn sanitize_path(path: &str) -> Option<String> {
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let path = path.strip_prefix('/')?;
+    let is_safe = !path.is_empty()
+        && !path.contains('\\')
+        && !path.contains('\0')
+        && path.split('/').all(|segment| {
+            !segment.is_empty() && segment != "." && segment != ".." && !segment.contains(':')
+        });
+    is_safe.then(|| path.to_owned())
+}
 
-        if !file_path.exists() || file_path.is_dir() {
-            return Box::pin(std::future::ready(Response::network_internal_error(
-                "Invalid path",
-            )));
+fn exposure(path: &str) -> Option<Exposure> {
+    ALLOWED_FILES
+        .iter()
+        .find(|(file, _)| *file == path)
+        .map(|(_, exposure)| *exposure)
+}
+
+/// Whether the file at the given sanitized path may be served to the requester.
+pub(crate) fn is_allowed(path: &str, requester: Requester) -> bool {
+    let Some(exposure) = exposure(path) else {
+        return false;
+    };
+    match exposure {
+        Exposure::Page => requester == Requester::Navigation,
+        Exposure::Internal => requester == Requester::Internal,
+        Exposure::Public => true,
+    }
+}
+
+pub struct ResourceProtocolHandler {
+    /// The secret that the pages are given for calling the JSON API.
+    token: String,
+    /// The connection that the JSON API reads and writes the browser data with.
+    connection: Mutex<BrowserDataConnection>,
+}
+
+impl Default for ResourceProtocolHandler {
+    fn default() -> Self {
+        Self {
+            token: generate_token(),
+            connection: Mutex::new(BrowserDataConnection::new()),
         }
+    }
+}
 
-        let response = if let Ok(file) = File::open(file_path.clone()) {
-            let mut response = Response::new(
-                request.current_url(),
-                ResourceFetchTiming::new(request.timing_type()),
-            );
-            let reader = BufReader::with_capacity(FILE_CHUNK_SIZE, file);
-
-            // Set Content-Type header.
-            let mime = mime_guess::from_path(file_path).first_or_octet_stream();
-            response.headers.typed_insert(ContentType::from(mime));
-
-            // Setup channel to receive cross-thread messages about the file fetch
-            // operation.
-            let (mut done_sender, done_receiver) = unbounded_channel();
-            *done_chan = Some((done_sender.clone(), done_receiver));
-
-            *response.body.lock() = ResponseBody::Receiving(vec![]);
-
-            context.filemanager.lock().fetch_file_in_chunks(
-                &mut done_sender,
-                reader,
-                response.body.clone(),
-                context.cancellation_listener.clone(),
-                RelativePos::full_range(),
-            );
-
-            response
-        } else {
-            Response::network_internal_error("Opening file failed")
+impl ResourceProtocolHandler {
+    /// Respond with the browser data for the given path below [`API_PATH_PREFIX`].
+    fn response_for_api_path(&self, request: &mut Request, path: &str) -> Response {
+        let url = request.current_url();
+        let has_token = url
+            .as_url()
+            .query_pairs()
+            .any(|(name, value)| name == "token" && value == self.token.as_str());
+        if !has_token {
+            return Response::network_internal_error("Not allowed");
+        }
+        let connection = self.connection.lock().unwrap();
+        let json = match path {
+            // The search engine that the new tab page searches with.
+            "search-engine" => {
+                let search_engines = connection.load_search_engines();
+                let Some(search_engine) = default_search_engine(&search_engines) else {
+                    return Response::network_internal_error("No search engine");
+                };
+                serde_json::json!({
+                    "name": search_engine.name,
+                    "search_url": search_engine.search_url,
+                    "icon_url": search_engine.icon_url,
+                })
+            }
+            "pinned-sites" => new_tab_sites(&connection),
+            // Replaces the pinned sites with the JSON array that is posted.
+            "pinned-sites/save" => {
+                if request.method.as_str() != "POST" {
+                    return Response::network_internal_error("Not allowed");
+                }
+                let Some(pinned_sites) = read_request_body(request)
+                    .and_then(|body| parse_pinned_sites(&String::from_utf8_lossy(&body)))
+                else {
+                    return Response::network_internal_error("Invalid pinned sites");
+                };
+                connection.save_pinned_sites(&pinned_sites);
+                new_tab_sites(&connection)
+            }
+            _ => return Response::network_internal_error("Invalid path"),
         };
 
-        Box::pin(std::future::ready(response))
+        json_response(request, &json)
+    }
+
+    /// Respond with a page, with the token for the JSON API written into it.
+    fn response_for_page(&self, request: &Request, path: &str) -> Response {
+        let Some(html) = read_resource(&format!("resource_protocol/{path}")) else {
+            return Response::network_internal_error("Opening file failed");
+        };
+        let html = String::from_utf8_lossy(&html).replacen(
+            "</head>",
+            &format!(
+                "<meta name=\"resource-token\" content=\"{}\">\n</head>",
+                self.token
+            ),
+            1,
+        );
+
+        let mut response = Response::new(
+            request.current_url(),
+            ResourceFetchTiming::new(request.timing_type()),
+        );
+        response.headers.typed_insert(ContentType::html());
+        *response.body.lock() = ResponseBody::Done(html.into_bytes());
+        response
+    }
+
+    /// Respond with the file at the given sanitized path.
+    pub fn response_for_path(request: &Request, path: &str) -> Response {
+        let Some(contents) = read_resource(&format!("resource_protocol/{path}")) else {
+            return Response::network_internal_error("Invalid path");
+        };
+        let mut response = Response::new(
+            request.current_url(),
+            ResourceFetchTiming::new(request.timing_type()),
+        );
+        let mime = mime_guess::from_path(path).first_or_octet_stream();
+        response.headers.typed_insert(ContentType::from(mime));
+        *response.body.lock() = ResponseBody::Done(contents.into_owned());
+        response
+    }
+}
+
+/// A response to the given request with the given JSON as its body.
+pub(crate) fn json_response(request: &Request, json: &serde_json::Value) -> Response {
+    let mut response = Response::new(
+        request.current_url(),
+        ResourceFetchTiming::new(request.timing_type()),
+    );
+    response.headers.typed_insert(ContentType::json());
+    *response.body.lock() = ResponseBody::Done(json.to_string().into_bytes());
+    response
+}
+
+/// Read the whole body of the request, or `None` if it has none or it could not be read.
+pub(crate) fn read_request_body(request: &mut Request) -> Option<Vec<u8>> {
+    let stream = request.body.as_mut()?.take_stream()?;
+    let (chunk_sender, chunk_receiver) = ipc::channel().ok()?;
+    stream.send(BodyChunkRequest::Connect(chunk_sender)).ok()?;
+    stream.send(BodyChunkRequest::Chunk).ok()?;
+
+    let mut body = vec![];
+    loop {
+        match chunk_receiver.recv().ok()? {
+            BodyChunkResponse::Chunk(bytes) => {
+                body.extend_from_slice(&bytes);
+                stream.send(BodyChunkRequest::Chunk).ok()?;
+            }
+            BodyChunkResponse::Done => return Some(body),
+            BodyChunkResponse::Error => return None,
+        }
     }
 }
 
+/// The sites shown on the new tab page: the pinned ones, followed by the most visited ones
+/// until there are at least [`MINIMUM_NEW_TAB_SITES`].
+fn new_tab_sites(connection: &BrowserDataConnection) -> serde_json::Value {
+    let pinned_sites = connection.load_pinned_sites();
+    let top_sites = connection
+        .top_sites(MINIMUM_NEW_TAB_SITES * 2)
+        .into_iter()
+        .filter(|page| !pinned_sites.iter().any(|pinned| pinned.url == page.url))
+        .take(MINIMUM_NEW_TAB_SITES.saturating_sub(pinned_sites.len()));
+
+    let pinned_sites = pinned_sites.iter().map(
+        |pinned| serde_json::json!({ "title": pinned.title, "url": pinned.url, "pinned": true }),
+    );
+    let top_sites = top_sites
+        .map(|page| serde_json::json!({ "title": page.title, "url": page.url, "pinned": false }));
+    pinned_sites.chain(top_sites).collect()
+}
+
+/// Parse a JSON array of `{"title": …, "url": …}` objects, ignoring entries whose URL is not
+/// a valid web or file URL.
+pub(crate) fn parse_pinned_sites(json: &str) -> Option<Vec<PinnedSite>> {
+    let json: serde_json::Value = serde_json::from_str(json).ok()?;
+    Some(
+        json.as_array()?
+            .iter()
+            .filter_map(|site| {
+                let url = Url::parse(site.get("url")?.as_str()?).ok()?;
+                if !matches!(url.scheme(), "http" | "https" | "file") {
+                    return None;
+                }
+                let title = site
+                    .get("title")
+                    .and_then(|title| title.as_str())
+                    .filter(|title| !title.trim().is_empty())
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| url.host_str().unwrap_or(url.as_str()).to_owned());
+                Some(PinnedSite {
+                    title,
+                    url: url.into(),
+                })
+            })
+            .collect(),
+    )
+}
+
 impl ProtocolHandler for ResourceProtocolHandler {
+    /// The new tab page fetches its data from [`API_PATH_PREFIX`].
+    fn is_fetchable(&self) -> bool {
+        true
+    }
+
     fn load(
         &self,
         request: &mut Request,
-        done_chan: &mut DoneChannel,
+        _done_chan: &mut DoneChannel,
         context: &FetchContext,
     ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        apply_certificate_exceptions(context, &self.connection.lock().unwrap());
         let url = request.current_url();
+        let requester = Requester::of(request);
 
-        // TODO: Check referrer.
-        //       We unexpectedly get `NoReferrer` for all requests from the newtab page.
+        if let Some(path) = url.path().strip_prefix(API_PATH_PREFIX) {
+            let response = if requester == Requester::Internal {
+                self.response_for_api_path(request, path)
+            } else {
+                Response::network_internal_error("Not allowed")
+            };
+            return Box::pin(std::future::ready(response));
+        }
 
-        Self::response_for_path(request, done_chan, context, url.path())
+        let Some(path) = sanitize_path(url.path()) else {
+            return Box::pin(std::future::ready(Response::network_internal_error(
+                "Invalid path",
+            )));
+        };
+        if !is_allowed(&path, requester) {
+            return Box::pin(std::future::ready(Response::network_internal_error(
+                "Not allowed",
+            )));
+        }
+        let response = if exposure(&path) == Some(Exposure::Page) {
+            self.response_for_page(request, &path)
+        } else {
+            Self::response_for_path(request, &path)
+        };
+        Box::pin(std::future::ready(response))
     }
 }
@@ -2,14 +2,32 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+//! The files in the `resources` directory, which are compiled into the executable so that it
+//! can be moved or packaged without them.
+//!
+//! Files in an override directory take precedence over the compiled in ones, so that pages can
+//! be edited without rebuilding, or themed. This is the directory in the `PRISM_RESOURCES_DIR`
+//! environment variable if it is set, and otherwise the `resources` directory in the config
+//! directory if it exists.
+
+use std::borrow::Cow;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::OnceLock;
 use std::{env, fs};
 
-use cfg_if::cfg_if;
 use servo::resources::{self, Resource};
 
-static CMD_RESOURCE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+use crate::error_pages::{bad_cert_page, net_error_page};
+use crate::prefs::default_config_dir;
+
+const OVERRIDE_DIR_VARIABLE: &str = "PRISM_RESOURCES_DIR";
+
+/// The paths of the compiled in files, relative to the `resources` directory and with `/`
+/// separators, with their contents. This is generated by the build script.
+static EMBEDDED_RESOURCES: &[(&str, &[u8])] =
+    include!(concat!(env!("OUT_DIR"), "/embedded_resources.rs"));
+
+static OVERRIDE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
 
 struct ResourceReader;
 
@@ -17,70 +35,45 @@ pub fn init() {
     resources::set(Box::new(ResourceReader));
 }
 
-pub(crate) fn resources_dir_path() -> PathBuf {
-    // This needs to be called before the process is sandboxed
-    // as we only give permission to read inside the resources directory,
-    // not the permissions the "search" for the resources directory.
-    let mut dir = CMD_RESOURCE_DIR.lock().unwrap();
-    if let Some(ref path) = *dir {
-        return PathBuf::from(path);
-    }
-
-    // Try ./resources and ./Resources relative to the directory containing the
-    // canonicalised executable path, then each of its ancestors.
-    let mut path = env::current_exe().unwrap().canonicalize().unwrap();
-    while path.pop() {
-        path.push("resources");
-        if path.is_dir() {
-            *dir = Some(path);
-            return dir.clone().unwrap();
-        }
-        path.pop();
-
-        // Check for Resources on mac when using a case sensitive filesystem.
-        path.push("Resources");
-        if path.is_dir() {
-            *dir = Some(path);
-            return dir.clone().unwrap();
-        }
-        path.pop();
-    }
-
-    cfg_if! {
-        if #[cfg(servo_production)] {
-            panic!("Can't find resources directory")
-        } else {
-            // Static assert that this is really a non-production build, rather
-            // than a failure of the build script’s production check.
-            const _: () = assert!(cfg!(servo_do_not_use_in_production));
-
-            // Try ./resources in the current directory, then each of its ancestors.
-            // Not to be used in production builds without considering the security implications!
-            let mut path = std::env::current_dir().unwrap();
-            loop {
-                path.push("resources");
-                if path.is_dir() {
-                    *dir = Some(path);
-                    return dir.clone().unwrap();
-                }
-                path.pop();
+/// The directory whose files take precedence over the compiled in ones, if there is one.
+fn override_dir() -> Option<&'static PathBuf> {
+    // This needs to be called before the process is sandboxed, as we only give permission to
+    // read inside the directory.
+    OVERRIDE_DIR
+        .get_or_init(|| {
+            env::var_os(OVERRIDE_DIR_VARIABLE)
+                .map(PathBuf::from)
+                .or_else(|| Some(default_config_dir().join("resources")))
+                .filter(|dir| dir.is_dir())
+        })
+        .as_ref()
+}
 
-                if !path.pop() {
-                    panic!("Can't find resources directory")
-                }
-            }
-        }
+/// The contents of the resource at the given path, relative to the `resources` directory and
+/// with `/` separators.
+pub(crate) fn read_resource(path: &str) -> Option<Cow<'static, [u8]>> {
+    if let Some(dir) = override_dir()
+        && let Ok(contents) = fs::read(dir.join(path))
+    {
+        return Some(Cow::Owned(contents));
     }
+    EMBEDDED_RESOURCES
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, contents)| Cow::Borrowed(*contents))
 }
 
 impl resources::ResourceReaderMethods for ResourceReader {
     fn read(&self, file: Resource) -> Vec<u8> {
-        let mut path = resources_dir_path();
-        path.push(file.filename());
-        fs::read(path).expect("Can't read file")
+        let contents = read_resource(file.filename()).expect("Can't read file");
+        match file {
+            Resource::NetErrorHTML => net_error_page(&String::from_utf8_lossy(&contents)).into(),
+            Resource::BadCertHTML => bad_cert_page(&String::from_utf8_lossy(&contents)).into(),
+            _ => contents.into_owned(),
+        }
     }
     fn sandbox_access_files_dirs(&self) -> Vec<PathBuf> {
-        vec![resources_dir_path()]
+        override_dir().cloned().into_iter().collect()
     }
     fn sandbox_access_files(&self) -> Vec<PathBuf> {
         vec![]
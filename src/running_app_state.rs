@@ -4,23 +4,52 @@
 
 //! State and methods for desktop implementations.
 
-use std::cell::{Cell, RefCell};
-use std::collections::HashMap;
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::rc::Rc;
 
-use log::{error, info};
+use log::{error, info, warn};
 use servo::{
-    AllowOrDenyRequest, AuthenticationRequest, DeviceIntPoint, DeviceIntSize, EmbedderControl,
-    EmbedderControlId, EventLoopWaker, GamepadHapticEffectType, GenericSender, InputEventId,
-    InputEventResult, IpcSender, LoadStatus, MediaSessionEvent, PermissionRequest, Servo,
-    ServoDelegate, ServoError, WebView, WebViewDelegate, WebViewId, pref,
+    AllowOrDenyRequest, AuthenticationRequest, DeviceIntPoint, DeviceIntSize, EditingActionEvent,
+    EmbedderControl, EmbedderControlId, EventLoopWaker, GamepadHapticEffectType, GenericSender,
+    InputEventId, InputEventResult, IpcSender, JSValue, LoadStatus, MediaSessionEvent,
+    PermissionRequest, Servo, ServoDelegate, ServoError, WebRenderDebugOption, WebResourceLoad,
+    WebResourceResponse, WebView, WebViewDelegate, WebViewId, pref,
 };
 use url::Url;
 
 use crate::browser_window::{BrowserWindow, BrowserWindowId};
 use crate::data_storage::BrowserDataConnection;
-use crate::data_storage::history::HistoryEntry;
+use crate::data_storage::ca_certificates::CaCertificatePath;
+use crate::data_storage::certificate_exceptions::CertificateException;
+use crate::data_storage::content_blocking_exceptions::ContentBlockingException;
+use crate::data_storage::dark_mode_exceptions::DarkModeException;
+use crate::data_storage::https_exceptions::HttpsException;
+use crate::data_storage::redirect_rules::RedirectRule;
+use crate::data_storage::search_engines::{SearchEngine, built_in_search_engines};
+use crate::data_storage::user_scripts::UserScript;
+use crate::data_storage::user_stylesheets::UserStylesheet;
+use crate::keymap::Keymap;
+use crate::misc_utils::ca_certificates::SYSTEM_CERTIFICATES_SETTING;
+use crate::misc_utils::content_blocker::{CONTENT_BLOCKING_SETTING, ContentBlocker};
+use crate::misc_utils::dark_mode::{FORCED_DARK_MODE_SETTING, forced_dark_mode_script};
+use crate::misc_utils::https_only::{HTTPS_ONLY_SETTING, record_upgrade, upgrade_url};
+use crate::misc_utils::opensearch::{discover_search_engines, parse_opensearch_description};
+use crate::misc_utils::redirects::Redirector;
+use crate::misc_utils::save_page::{
+    SavePageMode, default_resource_file_name, default_saved_page_file_name, fetch_resource,
+    serialize_page,
+};
+use crate::misc_utils::screenshot::{
+    ScreenshotArea, ScreenshotTarget, capture_screenshot, default_screenshot_file_name,
+    save_screenshot_as_png, screenshot_to_egui_image,
+};
+use crate::misc_utils::user_scripts::{RunAt, UserScriptMetadata, UserScripts};
+use crate::misc_utils::user_stylesheets::{UserStylesheets, apply_user_stylesheets_script};
 use crate::prefs::ServoShellPreferences;
+use crate::prism_protocol::PRISM_SCHEME;
+use crate::url_cleaner::UrlCleaner;
 use crate::{GamepadSupport, NEW_TAB_PAGE_URL, data_storage};
 
 #[derive(Default)]
@@ -118,6 +147,15 @@ impl WebViewCollection {
     }
 }
 
+/// The name under which [`RunningAppState::search_suggestions_enabled`] is stored.
+const SEARCH_SUGGESTIONS_SETTING: &str = "search_suggestions";
+
+/// How many times the redirect rules may change the URL of a webview before a load completes.
+const MAX_RULE_REDIRECTS_PER_LOAD: usize = 5;
+
+/// How many of the subresources that a saved page is missing are listed to the user.
+const MAX_REPORTED_FAILED_RESOURCES: usize = 10;
+
 /// A command received via the user interacting with the user interface.
 pub enum UserInterfaceCommand {
     Go(String),
@@ -126,6 +164,53 @@ pub enum UserInterfaceCommand {
     Reload,
     NewWebView,
     CloseWebView(WebViewId),
+    Screenshot(ScreenshotArea, ScreenshotTarget),
+    SavePage(SavePageMode),
+    OpenInNewWebView(Url),
+    SearchInNewWebView(String),
+    CopyToClipboard(String),
+    /// Copy the URL without its tracking parameters, or the URL of the active page if `None`.
+    CopyCleanLink(Option<Url>),
+    AddBookmark {
+        title: String,
+        url: String,
+    },
+    /// Install the search engine from the OpenSearch description at the given URL.
+    InstallSearchEngine(Url),
+    /// Show the article in the active page in reader mode, or the original page if the reader
+    /// page is active.
+    ToggleReaderMode,
+    /// Install the user script with the given source, which was loaded from the given URL.
+    InstallUserScript {
+        source: String,
+        url: Url,
+    },
+    SaveResource(Url),
+    Inspect,
+    CloseActiveWebView,
+    ActivateWebView(WebViewId),
+    ActivateWebViewByIndex(usize),
+    ActivateLastWebView,
+    ActivateNextWebView,
+    ActivatePreviousWebView,
+    EditingAction(EditingActionEvent),
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    ExitFullscreen,
+    ToggleFullscreen,
+    OpenCommandPalette,
+    ShowHistory,
+    ShowBookmarks,
+    ShowDownloads,
+    ShowSettings,
+    /// Ask the user whether to clear the browsing history, and do so if they agree.
+    ConfirmClearHistory,
+    ClearHistory,
+    ToggleSamplingProfiler,
+    CaptureWebRender,
+    ToggleWebRenderDebugging(WebRenderDebugOption),
+    Quit,
 }
 
 pub(crate) struct RunningAppState {
@@ -148,6 +233,60 @@ pub(crate) struct RunningAppState {
     // See https://github.com/servo/servo/issues/36711.
     windows: RefCell<HashMap<BrowserWindowId, Rc<BrowserWindow>>>,
     browser_data_connection: BrowserDataConnection,
+
+    /// The port of the devtools server, once Servo reports that it has started.
+    devtools_port: Rc<Cell<Option<u16>>>,
+
+    /// The keyboard shortcuts, as loaded from the keymap file.
+    keymap: RefCell<Keymap>,
+
+    /// The rules for removing tracking parameters from URLs, as loaded from the rules file.
+    url_cleaner: RefCell<UrlCleaner>,
+
+    /// The enabled redirect rules from the profile.
+    redirector: RefCell<Redirector>,
+
+    /// How many times the redirect rules have changed the URL of each webview since its last
+    /// load completed, to stop sites that redirect back from looping forever.
+    rule_redirect_counts: RefCell<HashMap<WebViewId, usize>>,
+
+    /// The hosts that HTTPS-only mode has upgraded in each webview since its last load
+    /// completed, to stop sites that redirect back to HTTP from looping forever.
+    https_upgraded_hosts: RefCell<HashMap<WebViewId, HashSet<String>>>,
+
+    /// The enabled user scripts from the profile.
+    enabled_user_scripts: RefCell<UserScripts>,
+
+    /// The enabled user style sheets from the profile, with the one being edited in the
+    /// settings in place of its saved version.
+    enabled_user_stylesheets: RefCell<UserStylesheets>,
+
+    /// The user style sheet being edited in the settings, which is shown on pages before it is
+    /// saved.
+    user_stylesheet_preview: RefCell<Option<UserStylesheet>>,
+
+    /// The search engines that can be searched from the location bar, as stored in the profile.
+    search_engines: RefCell<Vec<SearchEngine>>,
+
+    /// Whether what is typed in the location bar is sent to the search engine for suggestions.
+    search_suggestions_enabled: Cell<bool>,
+
+    /// Whether sites are loaded over HTTPS even when they are linked to over HTTP.
+    https_only_enabled: Cell<bool>,
+
+    /// The filter lists in the profile, which block requests and hide elements on pages.
+    content_blocker: ContentBlocker,
+
+    content_blocking_enabled: Cell<bool>,
+
+    /// The hosts of the sites where content blocking has been turned off.
+    content_blocking_exceptions: RefCell<HashSet<String>>,
+
+    /// Whether light pages are darkened by inverting their colours.
+    forced_dark_mode_enabled: Cell<bool>,
+
+    /// The hosts of the sites that are never darkened.
+    dark_mode_exceptions: RefCell<HashSet<String>>,
 }
 
 impl RunningAppState {
@@ -156,7 +295,10 @@ impl RunningAppState {
         servoshell_preferences: ServoShellPreferences,
         _event_loop_waker: Box<dyn EventLoopWaker>,
     ) -> Self {
-        servo.set_delegate(Rc::new(ServoShellServoDelegate));
+        let devtools_port: Rc<Cell<Option<u16>>> = Default::default();
+        servo.set_delegate(Rc::new(ServoShellServoDelegate {
+            devtools_port: devtools_port.clone(),
+        }));
 
         let gamepad_support = if pref!(dom_gamepad_enabled) {
             GamepadSupport::maybe_new()
@@ -164,13 +306,67 @@ impl RunningAppState {
             None
         };
 
+        let browser_data_connection = data_storage::BrowserDataConnection::new();
+        let mut search_engines = browser_data_connection.load_search_engines();
+        if search_engines.is_empty() {
+            for search_engine in built_in_search_engines(&servoshell_preferences.searchpage) {
+                browser_data_connection.add_search_engine(&search_engine);
+            }
+            search_engines = browser_data_connection.load_search_engines();
+        }
+        // What is typed in the location bar is only sent to the search engine once the user
+        // has agreed to it.
+        let search_suggestions_enabled = browser_data_connection
+            .get_setting(SEARCH_SUGGESTIONS_SETTING)
+            .is_some_and(|value| value == "true");
+        let https_only_enabled = browser_data_connection
+            .get_setting(HTTPS_ONLY_SETTING)
+            .is_some_and(|value| value == "true");
+        let content_blocking_enabled = browser_data_connection
+            .get_setting(CONTENT_BLOCKING_SETTING)
+            .is_none_or(|value| value == "true");
+        let redirector = Redirector::new(&browser_data_connection.load_redirect_rules());
+        let enabled_user_scripts = UserScripts::new(&browser_data_connection.load_user_scripts());
+        let enabled_user_stylesheets =
+            UserStylesheets::new(&browser_data_connection.load_user_stylesheets());
+        let content_blocking_exceptions = browser_data_connection
+            .load_content_blocking_exceptions()
+            .into_iter()
+            .map(|exception| exception.host)
+            .collect();
+        let forced_dark_mode_enabled = browser_data_connection
+            .get_setting(FORCED_DARK_MODE_SETTING)
+            .is_some_and(|value| value == "true");
+        let dark_mode_exceptions = browser_data_connection
+            .load_dark_mode_exceptions()
+            .into_iter()
+            .map(|exception| exception.host)
+            .collect();
+
         Self {
             windows: Default::default(),
             gamepad_support: RefCell::new(gamepad_support),
             servoshell_preferences,
             servo,
             exit_scheduled: Default::default(),
-            browser_data_connection: data_storage::BrowserDataConnection::new(),
+            browser_data_connection,
+            devtools_port,
+            keymap: RefCell::new(Keymap::load()),
+            url_cleaner: RefCell::new(UrlCleaner::load()),
+            redirector: RefCell::new(redirector),
+            rule_redirect_counts: Default::default(),
+            https_upgraded_hosts: Default::default(),
+            enabled_user_scripts: RefCell::new(enabled_user_scripts),
+            enabled_user_stylesheets: RefCell::new(enabled_user_stylesheets),
+            user_stylesheet_preview: Default::default(),
+            search_engines: RefCell::new(search_engines),
+            search_suggestions_enabled: Cell::new(search_suggestions_enabled),
+            https_only_enabled: Cell::new(https_only_enabled),
+            content_blocker: ContentBlocker::load(),
+            content_blocking_enabled: Cell::new(content_blocking_enabled),
+            content_blocking_exceptions: RefCell::new(content_blocking_exceptions),
+            forced_dark_mode_enabled: Cell::new(forced_dark_mode_enabled),
+            dark_mode_exceptions: RefCell::new(dark_mode_exceptions),
         }
     }
 
@@ -217,6 +413,479 @@ impl RunningAppState {
         &self.servo
     }
 
+    pub(crate) fn keymap(&self) -> Ref<'_, Keymap> {
+        self.keymap.borrow()
+    }
+
+    /// Read the keymap file again, so that changes to it apply without a restart.
+    pub(crate) fn reload_keymap(&self) {
+        *self.keymap.borrow_mut() = Keymap::load();
+    }
+
+    pub(crate) fn url_cleaner(&self) -> Ref<'_, UrlCleaner> {
+        self.url_cleaner.borrow()
+    }
+
+    /// Read the URL cleaning rules file again, so that changes to it apply without a restart.
+    pub(crate) fn reload_url_cleaner(&self) {
+        *self.url_cleaner.borrow_mut() = UrlCleaner::load();
+    }
+
+    pub(crate) fn redirector(&self) -> Ref<'_, Redirector> {
+        self.redirector.borrow()
+    }
+
+    pub(crate) fn redirect_rules(&self) -> Vec<RedirectRule> {
+        self.browser_data_connection.load_redirect_rules()
+    }
+
+    pub(crate) fn add_redirect_rule(&self, rule: &RedirectRule) {
+        self.browser_data_connection.add_redirect_rule(rule);
+        self.reload_redirect_rules();
+    }
+
+    pub(crate) fn update_redirect_rule(&self, rule: &RedirectRule) {
+        self.browser_data_connection.update_redirect_rule(rule);
+        self.reload_redirect_rules();
+    }
+
+    pub(crate) fn remove_redirect_rule(&self, id: i32) {
+        self.browser_data_connection.remove_redirect_rule(id);
+        self.reload_redirect_rules();
+    }
+
+    fn reload_redirect_rules(&self) {
+        *self.redirector.borrow_mut() = Redirector::new(&self.redirect_rules());
+    }
+
+    /// The URL that the redirect rules load in place of the main frame, if any.
+    fn apply_redirect_rules(&self, webview: &WebView, load: &WebResourceLoad) -> Option<Url> {
+        let redirect = match self.redirector().redirect(&load.request().url) {
+            Ok(redirect) => redirect?,
+            Err(error) => {
+                warn!("Not redirecting {}: {error}", load.request().url);
+                return None;
+            }
+        };
+        // A site that redirects the new URL back to the old one would otherwise be redirected
+        // by the rules again forever, so the rules stop applying until a load completes.
+        let mut rule_redirect_counts = self.rule_redirect_counts.borrow_mut();
+        let count = rule_redirect_counts.entry(webview.id()).or_default();
+        if *count >= MAX_RULE_REDIRECTS_PER_LOAD {
+            warn!(
+                "Not redirecting {}: the site keeps redirecting back",
+                load.request().url
+            );
+            return None;
+        }
+        *count += 1;
+        Some(redirect.url)
+    }
+
+    pub(crate) fn user_scripts(&self) -> Vec<UserScript> {
+        self.browser_data_connection.load_user_scripts()
+    }
+
+    pub(crate) fn add_user_script(&self, user_script: &UserScript) {
+        self.browser_data_connection.add_user_script(user_script);
+        self.reload_user_scripts();
+    }
+
+    pub(crate) fn update_user_script(&self, user_script: &UserScript) {
+        self.browser_data_connection.update_user_script(user_script);
+        self.reload_user_scripts();
+    }
+
+    pub(crate) fn remove_user_script(&self, id: i32) {
+        self.browser_data_connection.remove_user_script(id);
+        self.reload_user_scripts();
+    }
+
+    fn reload_user_scripts(&self) {
+        *self.enabled_user_scripts.borrow_mut() = UserScripts::new(&self.user_scripts());
+    }
+
+    /// Install a user script that was loaded from a `.user.js` URL, replacing an older copy of
+    /// the same script if there is one.
+    pub(crate) fn install_user_script(&self, source: String, url: Url) -> Result<String, String> {
+        let metadata = UserScriptMetadata::parse(&source)?;
+        let existing = self.user_scripts().into_iter().find(|user_script| {
+            UserScriptMetadata::parse(&user_script.source)
+                .is_ok_and(|existing| existing.is_same_script(&metadata))
+        });
+        match existing {
+            Some(existing) => self.update_user_script(&UserScript {
+                source,
+                download_url: Some(url.into()),
+                ..existing
+            }),
+            None => self.add_user_script(&UserScript {
+                id: 0,
+                source,
+                enabled: true,
+                download_url: Some(url.into()),
+            }),
+        }
+        Ok(metadata.name)
+    }
+
+    /// Run the user scripts that match the page and run at one of the given times.
+    fn inject_user_scripts(&self, webview: &WebView, run_at: &[RunAt]) {
+        let Some(page_url) = webview.url() else {
+            return;
+        };
+        let script = self
+            .enabled_user_scripts
+            .borrow()
+            .injection_script(&page_url, run_at);
+        if let Some(script) = script {
+            webview.evaluate_javascript(script, |_| {});
+        }
+    }
+
+    /// Offer to install the user script shown in the [`WebView`], if it has loaded one.
+    fn offer_user_script_install(&self, webview: &WebView) {
+        let Some(url) = webview.url() else {
+            return;
+        };
+        if !url.path().ends_with(".user.js") {
+            return;
+        }
+        let window = self.window_for_webview_id(webview.id());
+        let webview_id = webview.id();
+        let script = "document.contentType === \"text/html\" ? null : \
+                      document.documentElement.textContent";
+        webview.evaluate_javascript(script, move |result| {
+            let Ok(JSValue::String(source)) = result else {
+                return;
+            };
+            let Ok(metadata) = UserScriptMetadata::parse(&source) else {
+                return;
+            };
+            let mut message = format!("{} will run on ", metadata.name);
+            if metadata.matches.is_empty() {
+                message.push_str("every page.");
+            } else {
+                message.push_str(&format!("pages matching {}.", metadata.matches.join(", ")));
+            }
+            if let Some(description) = &metadata.description {
+                message = format!("{description}\n\n{message}");
+            }
+            window.show_question_dialog(
+                webview_id,
+                "Install user script?".into(),
+                message,
+                "Install".into(),
+                UserInterfaceCommand::InstallUserScript { source, url },
+            );
+        });
+    }
+
+    pub(crate) fn user_stylesheets(&self) -> Vec<UserStylesheet> {
+        self.browser_data_connection.load_user_stylesheets()
+    }
+
+    pub(crate) fn add_user_stylesheet(&self, stylesheet: &UserStylesheet) {
+        self.browser_data_connection.add_user_stylesheet(stylesheet);
+        self.reload_user_stylesheets();
+    }
+
+    pub(crate) fn update_user_stylesheet(&self, stylesheet: &UserStylesheet) {
+        self.browser_data_connection
+            .update_user_stylesheet(stylesheet);
+        self.reload_user_stylesheets();
+    }
+
+    pub(crate) fn remove_user_stylesheet(&self, id: i32) {
+        self.browser_data_connection.remove_user_stylesheet(id);
+        self.reload_user_stylesheets();
+    }
+
+    /// Show a style sheet that is being edited on the open pages in place of its saved
+    /// version, or stop showing it if `None`.
+    pub(crate) fn preview_user_stylesheet(&self, stylesheet: Option<UserStylesheet>) {
+        if *self.user_stylesheet_preview.borrow() == stylesheet {
+            return;
+        }
+        *self.user_stylesheet_preview.borrow_mut() = stylesheet;
+        self.reload_user_stylesheets();
+    }
+
+    /// Load the user style sheets again and apply them to every open page, so that changes
+    /// are seen without reloading.
+    fn reload_user_stylesheets(&self) {
+        let mut stylesheets = self.user_stylesheets();
+        if let Some(preview) = self.user_stylesheet_preview.borrow().clone() {
+            match stylesheets
+                .iter_mut()
+                .find(|stylesheet| stylesheet.id == preview.id)
+            {
+                Some(stylesheet) => *stylesheet = preview,
+                None => stylesheets.push(preview),
+            }
+        }
+        *self.enabled_user_stylesheets.borrow_mut() = UserStylesheets::new(&stylesheets);
+        for webview in self.all_webviews() {
+            self.apply_user_stylesheets(&webview);
+        }
+    }
+
+    /// The webviews of every window.
+    fn all_webviews(&self) -> Vec<WebView> {
+        self.windows
+            .borrow()
+            .values()
+            .flat_map(|window| window.webviews().into_iter().map(|(_, webview)| webview))
+            .collect()
+    }
+
+    /// Replace the user style sheets of the page with the ones that match its URL. This is
+    /// done even when none match, as the page may still have ones that matched before.
+    fn apply_user_stylesheets(&self, webview: &WebView) {
+        let Some(page_url) = webview.url() else {
+            return;
+        };
+        let css = self.enabled_user_stylesheets.borrow().css_for(&page_url);
+        webview.evaluate_javascript(apply_user_stylesheets_script(&css), |_| {});
+    }
+
+    pub(crate) fn search_engines(&self) -> Ref<'_, Vec<SearchEngine>> {
+        self.search_engines.borrow()
+    }
+
+    pub(crate) fn add_search_engine(&self, search_engine: SearchEngine) {
+        self.browser_data_connection
+            .add_search_engine(&search_engine);
+        self.reload_search_engines();
+    }
+
+    pub(crate) fn remove_search_engine(&self, id: i32) {
+        self.browser_data_connection.remove_search_engine(id);
+        self.reload_search_engines();
+    }
+
+    pub(crate) fn set_default_search_engine(&self, id: i32) {
+        self.browser_data_connection.set_default_search_engine(id);
+        self.reload_search_engines();
+    }
+
+    pub(crate) fn set_search_engine_suggestions_enabled(&self, id: i32, enabled: bool) {
+        self.browser_data_connection
+            .set_search_engine_suggestions_enabled(id, enabled);
+        self.reload_search_engines();
+    }
+
+    pub(crate) fn search_suggestions_enabled(&self) -> bool {
+        self.search_suggestions_enabled.get()
+    }
+
+    pub(crate) fn set_search_suggestions_enabled(&self, enabled: bool) {
+        self.search_suggestions_enabled.set(enabled);
+        self.browser_data_connection
+            .set_setting(SEARCH_SUGGESTIONS_SETTING, &enabled.to_string());
+    }
+
+    pub(crate) fn https_only_enabled(&self) -> bool {
+        self.https_only_enabled.get()
+    }
+
+    pub(crate) fn set_https_only_enabled(&self, enabled: bool) {
+        self.https_only_enabled.set(enabled);
+        self.browser_data_connection
+            .set_setting(HTTPS_ONLY_SETTING, &enabled.to_string());
+    }
+
+    pub(crate) fn https_exceptions(&self) -> Vec<HttpsException> {
+        self.browser_data_connection.load_https_exceptions()
+    }
+
+    pub(crate) fn remove_https_exception(&self, id: i32) {
+        self.browser_data_connection.remove_https_exception(id);
+    }
+
+    /// Load the main frame over HTTPS instead of HTTP, if HTTPS-only mode is enabled. Loading
+    /// the new URL turns the request into a GET, so only GET requests are upgraded.
+    fn upgrade_to_https(&self, webview: WebView, load: WebResourceLoad) {
+        if !self.https_only_enabled.get() {
+            return;
+        }
+        let request = load.request();
+        if request.method != "GET" {
+            return;
+        }
+        let Some(upgraded_url) = upgrade_url(&request.url) else {
+            return;
+        };
+        let host = upgraded_url.host_str().unwrap_or_default().to_owned();
+        if self.browser_data_connection.has_https_exception(&host) {
+            return;
+        }
+
+        let is_redirect = request.is_redirect;
+        let url = request.url.clone();
+        load.intercept(WebResourceResponse::new(url)).cancel();
+        // A site that redirects from HTTPS back to HTTP would be upgraded again forever, so the
+        // load fails instead, and the error page offers to load the site over HTTP.
+        let mut https_upgraded_hosts = self.https_upgraded_hosts.borrow_mut();
+        let upgraded_hosts = https_upgraded_hosts.entry(webview.id()).or_default();
+        if is_redirect && upgraded_hosts.contains(&host) {
+            warn!("{host} redirected back to HTTP after being upgraded to HTTPS");
+            return;
+        }
+        upgraded_hosts.insert(host.clone());
+        drop(https_upgraded_hosts);
+        record_upgrade(&host);
+        webview.load(upgraded_url);
+    }
+
+    /// Block a subresource or frame load if the content blocker matches it.
+    fn block_content(&self, webview: WebView, load: WebResourceLoad) {
+        let Some(page_url) = webview.url() else {
+            return;
+        };
+        if !self.content_blocking_enabled_for(&page_url)
+            || !self
+                .content_blocker
+                .should_block(&load.request().url, Some(&page_url))
+        {
+            return;
+        }
+        let url = load.request().url.clone();
+        load.intercept(WebResourceResponse::new(url)).cancel();
+        self.window_for_webview_id(webview.id())
+            .count_blocked_request(webview.id());
+    }
+
+    /// Hide the elements that the element hiding filters match, with a style sheet that is
+    /// added to the page.
+    fn hide_blocked_elements(&self, webview: &WebView) {
+        let Some(page_url) = webview.url() else {
+            return;
+        };
+        if !self.content_blocking_enabled_for(&page_url) {
+            return;
+        }
+        let css = self.content_blocker.hiding_css(&page_url);
+        if css.is_empty() {
+            return;
+        }
+        let script = format!(
+            "(() => {{ const style = document.createElement('style'); style.textContent = {}; \
+             document.documentElement.appendChild(style); }})()",
+            serde_json::Value::from(css)
+        );
+        webview.evaluate_javascript(script, |_| {});
+    }
+
+    pub(crate) fn content_blocker(&self) -> &ContentBlocker {
+        &self.content_blocker
+    }
+
+    pub(crate) fn content_blocking_enabled(&self) -> bool {
+        self.content_blocking_enabled.get()
+    }
+
+    pub(crate) fn set_content_blocking_enabled(&self, enabled: bool) {
+        self.content_blocking_enabled.set(enabled);
+        self.browser_data_connection
+            .set_setting(CONTENT_BLOCKING_SETTING, &enabled.to_string());
+    }
+
+    /// Whether content is blocked on the page at the URL, which it isn't on internal pages and
+    /// on sites where it has been turned off.
+    pub(crate) fn content_blocking_enabled_for(&self, page_url: &Url) -> bool {
+        self.content_blocking_enabled.get()
+            && !self.content_blocker.is_empty()
+            && matches!(page_url.scheme(), "http" | "https")
+            && page_url
+                .host_str()
+                .is_some_and(|host| !self.content_blocking_exceptions.borrow().contains(host))
+    }
+
+    /// Turn content blocking on or off for a site, which is remembered as an exception.
+    pub(crate) fn set_content_blocking_enabled_for_host(&self, host: &str, enabled: bool) {
+        if enabled {
+            self.browser_data_connection
+                .remove_content_blocking_exception(host);
+            self.content_blocking_exceptions.borrow_mut().remove(host);
+        } else {
+            self.browser_data_connection
+                .add_content_blocking_exception(host);
+            self.content_blocking_exceptions
+                .borrow_mut()
+                .insert(host.to_owned());
+        }
+    }
+
+    pub(crate) fn content_blocking_exceptions(&self) -> Vec<ContentBlockingException> {
+        self.browser_data_connection
+            .load_content_blocking_exceptions()
+    }
+
+    pub(crate) fn forced_dark_mode_enabled(&self) -> bool {
+        self.forced_dark_mode_enabled.get()
+    }
+
+    pub(crate) fn set_forced_dark_mode_enabled(&self, enabled: bool) {
+        self.forced_dark_mode_enabled.set(enabled);
+        self.browser_data_connection
+            .set_setting(FORCED_DARK_MODE_SETTING, &enabled.to_string());
+        for webview in self.all_webviews() {
+            self.apply_forced_dark_mode(&webview);
+        }
+    }
+
+    /// Whether the page at the URL is darkened, which it isn't on internal pages and on sites
+    /// that are exceptions.
+    pub(crate) fn forced_dark_mode_enabled_for(&self, page_url: &Url) -> bool {
+        self.forced_dark_mode_enabled.get()
+            && matches!(page_url.scheme(), "http" | "https")
+            && page_url
+                .host_str()
+                .is_some_and(|host| !self.dark_mode_exceptions.borrow().contains(host))
+    }
+
+    /// Turn the forced dark mode on or off for a site, which is remembered as an exception,
+    /// and update the pages of that site that are open.
+    pub(crate) fn set_forced_dark_mode_enabled_for_host(&self, host: &str, enabled: bool) {
+        if enabled {
+            self.browser_data_connection
+                .remove_dark_mode_exception(host);
+            self.dark_mode_exceptions.borrow_mut().remove(host);
+        } else {
+            self.browser_data_connection.add_dark_mode_exception(host);
+            self.dark_mode_exceptions
+                .borrow_mut()
+                .insert(host.to_owned());
+        }
+        for webview in self.all_webviews() {
+            if webview
+                .url()
+                .is_some_and(|url| url.host_str() == Some(host))
+            {
+                self.apply_forced_dark_mode(&webview);
+            }
+        }
+    }
+
+    pub(crate) fn dark_mode_exceptions(&self) -> Vec<DarkModeException> {
+        self.browser_data_connection.load_dark_mode_exceptions()
+    }
+
+    /// Darken the page if the forced dark mode applies to it, or undo that if it no longer
+    /// does.
+    fn apply_forced_dark_mode(&self, webview: &WebView) {
+        let Some(page_url) = webview.url() else {
+            return;
+        };
+        let enabled = self.forced_dark_mode_enabled_for(&page_url);
+        webview.evaluate_javascript(forced_dark_mode_script(enabled), |_| {});
+    }
+
+    fn reload_search_engines(&self) {
+        *self.search_engines.borrow_mut() = self.browser_data_connection.load_search_engines();
+    }
+
     pub(crate) fn schedule_exit(&self) {
         self.exit_scheduled.set(true);
     }
@@ -314,8 +983,254 @@ impl RunningAppState {
         }
     }
 
-    pub fn get_browser_history(&self) -> Vec<HistoryEntry> {
-        self.browser_data_connection.get_browser_history()
+    /// Capture a screenshot of the given [`WebView`] and either ask the user where to save it
+    /// or put it on the clipboard.
+    pub(crate) fn take_screenshot(
+        self: &Rc<Self>,
+        webview: &WebView,
+        area: ScreenshotArea,
+        target: ScreenshotTarget,
+    ) {
+        let state = self.clone();
+        let webview_id = webview.id();
+        capture_screenshot(webview, area, move |result| {
+            let Some(window) = state.maybe_window_for_webview_id(webview_id) else {
+                return;
+            };
+            let image = match result {
+                Ok(image) => image,
+                Err(error) => {
+                    warn!("Failed to take screenshot: {error}");
+                    window.show_message_dialog(
+                        webview_id,
+                        "Could not take screenshot".into(),
+                        error.to_string(),
+                    );
+                    return;
+                }
+            };
+            match target {
+                ScreenshotTarget::Clipboard => {
+                    window.copy_image_to_clipboard(screenshot_to_egui_image(&image));
+                }
+                ScreenshotTarget::File => window.show_save_file_dialog(
+                    webview_id,
+                    &default_screenshot_file_name(),
+                    move |path| {
+                        let Err(error) = save_screenshot_as_png(&image, &path) else {
+                            return;
+                        };
+                        warn!("Failed to save screenshot to {path:?}: {error}");
+                        if let Some(window) = state.maybe_window_for_webview_id(webview_id) {
+                            window.show_message_dialog(
+                                webview_id,
+                                "Could not save screenshot".into(),
+                                format!("{} could not be written: {error}", path.display()),
+                            );
+                        }
+                    },
+                ),
+            }
+        });
+    }
+
+    /// Serialize the page in the given [`WebView`], ask the user where to save it and record
+    /// the saved file in the download history.
+    pub(crate) fn save_page(self: &Rc<Self>, webview: &WebView, mode: SavePageMode) {
+        let state = self.clone();
+        let webview_id = webview.id();
+        let title = webview.page_title();
+        let url = webview.url().map(|url| url.to_string()).unwrap_or_default();
+        serialize_page(webview, mode, move |result| {
+            let saved_page = match result {
+                Ok(saved_page) => saved_page,
+                Err(error) => {
+                    warn!("Failed to serialize page: {error}");
+                    return;
+                }
+            };
+            let Some(window) = state.maybe_window_for_webview_id(webview_id) else {
+                return;
+            };
+            let file_name = default_saved_page_file_name(title.clone());
+            let save_state = state.clone();
+            window.show_save_file_dialog(webview_id, &file_name, move |path| {
+                match saved_page.write_to(&path) {
+                    Ok(bytes_written) => {
+                        save_state.browser_data_connection.add_to_download_history(
+                            title.unwrap_or(file_name),
+                            url,
+                            &path,
+                            bytes_written.try_into().unwrap_or(u32::MAX),
+                        );
+                        save_state
+                            .report_failed_resources(webview_id, saved_page.failed_resources());
+                    }
+                    Err(error) => warn!("Failed to save page to {path:?}: {error}"),
+                }
+            });
+        });
+    }
+
+    /// Tell the user which subresources of a saved page couldn't be saved, usually because
+    /// their site doesn't let other sites read them.
+    fn report_failed_resources(&self, webview_id: WebViewId, failed_resources: &[String]) {
+        if failed_resources.is_empty() {
+            return;
+        }
+        let Some(window) = self.maybe_window_for_webview_id(webview_id) else {
+            return;
+        };
+        let mut message = format!(
+            "{} resources could not be saved, and the saved page still loads them from the \
+             original site:\n",
+            failed_resources.len()
+        );
+        for url in failed_resources.iter().take(MAX_REPORTED_FAILED_RESOURCES) {
+            message.push_str(&format!("\n{url}"));
+        }
+        if failed_resources.len() > MAX_REPORTED_FAILED_RESOURCES {
+            message.push_str("\n…");
+        }
+        window.show_message_dialog(webview_id, "Page saved incompletely".into(), message);
+    }
+
+    /// Download a single resource of the page in the given [`WebView`], ask the user where to
+    /// save it and record the saved file in the download history.
+    pub(crate) fn save_resource(self: &Rc<Self>, webview: &WebView, url: Url) {
+        let state = self.clone();
+        let webview_id = webview.id();
+        fetch_resource(webview, &url, move |result| {
+            let Some(window) = state.maybe_window_for_webview_id(webview_id) else {
+                return;
+            };
+            let contents = match result {
+                Ok(contents) => contents,
+                Err(error) => {
+                    warn!("Failed to download {url}: {error}");
+                    // The resource is fetched by the page, which can only read resources from
+                    // other sites if they allow it with CORS.
+                    window.show_message_dialog(
+                        webview_id,
+                        "Could not save file".into(),
+                        format!(
+                            "{url} could not be loaded. Files from other sites can only be \
+                             saved if the site allows it."
+                        ),
+                    );
+                    return;
+                }
+            };
+            let file_name = default_resource_file_name(&url);
+            let save_state = state.clone();
+            window.show_save_file_dialog(webview_id, &file_name, move |path| match std::fs::write(
+                &path, &contents,
+            ) {
+                Ok(()) => save_state.browser_data_connection.add_to_download_history(
+                    file_name,
+                    url.into(),
+                    &path,
+                    contents.len().try_into().unwrap_or(u32::MAX),
+                ),
+                Err(error) => warn!("Failed to save {url} to {path:?}: {error}"),
+            });
+        });
+    }
+
+    /// Fetch the OpenSearch description at the given URL from within the page that advertised
+    /// it and add the search engine it describes to the registry.
+    pub(crate) fn install_search_engine(self: &Rc<Self>, webview: &WebView, url: Url) {
+        let state = self.clone();
+        let webview_id = webview.id();
+        fetch_resource(webview, &url, move |result| {
+            let installed = result.and_then(|contents| {
+                let xml = String::from_utf8_lossy(&contents);
+                parse_opensearch_description(&xml, &url).map_err(|error| error.to_string())
+            });
+            let Some(window) = state.maybe_window_for_webview_id(webview_id) else {
+                return;
+            };
+            let description = match installed {
+                Ok(description) => description,
+                Err(error) => {
+                    warn!("Failed to install search engine from {url}: {error}");
+                    window.show_message_dialog(
+                        webview_id,
+                        "Could not add search engine".into(),
+                        error,
+                    );
+                    return;
+                }
+            };
+            let name = description.short_name.clone();
+            if !state
+                .search_engines()
+                .iter()
+                .any(|search_engine| search_engine.search_url == description.search_url)
+            {
+                state.add_search_engine(SearchEngine {
+                    id: 0,
+                    name: description.short_name,
+                    search_url: description.search_url,
+                    keyword: None,
+                    icon_url: description.icon_url,
+                    suggestions_url: description.suggestions_url,
+                    suggestions_enabled: true,
+                    is_default: false,
+                });
+            }
+            window.show_message_dialog(
+                webview_id,
+                "Search engine added".into(),
+                format!("{name} can now be chosen as the default search engine in Settings."),
+            );
+        });
+    }
+
+    pub(crate) fn clear_browser_history(&self) {
+        self.browser_data_connection.clear_browser_history();
+    }
+
+    pub(crate) fn certificate_exceptions(&self) -> Vec<CertificateException> {
+        self.browser_data_connection.load_certificate_exceptions()
+    }
+
+    pub(crate) fn remove_certificate_exception(&self, id: i32) {
+        self.browser_data_connection
+            .remove_certificate_exception(id);
+    }
+
+    pub(crate) fn ca_certificate_paths(&self) -> Vec<CaCertificatePath> {
+        self.browser_data_connection.load_ca_certificate_paths()
+    }
+
+    pub(crate) fn add_ca_certificate_path(&self, path: &Path) {
+        self.browser_data_connection.add_ca_certificate_path(path);
+    }
+
+    pub(crate) fn remove_ca_certificate_path(&self, id: i32) {
+        self.browser_data_connection.remove_ca_certificate_path(id);
+    }
+
+    /// Whether the certificates installed on the system are trusted. Unlike most settings, this
+    /// is read from the database every time, as changing it only takes effect after a restart.
+    pub(crate) fn system_ca_certificates_enabled(&self) -> bool {
+        self.browser_data_connection
+            .get_setting(SYSTEM_CERTIFICATES_SETTING)
+            .is_some_and(|value| value == "true")
+    }
+
+    pub(crate) fn set_system_ca_certificates_enabled(&self, enabled: bool) {
+        self.browser_data_connection
+            .set_setting(SYSTEM_CERTIFICATES_SETTING, &enabled.to_string());
+    }
+
+    pub(crate) fn add_bookmark(&self, title: String, url: String) {
+        self.browser_data_connection.add_bookmark(title, url);
+    }
+
+    pub(crate) fn devtools_port(&self) -> Option<u16> {
+        self.devtools_port.get()
     }
 }
 
@@ -335,8 +1250,18 @@ impl WebViewDelegate for RunningAppState {
         self.window_for_webview_id(webview.id()).set_needs_update();
     }
 
+    fn notify_url_changed(&self, webview: WebView, _url: Url) {
+        // Navigating within the same document, such as with `history.pushState`, doesn't load
+        // the page again, so the style sheets for the new URL are applied here.
+        self.apply_user_stylesheets(&webview);
+    }
+
     fn notify_page_title_changed(&self, webview: WebView, new_page_title_opt: Option<String>) {
-        let url_to_add_opt = webview.url().map(|url| url.to_string());
+        // Internal pages are reached from the menu, so they are left out of the history.
+        let url_to_add_opt = webview
+            .url()
+            .filter(|url| url.scheme() != PRISM_SCHEME)
+            .map(|url| url.to_string());
         if let (Some(url_to_add), Some(new_page_title)) = (url_to_add_opt, new_page_title_opt) {
             if url_to_add != NEW_TAB_PAGE_URL
                 && !url_to_add.is_empty()
@@ -380,7 +1305,7 @@ impl WebViewDelegate for RunningAppState {
         result: InputEventResult,
     ) {
         self.platform_window_for_webview_id(webview.id())
-            .notify_input_event_handled(&webview, id, result);
+            .notify_input_event_handled(&self.keymap(), &webview, id, result);
     }
 
     fn notify_cursor_changed(&self, webview: WebView, cursor: servo::Cursor) {
@@ -388,8 +1313,60 @@ impl WebViewDelegate for RunningAppState {
             .set_cursor(cursor);
     }
 
-    fn notify_load_status_changed(&self, webview: WebView, _status: LoadStatus) {
-        self.window_for_webview_id(webview.id()).set_needs_update();
+    fn notify_load_status_changed(&self, webview: WebView, status: LoadStatus) {
+        let window = self.window_for_webview_id(webview.id());
+        window.set_needs_update();
+        match status {
+            LoadStatus::Started => {
+                window.set_advertised_search_engines(webview.id(), vec![]);
+                window.set_reader_article(webview.id(), None);
+            }
+            LoadStatus::Complete => {
+                self.rule_redirect_counts.borrow_mut().remove(&webview.id());
+                self.https_upgraded_hosts.borrow_mut().remove(&webview.id());
+                self.apply_forced_dark_mode(&webview);
+                self.apply_user_stylesheets(&webview);
+                self.inject_user_scripts(&webview, &[RunAt::DocumentIdle]);
+                self.offer_user_script_install(&webview);
+                window.find_reader_article(&webview);
+                let webview_id = webview.id();
+                discover_search_engines(&webview, move |search_engines| {
+                    window.set_advertised_search_engines(webview_id, search_engines);
+                });
+            }
+            LoadStatus::HeadParsed => {
+                self.hide_blocked_elements(&webview);
+                self.apply_forced_dark_mode(&webview);
+                self.apply_user_stylesheets(&webview);
+                self.inject_user_scripts(&webview, &[RunAt::DocumentStart, RunAt::DocumentEnd]);
+            }
+        }
+    }
+
+    fn load_web_resource(&self, webview: WebView, load: WebResourceLoad) {
+        if load.request().is_for_main_frame {
+            self.window_for_webview_id(webview.id())
+                .reset_blocked_request_count(webview.id());
+            // Links and redirects to URLs with tracking parameters are loaded without them, and
+            // then the redirect rules are applied. Loading the new URL turns the request into a
+            // GET, so other requests such as form submissions are left alone.
+            let new_url = if load.request().method == "GET" {
+                self.url_cleaner()
+                    .clean(&load.request().url)
+                    .or_else(|| self.apply_redirect_rules(&webview, &load))
+            } else {
+                None
+            };
+            if let Some(new_url) = new_url {
+                let url = load.request().url.clone();
+                load.intercept(WebResourceResponse::new(url)).cancel();
+                webview.load(new_url);
+                return;
+            }
+            self.upgrade_to_https(webview, load);
+        } else {
+            self.block_content(webview, load);
+        }
     }
 
     fn notify_fullscreen_state_changed(&self, webview: WebView, fullscreen_state: bool) {
@@ -472,10 +1449,14 @@ impl WebViewDelegate for RunningAppState {
     }
 }
 
-struct ServoShellServoDelegate;
+struct ServoShellServoDelegate {
+    devtools_port: Rc<Cell<Option<u16>>>,
+}
+
 impl ServoDelegate for ServoShellServoDelegate {
     fn notify_devtools_server_started(&self, port: u16, _token: String) {
         info!("Devtools Server running on port {port}");
+        self.devtools_port.set(Some(port));
     }
 
     fn request_devtools_connection(&self, request: AllowOrDenyRequest) {
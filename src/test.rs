@@ -2,14 +2,64 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::parser::location_bar_input_to_url;
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use keyboard_types::{Code, Key, KeyState, KeyboardEvent, Location, Modifiers, NamedKey};
+use url::Url;
+
+use crate::data_storage::BrowserDataConnection;
+use crate::data_storage::bookmarks::BookmarkEntry;
+use crate::data_storage::history::VisitedPage;
+use crate::data_storage::pinned_sites::PinnedSite;
+use crate::data_storage::redirect_rules::{RedirectRule, RedirectRuleKind};
+use crate::data_storage::search_engines::{built_in_search_engines, default_search_engine};
+use crate::data_storage::user_scripts::UserScript;
+use crate::data_storage::user_stylesheets::UserStylesheet;
+use crate::error_pages::{error_page_token_allows, fill_in_net_error_page, new_error_page_token};
+use crate::keymap::{BrowserAction, Keymap, Shortcut};
+use crate::keyutils::CMD_OR_CONTROL;
+use crate::misc_utils::ca_certificates::{client_config, parse_pem_certificates, pem_bundle};
+use crate::misc_utils::certificates::{
+    CertificateDetails, certificate_details, certificate_fingerprint, fetch_server_certificate,
+};
+use crate::misc_utils::content_blocker::ContentBlocker;
+use crate::misc_utils::https_only::upgrade_url;
+use crate::misc_utils::opensearch::{
+    OpenSearchDescription, OpenSearchError, parse_opensearch_description,
+};
+use crate::misc_utils::reader_mode::{Article, original_url, reader_page_url};
+use crate::misc_utils::redirects::{RedirectError, Redirector};
+use crate::misc_utils::save_page::{default_resource_file_name, default_saved_page_file_name};
+use crate::misc_utils::search_suggestions::{
+    fetch_search_suggestions, parse_search_suggestions, proxy_for,
+};
+use crate::misc_utils::user_scripts::{RunAt, UserScriptMetadata, UserScripts};
+use crate::misc_utils::user_stylesheets::{
+    UserStylesheets, apply_user_stylesheets_script, parse_url_patterns,
+};
+use crate::parser::{location_bar_input_to_search, location_bar_input_to_url};
+use crate::resource_protocol::{Requester, is_allowed, parse_pinned_sites, sanitize_path};
+use crate::resources::read_resource;
+use crate::url_cleaner::UrlCleaner;
+use crate::user_interface::command_palette::fuzzy_score;
+use crate::user_interface::context_menu::{ContextMenuContext, EmbedderContextMenuItem};
+use crate::user_interface::search_bar::{SuggestionKind, inline_completion, rank_suggestions};
 
 // Helper function to test url
 fn test_url(input: &str, location: &str) {
     assert_eq!(
-        location_bar_input_to_url(input, "https://duckduckgo.com/html/?q=%s")
-            .unwrap()
-            .into_string(),
+        location_bar_input_to_url(
+            input,
+            &built_in_search_engines("https://duckduckgo.com/html/?q=%s")
+        )
+        .unwrap()
+        .into_string(),
         location
     );
 }
@@ -84,3 +134,1611 @@ fn test_issue_35754() {
     // user-local domain
     test_url_any_os("foo/bar", "https://foo/bar");
 }
+
+#[test]
+fn test_saved_page_file_names() {
+    let file_name = |title: &str| default_saved_page_file_name(Some(title.into()));
+    assert_eq!(file_name("Rust: A Language"), "Rust_ A Language.html");
+    assert_eq!(file_name("Tabs\tand\nnew lines"), "Tabsandnew lines.html");
+    assert_eq!(file_name("  ...  "), "page.html");
+    assert_eq!(default_saved_page_file_name(None), "page.html");
+    // Names that Windows reserves for devices can't be used for files, even with an extension.
+    assert_eq!(file_name("CON"), "_CON.html");
+    assert_eq!(file_name("lpt1.txt"), "_lpt1.txt.html");
+    assert_eq!(file_name("Console"), "Console.html");
+
+    let resource_name = |url: &str| default_resource_file_name(&Url::parse(url).unwrap());
+    assert_eq!(
+        resource_name("https://example.com/images/cat%20photo.jpg"),
+        "cat photo.jpg"
+    );
+    assert_eq!(resource_name("https://example.com/nul.png"), "_nul.png");
+    assert_eq!(resource_name("https://example.com/a%0Ab%3A.png"), "ab_.png");
+    assert_eq!(resource_name("https://example.com/"), "download");
+}
+
+#[test]
+fn test_search_engine_keywords() {
+    test_url_any_os(
+        "w rust",
+        "https://en.wikipedia.org/wiki/Special:Search?search=rust",
+    );
+    test_url_any_os(
+        "W  rust lang",
+        "https://en.wikipedia.org/wiki/Special:Search?search=rust%20lang",
+    );
+    test_url_any_os("d rust", "https://duckduckgo.com/html/?q=rust");
+
+    // Not a keyword
+    test_url_any_os("x rust", "https://duckduckgo.com/html/?q=x%20rust");
+
+    // A keyword on its own is a search for the keyword
+    test_url_any_os("w", "https://duckduckgo.com/html/?q=w");
+}
+
+fn parse_opensearch_fixture(xml: &str) -> Result<OpenSearchDescription, OpenSearchError> {
+    let description_url = Url::parse("https://example.com/opensearch.xml").unwrap();
+    parse_opensearch_description(xml, &description_url)
+}
+
+#[test]
+fn test_opensearch_description() {
+    let description =
+        parse_opensearch_fixture(include_str!("../tests/fixtures/opensearch/wikipedia.xml"))
+            .unwrap();
+    assert_eq!(description.short_name, "Wikipedia (en)");
+    assert_eq!(description.description.as_deref(), Some("Wikipedia (en)"));
+    assert_eq!(
+        description.search_url,
+        "https://en.wikipedia.org/w/index.php?title=Special:Search&search=%s"
+    );
+    assert_eq!(
+        description.suggestions_url.as_deref(),
+        Some("https://en.wikipedia.org/w/api.php?action=opensearch&search=%s&namespace=0")
+    );
+    assert_eq!(
+        description.icon_url.as_deref(),
+        Some("https://en.wikipedia.org/static/favicon/wikipedia.ico")
+    );
+}
+
+#[test]
+fn test_opensearch_description_relative_urls() {
+    let description =
+        parse_opensearch_fixture(include_str!("../tests/fixtures/opensearch/relative.xml"))
+            .unwrap();
+    assert_eq!(description.short_name, "Example & Co");
+    // POST templates are skipped, known parameters are filled in and unknown optional ones
+    // are left empty.
+    assert_eq!(
+        description.search_url,
+        "https://example.com/search?q=%s&page=1&lang=*&ie=UTF-8&ref="
+    );
+    assert_eq!(description.suggestions_url, None);
+    assert_eq!(
+        description.icon_url.as_deref(),
+        Some("https://example.com/icons/small.png")
+    );
+}
+
+#[test]
+fn test_opensearch_description_errors() {
+    assert_eq!(
+        parse_opensearch_fixture(include_str!(
+            "../tests/fixtures/opensearch/missing-search-url.xml"
+        )),
+        Err(OpenSearchError::MissingSearchUrl)
+    );
+    assert_eq!(
+        parse_opensearch_fixture(include_str!(
+            "../tests/fixtures/opensearch/not-opensearch.xml"
+        )),
+        Err(OpenSearchError::NotOpenSearch)
+    );
+    assert!(matches!(
+        parse_opensearch_fixture("<OpenSearchDescription><ShortName>Broken</Short"),
+        Err(OpenSearchError::Xml(_))
+    ));
+    assert_eq!(
+        parse_opensearch_fixture(
+            "<OpenSearchDescription><ShortName>Required</ShortName>\
+             <Url type=\"text/html\" template=\"https://example.com/?q={searchTerms}&amp;x={custom}\"/>\
+             </OpenSearchDescription>"
+        ),
+        Err(OpenSearchError::InvalidTemplate(
+            "https://example.com/?q={searchTerms}&x={custom}".into()
+        ))
+    );
+}
+
+#[test]
+fn test_location_bar_input_to_search() {
+    let search_engines = built_in_search_engines("https://duckduckgo.com/html/?q=%s");
+    let search = |input| {
+        location_bar_input_to_search(input, &search_engines)
+            .map(|(search_engine, terms)| (search_engine.name.as_str(), terms))
+    };
+    assert_eq!(search("rust lang"), Some(("DuckDuckGo", "rust lang")));
+    assert_eq!(search(" w  rust "), Some(("Wikipedia", "rust")));
+    assert_eq!(search("servo.org"), None);
+    assert_eq!(search("https://servo.org/"), None);
+    assert_eq!(search("  "), None);
+}
+
+fn bookmark(title: &str, url: &str) -> BookmarkEntry {
+    BookmarkEntry {
+        id: 0,
+        title: title.into(),
+        url: url.into(),
+        time_modified: Default::default(),
+    }
+}
+
+fn visited_page(title: &str, url: &str, visit_count: u32) -> VisitedPage {
+    VisitedPage {
+        title: title.into(),
+        url: url.into(),
+        visit_count,
+        last_visited: Default::default(),
+    }
+}
+
+#[test]
+fn test_location_bar_suggestion_ranking() {
+    let suggestions = rank_suggestions(
+        "rust",
+        &[],
+        vec![
+            bookmark("The Rust Book", "https://doc.rust-lang.org/book/"),
+            bookmark("Notes on rust", "https://example.org/notes"),
+        ],
+        vec![
+            visited_page("Rust", "https://www.rust-lang.org/", 3),
+            visited_page("Learning rust", "https://example.com/rust", 50),
+            visited_page("The Rust Book", "https://doc.rust-lang.org/book/", 10),
+            visited_page("Rusty notes", "https://example.net/notes", 0),
+        ],
+    );
+    let ranked: Vec<(&str, SuggestionKind)> = suggestions
+        .iter()
+        .map(|suggestion| (suggestion.url.as_str(), suggestion.kind.clone()))
+        .collect();
+    assert_eq!(
+        ranked,
+        [
+            // The start of the domain matches, which beats everything else.
+            ("https://www.rust-lang.org/", SuggestionKind::History),
+            ("https://example.com/rust", SuggestionKind::History),
+            // Also in the history, which adds to it, but it stays a bookmark.
+            ("https://doc.rust-lang.org/book/", SuggestionKind::Bookmark),
+            // Bookmarks rank above history that matches as well.
+            ("https://example.org/notes", SuggestionKind::Bookmark),
+            ("https://example.net/notes", SuggestionKind::History),
+        ]
+    );
+
+    let history = (0..20)
+        .map(|index| visited_page("Rust", &format!("https://example.com/{index}"), 1))
+        .collect();
+    assert_eq!(rank_suggestions("rust", &[], vec![], history).len(), 8);
+}
+
+#[test]
+fn test_location_bar_inline_completion() {
+    let suggestions = rank_suggestions(
+        "r",
+        &[],
+        vec![],
+        vec![
+            visited_page("Rust", "https://www.rust-lang.org/", 3),
+            visited_page("Rust docs", "https://docs.rs/", 1),
+        ],
+    );
+    assert_eq!(
+        inline_completion("Ru", &suggestions).as_deref(),
+        Some("Rust-lang.org")
+    );
+    assert_eq!(
+        inline_completion("docs", &suggestions).as_deref(),
+        Some("docs.rs")
+    );
+    assert_eq!(inline_completion("rust-lang.org", &suggestions), None);
+    assert_eq!(inline_completion("www.rust", &suggestions), None);
+    assert_eq!(inline_completion("rust lang", &suggestions), None);
+    assert_eq!(inline_completion("rust-lang.org/", &suggestions), None);
+    assert_eq!(inline_completion("", &suggestions), None);
+}
+
+#[test]
+fn test_command_palette_fuzzy_score() {
+    // A prefix beats the same characters scattered through the candidate.
+    assert!(fuzzy_score("new", "New tab") > fuzzy_score("new", "Narrow view window"));
+    // Characters at the start of words score higher than those inside them.
+    assert!(fuzzy_score("sb", "Show bookmarks") > fuzzy_score("sb", "Subtitles"));
+    // When the query matches equally well, the shorter candidate wins.
+    assert!(fuzzy_score("reload", "Reload") > fuzzy_score("reload", "Reload all tabs"));
+    // Case and whitespace in the query don't matter.
+    assert_eq!(
+        fuzzy_score("NEW T", "New tab"),
+        fuzzy_score("newt", "new tab")
+    );
+
+    assert_eq!(fuzzy_score("xyz", "New tab"), None);
+    // The characters have to appear in order.
+    assert_eq!(fuzzy_score("tn", "New tab"), None);
+    assert_eq!(fuzzy_score("new tabs", "New tab"), None);
+    assert!(fuzzy_score("", "New tab").is_some());
+}
+
+#[test]
+fn test_context_menu_items() {
+    use EmbedderContextMenuItem::*;
+    let link = Url::parse("https://example.com/article?utm_source=feed").unwrap();
+    let image = Url::parse("https://example.com/photo.jpg").unwrap();
+    let context = |link_url: Option<&Url>, image_url: Option<&Url>, selection: Option<&str>| {
+        ContextMenuContext {
+            link_url: link_url.cloned(),
+            image_url: image_url.cloned(),
+            selection: Rc::new(RefCell::new(selection.map(str::to_owned))),
+        }
+    };
+
+    // Only the items that always apply, without a separator above them.
+    assert_eq!(context(None, None, None).items(), vec![Inspect]);
+    // A selection of only whitespace is not searched for.
+    assert_eq!(context(None, None, Some(" \n")).items(), vec![Inspect]);
+
+    assert_eq!(
+        context(Some(&link), None, None).items(),
+        vec![
+            OpenLinkInNewTab(link.clone()),
+            CopyLinkAddress(link.clone()),
+            CopyCleanLink(link.clone()),
+            BookmarkLink(link.clone()),
+            Separator,
+            Inspect,
+        ]
+    );
+    assert_eq!(
+        context(None, Some(&image), None).items(),
+        vec![SaveImageAs(image.clone()), Separator, Inspect]
+    );
+    assert_eq!(
+        context(None, None, Some("  servo  ")).items(),
+        vec![SearchForSelection("servo".into()), Separator, Inspect]
+    );
+
+    // A linked image with text selected has every group, one separator apart.
+    let items = context(Some(&link), Some(&image), Some("servo")).items();
+    assert_eq!(
+        items,
+        vec![
+            OpenLinkInNewTab(link.clone()),
+            CopyLinkAddress(link.clone()),
+            CopyCleanLink(link.clone()),
+            BookmarkLink(link.clone()),
+            Separator,
+            SaveImageAs(image.clone()),
+            Separator,
+            SearchForSelection("servo".into()),
+            Separator,
+            Inspect,
+        ]
+    );
+    assert_eq!(items[3].label(), "Bookmark this link");
+    assert_eq!(
+        SearchForSelection("a selection that is too long to show".into()).label(),
+        "Search the web for “a selection that is too…”"
+    );
+}
+
+#[test]
+fn test_search_suggestions_url() {
+    let search_engines = built_in_search_engines("https://duckduckgo.com/html/?q=%s");
+    let mut search_engine = default_search_engine(&search_engines).unwrap().clone();
+    assert_eq!(
+        search_engine
+            .suggestions_url_for("rust & c++")
+            .map(String::from),
+        Some("https://duckduckgo.com/ac/?q=rust%20%26%20c%2B%2B&type=list".into())
+    );
+    search_engine.suggestions_enabled = false;
+    assert_eq!(search_engine.suggestions_url_for("rust"), None);
+
+    // The search results page gets the same encoding.
+    assert_eq!(
+        search_engine
+            .search_url_for("rust & c++")
+            .map(servo::ServoUrl::into_string),
+        Some("https://duckduckgo.com/html/?q=rust%20%26%20c%2B%2B".into())
+    );
+    assert_eq!(search_engine.search_url_for(""), None);
+}
+
+#[test]
+fn test_parse_search_suggestions() {
+    assert_eq!(
+        parse_search_suggestions(br#"["rust", ["rust lang", "rustup", 3], ["ignored"]]"#),
+        Some(vec!["rust lang".to_owned(), "rustup".to_owned()])
+    );
+    assert_eq!(parse_search_suggestions(br#"{"suggestions": []}"#), None);
+    assert_eq!(parse_search_suggestions(b"not json"), None);
+}
+
+/// Serve a single request with the given response, returning the URL of the server and the
+/// request line it received.
+fn serve_once(response: &'static [u8]) -> (Url, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+        }
+        stream.write_all(response).unwrap();
+        String::from_utf8_lossy(&request)
+            .lines()
+            .next()
+            .unwrap()
+            .to_owned()
+    });
+    let url = Url::parse(&format!("http://127.0.0.1:{port}/ac?q=rust%20lang")).unwrap();
+    (url, server)
+}
+
+#[test]
+fn test_fetch_search_suggestions() {
+    let (url, server) = serve_once(
+        b"HTTP/1.0 200 OK\r\nContent-Type: application/x-suggestions+json\r\n\r\n\
+          [\"rust lang\", [\"rust language\", \"rust lang book\"]]",
+    );
+    assert_eq!(
+        fetch_search_suggestions(&url, Duration::from_secs(5)),
+        Ok(vec![
+            "rust language".to_owned(),
+            "rust lang book".to_owned()
+        ])
+    );
+    assert_eq!(server.join().unwrap(), "GET /ac?q=rust%20lang HTTP/1.0");
+
+    let (url, server) = serve_once(b"HTTP/1.0 404 Not Found\r\n\r\n");
+    assert!(fetch_search_suggestions(&url, Duration::from_secs(5)).is_err());
+    server.join().unwrap();
+
+    // The connection closes before the whole body has been sent.
+    let (url, server) =
+        serve_once(b"HTTP/1.0 200 OK\r\nContent-Length: 64\r\n\r\n[\"rust\", [\"rust lang\"]]");
+    assert!(fetch_search_suggestions(&url, Duration::from_secs(5)).is_err());
+    server.join().unwrap();
+}
+
+#[test]
+fn test_fetch_search_suggestions_content_length() {
+    // The response ends where its length says, even though the connection is kept open.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buffer = [0; 1024];
+        let _ = stream.read(&mut buffer).unwrap();
+        stream
+            .write_all(b"HTTP/1.0 200 OK\r\nContent-Length: 23\r\n\r\n[\"rust\", [\"rust lang\"]]")
+            .unwrap();
+        thread::sleep(Duration::from_secs(2));
+    });
+
+    let url = Url::parse(&format!("http://127.0.0.1:{port}/ac?q=rust")).unwrap();
+    let start = Instant::now();
+    assert_eq!(
+        fetch_search_suggestions(&url, Duration::from_secs(5)),
+        Ok(vec!["rust lang".to_owned()])
+    );
+    assert!(start.elapsed() < Duration::from_secs(1));
+    server.join().unwrap();
+}
+
+#[test]
+fn test_fetch_search_suggestions_redirect() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let responses: [&[u8]; 2] = [
+            b"HTTP/1.0 302 Found\r\nLocation: /v2/ac?q=rust\r\n\r\n",
+            b"HTTP/1.0 200 OK\r\n\r\n[\"rust\", [\"rustup\"]]",
+        ];
+        responses.map(|response| {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+            stream.write_all(response).unwrap();
+            String::from_utf8_lossy(&request).into_owned()
+        })
+    });
+
+    let url = Url::parse(&format!("http://127.0.0.1:{port}/ac?q=rust")).unwrap();
+    assert_eq!(
+        fetch_search_suggestions(&url, Duration::from_secs(5)),
+        Ok(vec!["rustup".to_owned()])
+    );
+    let [first, second] = server.join().unwrap();
+    // The port isn't the default one, so it is part of the Host header.
+    assert!(first.starts_with("GET /ac?q=rust HTTP/1.0\r\n"));
+    assert!(first.contains(&format!("\r\nHost: 127.0.0.1:{port}\r\n")));
+    assert!(second.starts_with("GET /v2/ac?q=rust HTTP/1.0\r\n"));
+}
+
+#[test]
+fn test_search_suggestions_proxy() {
+    let url = Url::parse("https://duckduckgo.com/ac/?q=rust").unwrap();
+    let proxy = "http://proxy.example.com:3128";
+    assert_eq!(
+        proxy_for(&url, proxy, "").map(String::from).as_deref(),
+        Some("http://proxy.example.com:3128/")
+    );
+    assert_eq!(proxy_for(&url, "", ""), None);
+    assert_eq!(proxy_for(&url, "socks5://proxy.example.com", ""), None);
+    assert_eq!(proxy_for(&url, proxy, "localhost, .duckduckgo.com"), None);
+    assert_eq!(proxy_for(&url, proxy, "DuckDuckGo.com"), None);
+    assert_eq!(proxy_for(&url, proxy, "*"), None);
+    assert!(proxy_for(&url, proxy, "goduckduckgo.com").is_some());
+}
+
+#[test]
+fn test_fetch_search_suggestions_timeout() {
+    // Accept the connection but never respond.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let connection = listener.accept().unwrap();
+        thread::sleep(Duration::from_secs(2));
+        drop(connection);
+    });
+
+    let url = Url::parse(&format!("http://127.0.0.1:{port}/ac?q=rust")).unwrap();
+    let start = Instant::now();
+    assert!(fetch_search_suggestions(&url, Duration::from_millis(200)).is_err());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    server.join().unwrap();
+}
+
+#[test]
+fn test_parse_pinned_sites() {
+    let pinned_sites = parse_pinned_sites(
+        r#"[
+            {"title": "Servo", "url": "https://servo.org"},
+            {"title": " ", "url": "https://example.com/page"},
+            {"title": "Script", "url": "javascript:alert(1)"},
+            {"title": "Not a URL", "url": "servo"}
+        ]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        pinned_sites,
+        vec![
+            PinnedSite {
+                title: "Servo".into(),
+                url: "https://servo.org/".into(),
+            },
+            PinnedSite {
+                title: "example.com".into(),
+                url: "https://example.com/page".into(),
+            },
+        ]
+    );
+    assert_eq!(parse_pinned_sites(r#"{"title": "Servo"}"#), None);
+}
+
+#[test]
+fn test_resource_path_traversal() {
+    assert_eq!(sanitize_path("/newtab.html"), Some("newtab.html".into()));
+    assert_eq!(sanitize_path("/newtab%2Ecss"), Some("newtab.css".into()));
+
+    for path in [
+        "/../browser_data.db",
+        "/%2e%2e/browser_data.db",
+        "/%2E%2e%2fbrowser_data.db",
+        "/icons/../../browser_data.db",
+        "/./newtab.html",
+        "//etc/passwd",
+        "/%2fetc/passwd",
+        "/C:/Windows/win.ini",
+        "/..%5cbrowser_data.db",
+        "/newtab.html%00.css",
+        "newtab.html",
+        "/",
+    ] {
+        assert_eq!(sanitize_path(path), None, "{path}");
+    }
+}
+
+#[test]
+fn test_resource_origin_policy() {
+    let internal = Url::parse("resource:///newtab.html").unwrap();
+    let prism = Url::parse("prism:history").unwrap();
+    let web = Url::parse("https://example.com/").unwrap();
+
+    assert_eq!(Requester::classify(true, None, None), Requester::Navigation);
+    assert_eq!(
+        Requester::classify(false, None, Some(&internal)),
+        Requester::Internal
+    );
+    assert_eq!(
+        Requester::classify(false, None, Some(&prism)),
+        Requester::Internal
+    );
+    assert_eq!(
+        Requester::classify(false, Some("https"), Some(&web)),
+        Requester::Web
+    );
+    assert_eq!(
+        Requester::classify(true, Some("https"), Some(&web)),
+        Requester::Web
+    );
+
+    // Web content that doesn't send a referrer is still told apart by its origin.
+    assert_eq!(
+        Requester::classify(false, Some("https"), None),
+        Requester::Web
+    );
+    assert_eq!(
+        Requester::classify(true, Some("https"), None),
+        Requester::Web
+    );
+
+    // A sandboxed frame that sends no referrer has an opaque origin like the browser's own
+    // pages, which isn't enough to count as one of them.
+    assert_eq!(Requester::classify(false, None, None), Requester::Web);
+
+    // Pages can only be navigated to, so web content can't embed them.
+    assert!(is_allowed("newtab.html", Requester::Navigation));
+    assert!(!is_allowed("newtab.html", Requester::Internal));
+    assert!(!is_allowed("newtab.html", Requester::Web));
+
+    assert!(is_allowed("newtab.css", Requester::Internal));
+    assert!(!is_allowed("newtab.css", Requester::Web));
+
+    assert!(is_allowed(
+        "servo-color-positive-no-container.svg",
+        Requester::Web
+    ));
+
+    // Files that aren't listed are never served.
+    assert!(!is_allowed("unlisted.html", Requester::Navigation));
+    assert!(!is_allowed("unlisted.css", Requester::Internal));
+}
+
+#[test]
+fn test_embedded_resources() {
+    let new_tab_page = read_resource("resource_protocol/newtab.html").unwrap();
+    assert!(String::from_utf8_lossy(&new_tab_page).contains("</head>"));
+    assert!(read_resource("prism_protocol/prism.js").is_some());
+    assert!(read_resource("neterror.html").is_some());
+    assert!(read_resource("resource_protocol/missing.html").is_none());
+}
+
+#[test]
+fn test_net_error_page() {
+    let template =
+        "<textarea>${reason}</textarea><script>const SEARCH_URL = ${search_url};</script>";
+    assert_eq!(
+        fill_in_net_error_page(template, Some("https://example.com/?q=%s")),
+        "<textarea>${reason}</textarea><script>const SEARCH_URL = \"https://example.com/?q=%s\";</script>"
+    );
+    assert_eq!(
+        fill_in_net_error_page(template, None),
+        "<textarea>${reason}</textarea><script>const SEARCH_URL = null;</script>"
+    );
+    assert_eq!(
+        fill_in_net_error_page(template, Some("https://example.com/</script>?q=%s")),
+        "<textarea>${reason}</textarea><script>const SEARCH_URL = \"https://example.com/\\u003c/script>?q=%s\";</script>"
+    );
+}
+
+#[test]
+fn test_error_page_token() {
+    let token = new_error_page_token();
+    assert!(error_page_token_allows(&token, "https://expired.test"));
+    assert!(error_page_token_allows(&token, "https://expired.test"));
+    assert!(!error_page_token_allows(&token, "https://bank.test"));
+    // The same host on another port is another site.
+    assert!(!error_page_token_allows(
+        &token,
+        "https://expired.test:8443"
+    ));
+
+    let other_token = new_error_page_token();
+    assert_ne!(token, other_token);
+    assert!(error_page_token_allows(&other_token, "https://bank.test"));
+    assert!(!error_page_token_allows("not a token", "https://bank.test"));
+}
+
+#[test]
+fn test_certificate_details() {
+    let der = include_bytes!("../tests/fixtures/certificates/self-signed.der");
+    assert_eq!(
+        certificate_details(der).unwrap(),
+        CertificateDetails {
+            subject: "staging.internal (Prism Staging)".into(),
+            issuer: "staging.internal (Prism Staging)".into(),
+            not_before: "2025-01-01T00:00:00+00:00".into(),
+            not_after: "2035-01-01T00:00:00+00:00".into(),
+            fingerprint: "D5:FF:57:F6:F3:22:8A:ED:21:F8:87:20:42:91:42:CC:\
+                          1C:90:DA:5C:F1:90:F3:B0:6F:9A:B7:03:EE:44:D2:52"
+                .into(),
+        }
+    );
+    assert!(certificate_details(b"not a certificate").is_err());
+}
+
+/// Serve a single TLS connection for `localhost` with a certificate signed by a newly generated
+/// certificate authority, returning the port, the authority's certificate as PEM and the DER
+/// encoded certificate of the server.
+fn serve_tls_once() -> (u16, String, Vec<u8>, thread::JoinHandle<()>) {
+    let ca_key = rcgen::KeyPair::generate().unwrap();
+    let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let ca = rcgen::CertifiedIssuer::self_signed(ca_params, ca_key).unwrap();
+
+    let key = rcgen::KeyPair::generate().unwrap();
+    let certificate = rcgen::CertificateParams::new(vec!["localhost".to_owned()])
+        .unwrap()
+        .signed_by(&key, &ca)
+        .unwrap();
+    let server_config = Arc::new(
+        rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![certificate.der().clone()],
+                rustls::pki_types::PrivateKeyDer::try_from(key.serialize_der()).unwrap(),
+            )
+            .unwrap(),
+    );
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let connection = rustls::ServerConnection::new(server_config).unwrap();
+        let mut stream = rustls::StreamOwned::new(connection, stream);
+        // The handshake fails when the client doesn't trust the certificate.
+        let _ = stream.write_all(b"trusted");
+        let _ = stream.flush();
+    });
+    (port, ca.pem(), certificate.der().to_vec(), server)
+}
+
+/// Connect to the server with the given TLS configuration and read what it sends.
+fn read_tls(port: u16, config: Arc<rustls::ClientConfig>) -> Result<String, std::io::Error> {
+    let server_name = rustls::pki_types::ServerName::try_from("localhost").unwrap();
+    let connection = rustls::ClientConnection::new(config, server_name).unwrap();
+    let stream = std::net::TcpStream::connect(("127.0.0.1", port))?;
+    let mut stream = rustls::StreamOwned::new(connection, stream);
+    let mut received = String::new();
+    match stream.read_to_string(&mut received) {
+        // The server closes the connection without a close_notify.
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => Ok(received),
+        result => result.map(|_| received),
+    }
+}
+
+#[test]
+fn test_custom_ca_certificate() {
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+    // The generated authority isn't one of the built-in ones.
+    let (port, _, _, server) = serve_tls_once();
+    assert!(read_tls(port, client_config(&[])).is_err());
+    server.join().unwrap();
+
+    let (port, ca_pem, _, server) = serve_tls_once();
+    let certificates = parse_pem_certificates(ca_pem.as_bytes()).unwrap();
+    assert_eq!(
+        read_tls(port, client_config(&certificates)).unwrap(),
+        "trusted"
+    );
+    server.join().unwrap();
+
+    // Bundles written for Servo can be read back.
+    assert_eq!(
+        parse_pem_certificates(pem_bundle(&certificates).as_bytes()).unwrap(),
+        certificates
+    );
+    assert!(parse_pem_certificates(b"").is_err());
+    assert!(parse_pem_certificates(b"-----BEGIN CERTIFICATE-----\nnot base64!\n").is_err());
+}
+
+#[test]
+fn test_fetch_server_certificate() {
+    // The certificate is read even though it isn't trusted.
+    let (port, _, der, server) = serve_tls_once();
+    let fetched = fetch_server_certificate("localhost", port).unwrap();
+    assert_eq!(
+        certificate_fingerprint(&fetched),
+        certificate_fingerprint(&der)
+    );
+    server.join().unwrap();
+
+    // Nothing is listening any more.
+    assert!(fetch_server_certificate("localhost", port).is_err());
+}
+
+#[test]
+fn test_https_only_upgrade() {
+    let upgrade = |url: &str| upgrade_url(&Url::parse(url).unwrap()).map(String::from);
+    assert_eq!(
+        upgrade("http://example.com/path?q=1#top").as_deref(),
+        Some("https://example.com/path?q=1#top")
+    );
+    // The default port is normalized away when parsing, so it doesn't count as explicit.
+    assert_eq!(
+        upgrade("http://example.com:80/").as_deref(),
+        Some("https://example.com/")
+    );
+    assert_eq!(upgrade("https://example.com/"), None);
+    assert_eq!(upgrade("http://example.com:8080/"), None);
+    assert_eq!(upgrade("http://localhost/"), None);
+    assert_eq!(upgrade("http://app.localhost/"), None);
+    assert_eq!(upgrade("http://127.0.0.1/"), None);
+    assert_eq!(upgrade("http://[::1]/"), None);
+    assert_eq!(
+        upgrade("http://192.168.1.1/").as_deref(),
+        Some("https://192.168.1.1/")
+    );
+    assert_eq!(upgrade("resource:///newtab.html"), None);
+}
+
+fn sample_content_blocker() -> ContentBlocker {
+    let mut blocker = ContentBlocker::default();
+    blocker.add_filter_list(
+        "sample",
+        include_str!("../tests/fixtures/filter_lists/sample.txt"),
+    );
+    blocker
+}
+
+#[test]
+fn test_https_exception_between_connections() {
+    // The network error page makes the exception through the connection of the prism: handler,
+    // and the browser reads it through its own when the site is loaded over HTTP.
+    let path = std::env::temp_dir().join(format!(
+        "prism-test-https-exceptions-{}.db",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let browser = BrowserDataConnection::open(&path);
+    let prism = BrowserDataConnection::open(&path);
+    assert!(!browser.has_https_exception("legacy.example.com"));
+    prism.add_https_exception("legacy.example.com");
+    assert!(browser.has_https_exception("legacy.example.com"));
+    assert!(!browser.has_https_exception("example.com"));
+    drop((browser, prism));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_content_blocker_network_filters() {
+    let blocker = sample_content_blocker();
+    let page = Url::parse("https://www.site.org/article").unwrap();
+    let blocks =
+        |url: &str, page: &Url| blocker.should_block(&Url::parse(url).unwrap(), Some(page));
+
+    // Domain anchors match the domain and its subdomains, but not other domains that end the
+    // same way.
+    assert!(blocks("https://ads.example.com/script.js", &page));
+    assert!(blocks("https://eu.ads.example.com/", &page));
+    assert!(!blocks("https://bads.example.com/script.js", &page));
+    assert!(!blocks("https://ads.example.community/", &page));
+
+    // Wildcards and end anchors.
+    assert!(blocks("http://tracker.net/pixel-1x1.gif", &page));
+    assert!(!blocks("http://tracker.net/pixel-1x1.gif?cache=1", &page));
+    assert!(blocks(
+        "https://cdn.site.org/img/banner/top/ad_1.png",
+        &page
+    ));
+    assert!(!blocks("https://cdn.site.org/img/banner/ad_1.png", &page));
+    assert!(blocks("https://start.example.org/popunder.js", &page));
+    assert!(!blocks("https://www.start.example.org/popunder.js", &page));
+    assert!(!blocks("https://ADS.example.com.evil/", &page));
+    assert!(blocks("https://ADS.EXAMPLE.COM/", &page));
+
+    // $third-party and $~third-party.
+    assert!(blocks("https://cdn.widgets.com/embed.js", &page));
+    let widgets = Url::parse("https://www.widgets.com/").unwrap();
+    assert!(!blocks("https://cdn.widgets.com/embed.js", &widgets));
+    let shop = Url::parse("https://shop.com/cart").unwrap();
+    assert!(blocks("https://metrics.shop.com/collect", &shop));
+    assert!(!blocks("https://metrics.shop.com/collect", &page));
+
+    // $domain= with an excluded subdomain.
+    let news = Url::parse("https://www.news.org/").unwrap();
+    let world_news = Url::parse("https://world.news.com/").unwrap();
+    let sports_news = Url::parse("https://sports.news.com/").unwrap();
+    assert!(!blocks("https://social.net/button/like", &news));
+    assert!(blocks("https://social.net/button/like", &world_news));
+    assert!(!blocks("https://social.net/button/like", &sports_news));
+
+    // A `$` that isn't followed by options is part of the pattern.
+    assert!(blocks("https://stats.net/cgi-bin/count$visit.php", &page));
+    assert!(!blocks("https://stats.net/cgi-bin/count", &page));
+
+    // Filters with resource types are skipped, as are regular expressions.
+    assert!(!blocks("https://video.net/player.js", &page));
+    assert!(!blocks("https://site.org/ad1.js", &page));
+
+    // Exceptions.
+    assert!(!blocks("https://ads.example.com/allowed/logo.png", &page));
+    let trusted = Url::parse("https://www.trusted.org/").unwrap();
+    assert!(!blocks("https://ads.example.com/script.js", &trusted));
+
+    // Only web requests are blocked.
+    assert!(!blocks("data:text/plain,ads.example.com", &page));
+
+    let lists = blocker.filter_lists();
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0].name, "sample");
+    assert_eq!(lists[0].network_filters, 10);
+    assert_eq!(lists[0].cosmetic_filters, 4);
+}
+
+#[test]
+fn test_content_blocker_element_hiding() {
+    let blocker = sample_content_blocker();
+    let css = |url: &str| blocker.hiding_css(&Url::parse(url).unwrap());
+    assert_eq!(
+        css("https://www.site.org/"),
+        ".ad-banner { display: none !important; }\n\
+         div[id^=\"sponsored-\"] { display: none !important; }\n"
+    );
+    assert_eq!(
+        css("https://shop.com/"),
+        "div[id^=\"sponsored-\"] { display: none !important; }\n"
+    );
+    assert_eq!(
+        css("https://world.news.com/"),
+        ".ad-banner { display: none !important; }\n\
+         div[id^=\"sponsored-\"] { display: none !important; }\n\
+         .promo { display: none !important; }\n"
+    );
+    assert!(!css("https://sports.news.com/").contains(".promo"));
+    assert!(!css("https://example.com/").contains("abp"));
+}
+
+#[test]
+fn test_url_cleaning() {
+    let mut url_cleaner = UrlCleaner::default();
+    url_cleaner.apply(
+        "
+        # Site specific rules and exemptions.
+        strip sid on shop.example
+        keep gclid on ads.example.com
+        exempt analytics.example.org
+        ",
+    );
+    assert!(url_cleaner.errors.is_empty());
+    let clean = |url: &str| {
+        url_cleaner
+            .clean(&Url::parse(url).unwrap())
+            .map(String::from)
+    };
+
+    assert_eq!(
+        clean("https://example.com/post?id=7&utm_source=news&utm_medium=email&fbclid=abc#top")
+            .as_deref(),
+        Some("https://example.com/post?id=7#top")
+    );
+    assert_eq!(
+        clean("https://example.com/?utm_source=a&gclid=b").as_deref(),
+        Some("https://example.com/")
+    );
+    // The parameters that are kept aren't encoded again.
+    assert_eq!(
+        clean("https://example.com/search?q=a%20b+c&utm_campaign=x&empty&utm%5Fterm=y").as_deref(),
+        Some("https://example.com/search?q=a%20b+c&empty")
+    );
+    assert_eq!(clean("https://example.com/?id=7"), None);
+    assert_eq!(clean("https://example.com/"), None);
+    assert_eq!(clean("file:///tmp/page.html?utm_source=a"), None);
+
+    // Per domain rules apply to subdomains too.
+    assert_eq!(
+        clean("https://www.shop.example/item?sid=1&color=red").as_deref(),
+        Some("https://www.shop.example/item?color=red")
+    );
+    assert_eq!(clean("https://example.com/?sid=1"), None);
+    assert_eq!(
+        clean("https://www.amazon.com/dp/B00?ref_=nav&pd_rd_w=x&th=1").as_deref(),
+        Some("https://www.amazon.com/dp/B00?th=1")
+    );
+    assert_eq!(
+        clean("https://ads.example.com/?gclid=1&utm_source=a").as_deref(),
+        Some("https://ads.example.com/?gclid=1")
+    );
+    assert_eq!(
+        clean("https://www.analytics.example.org/?utm_source=a"),
+        None
+    );
+    assert_eq!(
+        clean("https://notanalytics.example.org/?utm_source=a").as_deref(),
+        Some("https://notanalytics.example.org/")
+    );
+
+    url_cleaner.apply("strip\nremove fbclid\nstrip utm_*_id\nkeep *");
+    assert_eq!(url_cleaner.errors.len(), 4);
+    // Parameter names aren't only ASCII.
+    url_cleaner.apply("strip réf\nstrip suivi_é*\nstrip é*é");
+    assert_eq!(url_cleaner.errors.len(), 5);
+}
+
+fn redirect_rule(
+    id: i32,
+    kind: RedirectRuleKind,
+    pattern: &str,
+    replacement: &str,
+) -> RedirectRule {
+    RedirectRule {
+        id,
+        kind,
+        pattern: pattern.into(),
+        replacement: replacement.into(),
+        enabled: true,
+    }
+}
+
+#[test]
+fn test_keymap_comments() {
+    let mut keymap = Keymap::default();
+    keymap.apply_user_bindings(
+        "# Bindings for the number row\n\
+         ctrl+shift+# = new-tab # The key above 3\n\
+         \t# An indented comment\n\
+         alt+# = reload\n",
+    );
+    assert!(keymap.errors.is_empty(), "{:?}", keymap.errors);
+    assert!(
+        keymap
+            .shortcuts_for(BrowserAction::NewTab)
+            .contains(&"Ctrl+Shift+#".to_owned())
+    );
+    assert!(
+        keymap
+            .shortcuts_for(BrowserAction::Reload)
+            .contains(&"Alt+#".to_owned())
+    );
+}
+
+fn key_event(modifiers: Modifiers, key: Key) -> KeyboardEvent {
+    KeyboardEvent {
+        state: KeyState::Down,
+        key,
+        code: Code::Unidentified,
+        location: Location::Standard,
+        modifiers,
+        repeat: false,
+        is_composing: false,
+    }
+}
+
+#[test]
+fn test_keymap_shortcut_parsing() {
+    let shortcut = |text: &str| text.parse::<Shortcut>();
+    assert_eq!(
+        shortcut("ctrl+shift+t"),
+        Ok(Shortcut {
+            modifiers: Modifiers::CONTROL | Modifiers::SHIFT,
+            key: Key::Character("T".into()),
+        })
+    );
+    assert_eq!(
+        shortcut(" Control+Option+F5 "),
+        Ok(Shortcut {
+            modifiers: Modifiers::CONTROL | Modifiers::ALT,
+            key: Key::Named(NamedKey::F5),
+        })
+    );
+    assert_eq!(
+        shortcut("cmdorctrl++").map(|shortcut| (shortcut.modifiers, shortcut.key)),
+        Ok((CMD_OR_CONTROL, Key::Character("+".into())))
+    );
+    assert_eq!(
+        shortcut("cmdorctrl+plus"),
+        shortcut("cmdorctrl++"),
+        "plus is another name for +"
+    );
+    assert_eq!(
+        shortcut("alt+space").map(|shortcut| shortcut.key),
+        Ok(Key::Character(" ".into()))
+    );
+    assert_eq!(
+        shortcut("Escape"),
+        Ok(Shortcut {
+            modifiers: Modifiers::empty(),
+            key: Key::Named(NamedKey::Escape),
+        })
+    );
+    assert_eq!(
+        shortcut("ctrl+shift+t").unwrap().to_string(),
+        "Ctrl+Shift+T"
+    );
+
+    assert!(shortcut("hyper+t").is_err(), "unknown modifier");
+    assert!(shortcut("ctrl+").is_err(), "missing key");
+    assert!(shortcut("ctrl+NotAKey").is_err(), "unknown key");
+}
+
+#[test]
+fn test_keymap_user_bindings() {
+    let mut keymap = Keymap::default();
+    assert!(
+        !keymap
+            .shortcuts_for(BrowserAction::Reload)
+            .contains(&"F5".to_owned())
+    );
+    keymap.apply_user_bindings(
+        "F5 = reload\n\
+         F11 = show-history\n\
+         Escape = none\n\
+         ctrl+q = not-an-action\n\
+         this is not a binding\n",
+    );
+    assert_eq!(keymap.errors.len(), 2, "{:?}", keymap.errors);
+
+    // A new binding is added next to the defaults for the same action.
+    let reload = keymap.shortcuts_for(BrowserAction::Reload);
+    assert!(reload.contains(&"F5".to_owned()), "{reload:?}");
+    assert!(reload.len() > 1, "{reload:?}");
+
+    // Binding a shortcut replaces its default binding.
+    assert!(
+        keymap
+            .shortcuts_for(BrowserAction::ToggleFullscreen)
+            .is_empty()
+    );
+    assert_eq!(
+        keymap.action_for_event(
+            &key_event(Modifiers::empty(), Key::Named(NamedKey::F11)),
+            false
+        ),
+        Some(BrowserAction::ShowHistory)
+    );
+
+    // Binding a shortcut to `none` removes its default binding.
+    assert!(
+        keymap
+            .shortcuts_for(BrowserAction::ExitFullscreen)
+            .is_empty()
+    );
+    assert_eq!(
+        keymap.action_for_event(
+            &key_event(Modifiers::empty(), Key::Named(NamedKey::Escape)),
+            false
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_keymap_action_for_event() {
+    let keymap = Keymap::default();
+    let escape = key_event(Modifiers::empty(), Key::Named(NamedKey::Escape));
+    assert_eq!(
+        keymap.action_for_event(&escape, false),
+        Some(BrowserAction::ExitFullscreen)
+    );
+    assert_eq!(keymap.action_for_event(&escape, true), None);
+
+    // Overridable bindings are only found once the page has declined the event.
+    let reset_zoom = key_event(CMD_OR_CONTROL, Key::Character("0".into()));
+    assert_eq!(keymap.action_for_event(&reset_zoom, false), None);
+    assert_eq!(
+        keymap.action_for_event(&reset_zoom, true),
+        Some(BrowserAction::ResetZoom)
+    );
+
+    // Escape only exits fullscreen while fullscreen, and otherwise goes to the next binding for it.
+    let mut keymap = Keymap::default();
+    keymap.apply_user_bindings("Escape = exit-fullscreen\nEscape = close-tab\n");
+    let not_fullscreen = |action: BrowserAction| action != BrowserAction::ExitFullscreen;
+    assert_eq!(
+        keymap.enabled_action_for_event(&escape, false, not_fullscreen),
+        Some(BrowserAction::CloseTab)
+    );
+    assert_eq!(
+        Keymap::default().enabled_action_for_event(&escape, false, not_fullscreen),
+        None
+    );
+
+    // The modifiers have to match exactly.
+    let shifted_escape = key_event(Modifiers::SHIFT, Key::Named(NamedKey::Escape));
+    assert_eq!(keymap.action_for_event(&shifted_escape, false), None);
+    assert_eq!(
+        keymap.action_for_event(
+            &key_event(Modifiers::empty(), Key::Named(NamedKey::F1)),
+            false
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_keymap_conflicts() {
+    let mut keymap = Keymap::default();
+    assert!(keymap.conflicts().is_empty(), "{:?}", keymap.conflicts());
+
+    keymap.apply_user_bindings(
+        "F6 = reload\n\
+         F6 = new-tab\n\
+         alt+d = show-downloads\n",
+    );
+    let conflicts = keymap.conflicts();
+    assert_eq!(conflicts.len(), 2, "{conflicts:?}");
+
+    let f6 = conflicts
+        .iter()
+        .find(|conflict| conflict.shortcut.key == Key::Named(NamedKey::F6))
+        .unwrap();
+    assert_eq!(
+        f6.uses,
+        [
+            BrowserAction::Reload.description(),
+            BrowserAction::NewTab.description()
+        ]
+    );
+    // The first binding is the one that runs.
+    assert_eq!(
+        keymap.action_for_event(
+            &key_event(Modifiers::empty(), Key::Named(NamedKey::F6)),
+            false
+        ),
+        Some(BrowserAction::Reload)
+    );
+
+    // Shortcuts handled by the user interface itself conflict with any binding.
+    let alt_d = conflicts
+        .iter()
+        .find(|conflict| conflict.shortcut == "alt+d".parse().unwrap())
+        .unwrap();
+    assert_eq!(alt_d.uses.len(), 2);
+    assert!(alt_d.uses[0].ends_with("(built in)"), "{:?}", alt_d.uses);
+}
+
+#[test]
+fn test_redirect_rules() {
+    let mut rules = vec![
+        redirect_rule(1, RedirectRuleKind::Host, "reddit.com", "old.reddit.com"),
+        redirect_rule(
+            2,
+            RedirectRuleKind::Wildcard,
+            "*://go/*",
+            "https://intranet.example.com/links/$2",
+        ),
+        redirect_rule(
+            3,
+            RedirectRuleKind::Regex,
+            r"^https?://(?:www\.)?youtube\.com/shorts/(?<video>[\w-]+)",
+            "https://www.youtube.com/watch?v=${video}",
+        ),
+        redirect_rule(
+            4,
+            RedirectRuleKind::Wildcard,
+            "https://intranet.example.com/links/wiki",
+            "https://wiki.example.com/",
+        ),
+    ];
+    let redirect = |redirector: &Redirector, url: &str| {
+        redirector
+            .redirect(&Url::parse(url).unwrap())
+            .map(|redirect| redirect.map(|redirect| (redirect.url.to_string(), redirect.rule_ids)))
+    };
+    let redirector = Redirector::new(&rules);
+
+    assert_eq!(
+        redirect(&redirector, "https://www.reddit.com/r/rust/?sort=new"),
+        Ok(Some((
+            "https://old.reddit.com/r/rust/?sort=new".into(),
+            vec![1]
+        )))
+    );
+    assert_eq!(
+        redirect(&redirector, "https://old.reddit.com/r/rust/"),
+        Ok(None)
+    );
+    assert_eq!(redirect(&redirector, "https://notreddit.com/"), Ok(None));
+    assert_eq!(
+        redirect(&redirector, "https://go/standup"),
+        Ok(Some((
+            "https://intranet.example.com/links/standup".into(),
+            vec![2]
+        )))
+    );
+    assert_eq!(
+        redirect(
+            &redirector,
+            "https://youtube.com/shorts/a1-b2?feature=share"
+        ),
+        Ok(Some((
+            "https://www.youtube.com/watch?v=a1-b2".into(),
+            vec![3]
+        )))
+    );
+    // Rules are applied to the URLs that other rules redirect to.
+    assert_eq!(
+        redirect(&redirector, "http://go/wiki"),
+        Ok(Some(("https://wiki.example.com/".into(), vec![2, 4])))
+    );
+    assert_eq!(redirect(&redirector, "prism:settings"), Ok(None));
+
+    rules[0].enabled = false;
+    assert_eq!(
+        redirect(&Redirector::new(&rules), "https://reddit.com/"),
+        Ok(None)
+    );
+
+    // Rules that redirect to each other are stopped, while a rule that redirects to the same
+    // URL does nothing.
+    rules.push(redirect_rule(
+        5,
+        RedirectRuleKind::Host,
+        "wiki.example.com",
+        "example.com",
+    ));
+    rules.push(redirect_rule(
+        6,
+        RedirectRuleKind::Host,
+        "example.com",
+        "wiki.example.com",
+    ));
+    let redirector = Redirector::new(&rules);
+    assert_eq!(
+        redirect(&redirector, "http://go/wiki"),
+        Err(RedirectError::Loop)
+    );
+    let redirector = Redirector::new(&[redirect_rule(
+        1,
+        RedirectRuleKind::Regex,
+        "^(https://example.com/.*)$",
+        "$1",
+    )]);
+    assert_eq!(redirect(&redirector, "https://example.com/"), Ok(None));
+    let redirector = Redirector::new(&[redirect_rule(
+        1,
+        RedirectRuleKind::Regex,
+        "^https://example.com/(.*)$",
+        "https://example.com/$1/a",
+    )]);
+    assert_eq!(
+        redirect(&redirector, "https://example.com/"),
+        Err(RedirectError::Loop)
+    );
+    let redirector = Redirector::new(&[redirect_rule(
+        1,
+        RedirectRuleKind::Wildcard,
+        "https://example.com/*",
+        "$1",
+    )]);
+    assert_eq!(
+        redirect(&redirector, "https://example.com/page"),
+        Err(RedirectError::InvalidUrl(1, "page".into()))
+    );
+
+    let invalid = |kind, pattern, replacement| {
+        Redirector::validate(&redirect_rule(0, kind, pattern, replacement)).is_err()
+    };
+    assert!(invalid(
+        RedirectRuleKind::Host,
+        "https://reddit.com",
+        "old.reddit.com"
+    ));
+    assert!(invalid(
+        RedirectRuleKind::Host,
+        "reddit.com",
+        "https://old.reddit.com/"
+    ));
+    assert!(invalid(
+        RedirectRuleKind::Wildcard,
+        "*://go/*",
+        "https://example.com/$3"
+    ));
+    assert!(invalid(
+        RedirectRuleKind::Regex,
+        "(unclosed",
+        "https://example.com/"
+    ));
+    assert!(invalid(
+        RedirectRuleKind::Regex,
+        "go/(.*)",
+        "https://example.com/${2}"
+    ));
+    assert!(!invalid(
+        RedirectRuleKind::Regex,
+        "go/(.*)",
+        "https://example.com/$1?cost=$$5"
+    ));
+}
+
+#[test]
+fn test_user_script_metadata() {
+    let metadata = UserScriptMetadata::parse(include_str!(
+        "../tests/fixtures/user_scripts/wide-articles.user.js"
+    ))
+    .unwrap();
+    assert_eq!(metadata.name, "Wide articles");
+    assert_eq!(
+        metadata.namespace.as_deref(),
+        Some("https://example.com/scripts")
+    );
+    assert_eq!(metadata.version.as_deref(), Some("1.2"));
+    assert_eq!(metadata.matches.len(), 3);
+    assert_eq!(metadata.excludes.len(), 2);
+    assert_eq!(metadata.run_at, RunAt::DocumentEnd);
+
+    let applies_to = |url: &str| metadata.applies_to(&Url::parse(url).unwrap());
+    assert!(applies_to("https://example.com/articles/1"));
+    assert!(applies_to(
+        "https://blog.example.com/articles/1?page=2#comments"
+    ));
+    assert!(!applies_to("http://blog.example.com/articles/1"));
+    assert!(!applies_to("https://example.com/about"));
+    assert!(!applies_to("https://notexample.com/articles/1"));
+    assert!(applies_to("http://news.example.org/"));
+    assert!(applies_to("https://news.example.org:8443/today"));
+    assert!(applies_to("http://legacy.example.net/index.html"));
+    assert!(!applies_to("https://www.example.com/articles/print/1"));
+    assert!(!applies_to("https://news.example.org/login/"));
+
+    let everywhere = UserScriptMetadata::parse(include_str!(
+        "../tests/fixtures/user_scripts/everywhere.user.js"
+    ))
+    .unwrap();
+    assert_eq!(everywhere.run_at, RunAt::DocumentIdle);
+    assert!(everywhere.applies_to(&Url::parse("https://example.com/").unwrap()));
+    assert!(!everywhere.is_same_script(&metadata));
+
+    // Scripts never run on internal pages, not even those without patterns or with a regular
+    // expression matching anything.
+    let anything = UserScriptMetadata::parse(
+        "// ==UserScript==\n// @name Anything\n// @include /.*/\n// ==/UserScript==\n",
+    )
+    .unwrap();
+    for url in [
+        "prism:history",
+        "resource:///newtab.html",
+        "servo:preferences",
+        "about:blank",
+        "data:text/html,hello",
+    ] {
+        let url = Url::parse(url).unwrap();
+        assert!(!everywhere.applies_to(&url));
+        assert!(!anything.applies_to(&url));
+    }
+    assert!(everywhere.applies_to(&Url::parse("file:///home/user/page.html").unwrap()));
+    assert!(anything.applies_to(&Url::parse("http://example.com/").unwrap()));
+
+    let invalid = |metadata: &str| {
+        UserScriptMetadata::parse(&format!(
+            "// ==UserScript==\n{metadata}\n// ==/UserScript==\n"
+        ))
+        .is_err()
+    };
+    assert!(invalid("// @match https://example.com/*"));
+    assert!(invalid("// @name No path\n// @match https://example.com"));
+    assert!(invalid(
+        "// @name Bad scheme\n// @match ftp://example.com/*"
+    ));
+    assert!(invalid(
+        "// @name Bad host\n// @match https://ex*ample.com/*"
+    ));
+    assert!(invalid("// @name Bad regex\n// @include /(unclosed/"));
+    assert!(invalid("// @name Bad run-at\n// @run-at document-later"));
+    assert!(!invalid("// @name All\n// @match <all_urls>"));
+    assert!(UserScriptMetadata::parse("console.log(1);").is_err());
+    assert!(UserScriptMetadata::parse("// ==UserScript==\n// @name Unclosed\n").is_err());
+}
+
+#[test]
+fn test_user_script_injection() {
+    let user_script = |id, source: &str, enabled| UserScript {
+        id,
+        source: source.into(),
+        enabled,
+        download_url: None,
+    };
+    let user_scripts = UserScripts::new(&[
+        user_script(
+            1,
+            include_str!("../tests/fixtures/user_scripts/wide-articles.user.js"),
+            true,
+        ),
+        user_script(
+            2,
+            include_str!("../tests/fixtures/user_scripts/everywhere.user.js"),
+            true,
+        ),
+        user_script(
+            3,
+            "// ==UserScript==\n// @name Disabled\n// ==/UserScript==\nalert(1);",
+            false,
+        ),
+        user_script(4, "alert(2);", true),
+    ]);
+    let article = Url::parse("https://example.com/articles/1").unwrap();
+    let other = Url::parse("https://example.com/").unwrap();
+
+    let early = user_scripts
+        .injection_script(&article, &[RunAt::DocumentStart, RunAt::DocumentEnd])
+        .unwrap();
+    assert!(early.contains("article.style.maxWidth"));
+    assert!(early.contains("DOMContentLoaded"));
+    assert!(!early.contains("Hello from"));
+    assert_eq!(
+        user_scripts.injection_script(&other, &[RunAt::DocumentStart, RunAt::DocumentEnd]),
+        None
+    );
+
+    let idle = user_scripts
+        .injection_script(&other, &[RunAt::DocumentIdle])
+        .unwrap();
+    assert!(idle.contains("Hello from"));
+    assert!(idle.contains("\"Everywhere\""));
+    assert!(!idle.contains("alert"));
+}
+
+#[test]
+fn test_user_stylesheets() {
+    let stylesheet = |name: &str, url_patterns: &str, css: &str, enabled| UserStylesheet {
+        id: 0,
+        name: name.into(),
+        url_patterns: url_patterns.into(),
+        css: css.into(),
+        enabled,
+    };
+    let user_stylesheets = UserStylesheets::new(&[
+        stylesheet("Fonts", "", "body { font-family: serif; }", true),
+        stylesheet(
+            "Tools",
+            "https://*.tools.example.com/*\n\n  http://wiki.example.com/edit/*  ",
+            ".header { background: teal; }",
+            true,
+        ),
+        stylesheet("Disabled", "", "body { color: red; }", false),
+        stylesheet(
+            "Invalid",
+            "tools.example.com",
+            "body { color: blue; }",
+            true,
+        ),
+    ]);
+    let css_for = |url: &str| user_stylesheets.css_for(&Url::parse(url).unwrap());
+
+    assert_eq!(
+        css_for("https://build.tools.example.com/jobs?status=failed#top"),
+        "body { font-family: serif; }\n.header { background: teal; }"
+    );
+    assert_eq!(
+        css_for("http://wiki.example.com:8080/edit/Home"),
+        "body { font-family: serif; }\n.header { background: teal; }"
+    );
+    assert_eq!(
+        css_for("https://wiki.example.com/edit/Home"),
+        "body { font-family: serif; }"
+    );
+    assert_eq!(
+        css_for("https://example.com/"),
+        "body { font-family: serif; }"
+    );
+    assert_eq!(css_for("prism:settings"), "");
+
+    assert!(parse_url_patterns("https://example.com/*\n*://*.example.org/*").is_ok());
+    assert!(parse_url_patterns("https://example.com/*\nexample.org").is_err());
+
+    let script = apply_user_stylesheets_script("a::after { content: \"</style>\"; }");
+    assert!(script.contains(r#"const css = "a::after { content: \"</style>\"; }";"#));
+}
+
+fn extract_reader_fixture(html: &str) -> Option<Article> {
+    let url = Url::parse("https://news.example.com/2026/cycling/story.html").unwrap();
+    Article::extract(html, &url)
+}
+
+#[test]
+fn test_reader_mode_article() {
+    let article = extract_reader_fixture(include_str!(
+        "../tests/fixtures/reader_mode/news-article.html"
+    ))
+    .unwrap();
+    assert_eq!(article.title, "City council approves a new cycling network");
+    assert_eq!(article.byline.as_deref(), Some("Maria Okafor"));
+    assert_eq!(article.site_name.as_deref(), Some("The Riverside Gazette"));
+    assert_eq!(article.reading_minutes(), 2);
+
+    let content = &article.content;
+    assert!(content.contains("<p>The city council voted on Tuesday night"));
+    assert!(content.contains("<p>Opponents argued that removing parking spaces"));
+    // Lazily loaded images and relative links point at the original site.
+    assert!(content.contains(
+        r#"<img src="https://news.example.com/images/cycle-lanes.jpg" alt="A protected cycle lane on Bridge Street">"#
+    ));
+    assert!(content.contains(
+        r#"<a href="https://news.example.com/2026/plans/cycling-network.html">next week</a>"#
+    ));
+    assert!(content.contains("<figcaption>The first protected lanes"));
+    // The title and byline are shown above the content rather than in it.
+    assert!(!content.contains("<h"));
+    assert!(!content.contains("By "));
+    // Navigation, ads, share links, the sidebar, comments and the footer are left out.
+    for clutter in [
+        "Sport",
+        "Mega Mart",
+        "Share on",
+        "Most read",
+        "Comments",
+        "potholes",
+        "All rights reserved",
+    ] {
+        assert!(!content.contains(clutter), "{clutter} is in the article");
+    }
+    // Nothing that could run a script is kept.
+    for unsafe_markup in ["<script", "onclick", "javascript:", "analytics"] {
+        assert!(
+            !content.contains(unsafe_markup),
+            "{unsafe_markup} is in the article"
+        );
+    }
+    assert!(content.contains("cheaper to get around. Show more He added"));
+}
+
+#[test]
+fn test_reader_mode_paragraph_divs() {
+    let article =
+        extract_reader_fixture(include_str!("../tests/fixtures/reader_mode/blog-post.html"))
+            .unwrap();
+    assert_eq!(article.title, "Notes on Writing a Tiny Parser");
+    assert_eq!(article.byline, None);
+    assert_eq!(article.site_name, None);
+
+    let content = &article.content;
+    assert!(content.contains("<p>Parsers have a reputation for being difficult"));
+    assert!(content.contains(
+        "<pre><code>fn expression(tokens: &amp;mut Tokens) -&gt; Value {\n    let mut value"
+    ));
+    // Links are relative to the <base> of the page.
+    assert!(
+        content.contains(r#"<a href="https://blog.example.net/posts/tiny-parser/source.html">"#)
+    );
+    assert!(!content.contains("Archive"));
+}
+
+#[test]
+fn test_reader_mode_not_an_article() {
+    assert_eq!(
+        extract_reader_fixture(include_str!(
+            "../tests/fixtures/reader_mode/front-page.html"
+        )),
+        None
+    );
+    assert_eq!(
+        extract_reader_fixture("<html><body><p>Too short.</p></body></html>"),
+        None
+    );
+}
+
+#[test]
+fn test_reader_page_url() {
+    let article_url = Url::parse("https://news.example.com/story?id=7&page=2#top").unwrap();
+    let reader_url = reader_page_url(&article_url);
+    assert!(reader_url.as_str().starts_with("prism:reader?url="));
+    assert_eq!(original_url(&reader_url), Some(article_url.clone()));
+    assert_eq!(original_url(&article_url), None);
+    assert_eq!(original_url(&Url::parse("prism:history").unwrap()), None);
+
+    // Only web pages are taken for articles, so nothing else is linked to as the original.
+    let script_url = Url::parse("prism:reader?url=javascript%3Aalert(1)").unwrap();
+    assert_eq!(original_url(&script_url), None);
+}
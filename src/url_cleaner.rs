@@ -0,0 +1,238 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Removing tracking parameters such as `utm_source` and `fbclid` from URLs before they are
+//! loaded or copied.
+//!
+//! The rules are read from `url_cleaning.conf` in the config directory, with one rule per line,
+//! and are added to the default rules:
+//!
+//! ```text
+//! # Remove a parameter from every URL. A `*` at the end matches any suffix.
+//! strip utm_*
+//! # Remove a parameter only on a domain and its subdomains.
+//! strip ref_ on amazon.com
+//! # Never remove a parameter, on every domain or on one.
+//! keep gclid on ads.example.com
+//! # Never clean the URLs of a domain and its subdomains.
+//! exempt example.com
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use url::Url;
+
+use crate::prefs::default_config_dir;
+
+const URL_CLEANING_FILE_NAME: &str = "url_cleaning.conf";
+
+const DEFAULT_RULES: &str = "
+strip utm_*
+strip fbclid
+strip gclid
+strip dclid
+strip gbraid
+strip wbraid
+strip msclkid
+strip yclid
+strip twclid
+strip ttclid
+strip igshid
+strip mc_cid
+strip mc_eid
+strip _hsenc
+strip _hsmi
+strip mkt_tok
+strip ref_ on amazon.com
+strip pd_rd_* on amazon.com
+strip pf_rd_* on amazon.com
+strip si on youtube.com
+strip si on youtu.be
+strip si on open.spotify.com
+";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RuleAction {
+    Strip,
+    Keep,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Rule {
+    pub action: RuleAction,
+    /// The parameter name, which ends with `*` to match any suffix.
+    pub parameter: String,
+    /// The domain that the rule is limited to, including its subdomains.
+    pub domain: Option<String>,
+}
+
+impl Rule {
+    fn applies_to(&self, parameter: &str, host: &str) -> bool {
+        let parameter_matches = match self.parameter.strip_suffix('*') {
+            Some(prefix) => parameter.starts_with(prefix),
+            None => parameter == self.parameter,
+        };
+        parameter_matches
+            && self
+                .domain
+                .as_deref()
+                .is_none_or(|domain| is_on_domain(host, domain))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct UrlCleaner {
+    rules: Vec<Rule>,
+    exempt_domains: Vec<String>,
+    /// Problems found while reading the rules file, to be shown in the settings.
+    pub errors: Vec<String>,
+}
+
+impl Default for UrlCleaner {
+    fn default() -> Self {
+        let mut url_cleaner = UrlCleaner {
+            rules: vec![],
+            exempt_domains: vec![],
+            errors: vec![],
+        };
+        url_cleaner.apply(DEFAULT_RULES);
+        url_cleaner
+    }
+}
+
+impl UrlCleaner {
+    pub(crate) fn rules_file_path() -> PathBuf {
+        default_config_dir().join(URL_CLEANING_FILE_NAME)
+    }
+
+    /// Load the default rules, with the rules from the rules file in the config directory
+    /// added to them.
+    pub(crate) fn load() -> Self {
+        Self::load_from(&Self::rules_file_path())
+    }
+
+    pub(crate) fn load_from(path: &Path) -> Self {
+        let mut url_cleaner = UrlCleaner::default();
+        if let Ok(contents) = std::fs::read_to_string(path) {
+            url_cleaner.apply(&contents);
+        }
+        for error in &url_cleaner.errors {
+            warn!("{}: {error}", path.display());
+        }
+        url_cleaner
+    }
+
+    /// Parse `contents` and add its rules.
+    pub(crate) fn apply(&mut self, contents: &str) {
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (action, parameter, domain) = match words[..] {
+                ["exempt", domain] => {
+                    self.exempt_domains.push(domain.to_ascii_lowercase());
+                    continue;
+                }
+                ["strip", parameter] => (RuleAction::Strip, parameter, None),
+                ["keep", parameter] => (RuleAction::Keep, parameter, None),
+                ["strip", parameter, "on", domain] => (RuleAction::Strip, parameter, Some(domain)),
+                ["keep", parameter, "on", domain] => (RuleAction::Keep, parameter, Some(domain)),
+                _ => {
+                    self.errors.push(format!(
+                        "Line {}: expected \"strip <parameter> [on <domain>]\", \
+                         \"keep <parameter> [on <domain>]\" or \"exempt <domain>\"",
+                        line_number + 1
+                    ));
+                    continue;
+                }
+            };
+            if parameter == "*"
+                || parameter
+                    .strip_suffix('*')
+                    .unwrap_or(parameter)
+                    .contains('*')
+            {
+                self.errors.push(format!(
+                    "Line {}: \"*\" can only be used at the end of a parameter name",
+                    line_number + 1
+                ));
+                continue;
+            }
+            self.rules.push(Rule {
+                action,
+                parameter: parameter.to_owned(),
+                domain: domain.map(str::to_ascii_lowercase),
+            });
+        }
+    }
+
+    pub(crate) fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub(crate) fn exempt_domains(&self) -> &[String] {
+        &self.exempt_domains
+    }
+
+    /// Whether a parameter should be removed from a URL on the given host.
+    fn should_strip(&self, parameter: &str, host: &str) -> bool {
+        let applies = |action| {
+            self.rules
+                .iter()
+                .any(|rule| rule.action == action && rule.applies_to(parameter, host))
+        };
+        applies(RuleAction::Strip) && !applies(RuleAction::Keep)
+    }
+
+    /// The URL without its tracking parameters, or `None` if it has none or is exempt.
+    pub(crate) fn clean(&self, url: &Url) -> Option<Url> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+        let host = url.host_str()?.to_ascii_lowercase();
+        if self
+            .exempt_domains
+            .iter()
+            .any(|domain| is_on_domain(&host, domain))
+        {
+            return None;
+        }
+
+        // The kept parameters are copied as they are, rather than being decoded and encoded
+        // again, so that the rest of the URL stays exactly the same.
+        let query = url.query()?;
+        let kept: Vec<&str> = query
+            .split('&')
+            .filter(|pair| {
+                let name = pair.split('=').next().unwrap_or_default();
+                let name = percent_encoding::percent_decode_str(name).decode_utf8_lossy();
+                pair.is_empty() || !self.should_strip(&name, &host)
+            })
+            .collect();
+        if kept.len() == query.split('&').count() {
+            return None;
+        }
+
+        let mut clean_url = url.clone();
+        let kept = kept.join("&");
+        clean_url.set_query((!kept.is_empty()).then_some(kept.as_str()));
+        Some(clean_url)
+    }
+
+    /// The URL without its tracking parameters.
+    pub(crate) fn clean_or_keep(&self, url: Url) -> Url {
+        self.clean(&url).unwrap_or(url)
+    }
+}
+
+/// Whether the host is the domain or one of its subdomains.
+fn is_on_domain(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.ends_with('.'))
+}
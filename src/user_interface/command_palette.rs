@@ -0,0 +1,153 @@
+use egui::{Key, RichText};
+
+use crate::keymap::{BrowserAction, Keymap};
+use crate::running_app_state::UserInterfaceCommand;
+
+/// The most entries that are shown at once, the rest can be reached by typing more.
+const MAXIMUM_VISIBLE_ENTRIES: usize = 12;
+
+/// An overlay that fuzzy searches every [`BrowserAction`] and runs the chosen one.
+#[derive(Default)]
+pub(crate) struct CommandPalette {
+    open: bool,
+    query: String,
+    selected: usize,
+    /// Set when the palette is opened, so that the search field takes focus on the next update.
+    focus_query: bool,
+}
+
+impl CommandPalette {
+    pub(crate) fn open(&mut self) {
+        self.open = true;
+        self.query.clear();
+        self.selected = 0;
+        self.focus_query = true;
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub(crate) fn show(
+        &mut self,
+        ctx: &egui::Context,
+        keymap: &Keymap,
+        event_queue: &mut Vec<UserInterfaceCommand>,
+    ) {
+        if !self.open {
+            return;
+        }
+
+        let mut entries: Vec<(i32, BrowserAction)> = BrowserAction::ALL
+            .iter()
+            .filter_map(|action| {
+                let score = fuzzy_score(&self.query, &action.description())
+                    .max(fuzzy_score(&self.query, &action.name()))?;
+                Some((score, *action))
+            })
+            .collect();
+        // Stable, so that equally good matches keep the order of `BrowserAction::ALL`.
+        entries.sort_by_key(|(score, _)| -score);
+        entries.truncate(MAXIMUM_VISIBLE_ENTRIES);
+
+        let (escape, enter, up, down) = ctx.input_mut(|input| {
+            (
+                input.consume_key(egui::Modifiers::NONE, Key::Escape),
+                input.consume_key(egui::Modifiers::NONE, Key::Enter),
+                input.consume_key(egui::Modifiers::NONE, Key::ArrowUp),
+                input.consume_key(egui::Modifiers::NONE, Key::ArrowDown),
+            )
+        });
+        if down {
+            self.selected = (self.selected + 1).min(entries.len().saturating_sub(1));
+        }
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        self.selected = self.selected.min(entries.len().saturating_sub(1));
+
+        let mut chosen = enter.then(|| entries.get(self.selected).map(|(_, action)| *action));
+        egui::Window::new("Command palette")
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .fixed_size([480.0, 0.0])
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 48.0])
+            .show(ctx, |ui| {
+                let query_field = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text("Type a command")
+                        .desired_width(f32::INFINITY),
+                );
+                if self.focus_query {
+                    query_field.request_focus();
+                    self.focus_query = false;
+                }
+                if query_field.changed() {
+                    self.selected = 0;
+                }
+                ui.separator();
+
+                if entries.is_empty() {
+                    ui.weak("No matching commands");
+                }
+                for (index, (_, action)) in entries.iter().enumerate() {
+                    let shortcuts = keymap.shortcuts_for(*action).join(", ");
+                    let response = ui
+                        .horizontal(|ui| {
+                            let label =
+                                ui.selectable_label(index == self.selected, action.description());
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.label(RichText::new(shortcuts).weak());
+                                },
+                            );
+                            label
+                        })
+                        .inner;
+                    if response.clicked() {
+                        chosen = Some(Some(*action));
+                    }
+                }
+            });
+
+        if escape {
+            self.open = false;
+        }
+        if let Some(chosen) = chosen {
+            self.open = false;
+            if let Some(action) = chosen {
+                event_queue.push(action.user_interface_command());
+            }
+        }
+    }
+}
+
+/// Score how well `query` matches `candidate`, where all characters of the query have to appear
+/// in order. Consecutive characters and characters at the start of words score higher. Returns
+/// `None` if the query does not match.
+pub(crate) fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match = None;
+    for query_character in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let offset = candidate[position..]
+            .iter()
+            .position(|character| *character == query_character)?;
+        let index = position + offset;
+
+        score += 1;
+        if previous_match.is_some_and(|previous| previous + 1 == index) {
+            score += 5;
+        }
+        if index == 0 || !candidate[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous_match = Some(index);
+        position = index + 1;
+    }
+    // Prefer shorter candidates when the query matches equally well.
+    Some(score * 100 - candidate.len() as i32)
+}
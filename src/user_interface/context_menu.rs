@@ -0,0 +1,135 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use servo::ContextMenu;
+use url::Url;
+
+use crate::running_app_state::UserInterfaceCommand;
+
+/// The longest selection that is shown in full in the "Search the web for …" item.
+const MAXIMUM_SELECTION_LABEL_LENGTH: usize = 24;
+
+/// What was under the cursor when a context menu was requested. This decides which of the
+/// browser's own items are added below the ones Servo supplies.
+pub(crate) struct ContextMenuContext {
+    pub link_url: Option<Url>,
+    pub image_url: Option<Url>,
+    /// The text selected on the page. Reading the selection needs a round trip to the page,
+    /// so this is filled in shortly after the menu is shown.
+    pub selection: Rc<RefCell<Option<String>>>,
+}
+
+impl ContextMenuContext {
+    pub(crate) fn new(menu: &ContextMenu) -> Self {
+        let element_info = menu.element_info();
+        Self {
+            link_url: element_info.link_url.clone(),
+            image_url: element_info.image_url.clone(),
+            selection: Default::default(),
+        }
+    }
+
+    /// The browser provided items to show for this context, in display order, with separators
+    /// between the groups of items that apply.
+    pub(crate) fn items(&self) -> Vec<EmbedderContextMenuItem> {
+        let mut groups = vec![];
+        if let Some(link_url) = &self.link_url {
+            groups.push(vec![
+                EmbedderContextMenuItem::OpenLinkInNewTab(link_url.clone()),
+                EmbedderContextMenuItem::CopyLinkAddress(link_url.clone()),
+                EmbedderContextMenuItem::CopyCleanLink(link_url.clone()),
+                EmbedderContextMenuItem::BookmarkLink(link_url.clone()),
+            ]);
+        }
+        if let Some(image_url) = &self.image_url {
+            groups.push(vec![EmbedderContextMenuItem::SaveImageAs(
+                image_url.clone(),
+            )]);
+        }
+        if let Some(selection) = self
+            .selection
+            .borrow()
+            .as_ref()
+            .map(|selection| selection.trim())
+            .filter(|selection| !selection.is_empty())
+        {
+            groups.push(vec![EmbedderContextMenuItem::SearchForSelection(
+                selection.to_owned(),
+            )]);
+        }
+        groups.push(vec![EmbedderContextMenuItem::Inspect]);
+
+        let mut items = vec![];
+        for group in groups {
+            if !items.is_empty() {
+                items.push(EmbedderContextMenuItem::Separator);
+            }
+            items.extend(group);
+        }
+        items
+    }
+}
+
+/// An item that the browser adds to the context menus of web content.
+#[derive(Debug, PartialEq)]
+pub(crate) enum EmbedderContextMenuItem {
+    OpenLinkInNewTab(Url),
+    CopyLinkAddress(Url),
+    CopyCleanLink(Url),
+    BookmarkLink(Url),
+    SaveImageAs(Url),
+    SearchForSelection(String),
+    Inspect,
+    Separator,
+}
+
+impl EmbedderContextMenuItem {
+    pub(crate) fn label(&self) -> String {
+        match self {
+            EmbedderContextMenuItem::OpenLinkInNewTab(_) => "Open link in new tab".into(),
+            EmbedderContextMenuItem::CopyLinkAddress(_) => "Copy link address".into(),
+            EmbedderContextMenuItem::CopyCleanLink(_) => "Copy clean link".into(),
+            EmbedderContextMenuItem::BookmarkLink(_) => "Bookmark this link".into(),
+            EmbedderContextMenuItem::SaveImageAs(_) => "Save image as…".into(),
+            EmbedderContextMenuItem::SearchForSelection(selection) => {
+                let selection = if selection.chars().count() > MAXIMUM_SELECTION_LABEL_LENGTH {
+                    let truncated: String = selection
+                        .chars()
+                        .take(MAXIMUM_SELECTION_LABEL_LENGTH - 1)
+                        .collect();
+                    format!("{truncated}…")
+                } else {
+                    selection.clone()
+                };
+                format!("Search the web for “{selection}”")
+            }
+            EmbedderContextMenuItem::Inspect => "Inspect".into(),
+            EmbedderContextMenuItem::Separator => String::new(),
+        }
+    }
+
+    /// The command to run when this item is chosen, if any.
+    pub(crate) fn into_user_interface_command(self) -> Option<UserInterfaceCommand> {
+        Some(match self {
+            EmbedderContextMenuItem::OpenLinkInNewTab(url) => {
+                UserInterfaceCommand::OpenInNewWebView(url)
+            }
+            EmbedderContextMenuItem::CopyLinkAddress(url) => {
+                UserInterfaceCommand::CopyToClipboard(url.into())
+            }
+            EmbedderContextMenuItem::CopyCleanLink(url) => {
+                UserInterfaceCommand::CopyCleanLink(Some(url))
+            }
+            EmbedderContextMenuItem::BookmarkLink(url) => UserInterfaceCommand::AddBookmark {
+                title: url.to_string(),
+                url: url.into(),
+            },
+            EmbedderContextMenuItem::SaveImageAs(url) => UserInterfaceCommand::SaveResource(url),
+            EmbedderContextMenuItem::SearchForSelection(selection) => {
+                UserInterfaceCommand::SearchInNewWebView(selection)
+            }
+            EmbedderContextMenuItem::Inspect => UserInterfaceCommand::Inspect,
+            EmbedderContextMenuItem::Separator => return None,
+        })
+    }
+}
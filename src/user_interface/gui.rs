@@ -7,11 +7,9 @@ use std::rc::Rc;
 use std::sync::Arc;
 
 use dpi::PhysicalSize;
-use egui::text::{CCursor, CCursorRange};
-use egui::text_edit::TextEditState;
 use egui::{
-    Key, Label, LayerId, Modifiers, PaintCallback, Popup, SidePanel, TopBottomPanel, Vec2,
-    WidgetInfo, WidgetType, pos2,
+    Label, LayerId, PaintCallback, Popup, RichText, SidePanel, TopBottomPanel, Vec2, WidgetInfo,
+    WidgetType, pos2,
 };
 use egui_glow::{CallbackFn, EguiGlow};
 use egui_winit::EventResponse;
@@ -27,19 +25,25 @@ use winit::window::Window;
 
 use crate::NEW_TAB_PAGE_URL;
 use crate::browser_window::BrowserWindow;
-use crate::data_storage::history::HistoryEntry;
 use crate::event_loop::AppEvent;
 use crate::geometry::winit_position_to_euclid_point;
+use crate::misc_utils::opensearch::AdvertisedSearchEngine;
+use crate::misc_utils::reader_mode::original_url;
+use crate::misc_utils::save_page::SavePageMode;
+use crate::misc_utils::screenshot::{ScreenshotArea, ScreenshotTarget};
 use crate::running_app_state::{RunningAppState, UserInterfaceCommand};
 
 use super::browser_tab::create_browser_tab;
+use super::command_palette::CommandPalette;
+use super::search_bar::SearchBar;
+use super::settings_menu::{SettingsPage, create_settings_page};
 
 pub const TAB_WIDTH: f32 = 200.0;
 pub const FAVICON_SIZE: f32 = 16.0;
 
-enum AppPage {
+pub(crate) enum AppPage {
     Main,
-    History,
+    Settings,
 }
 
 /// The user interface of a headed servoshell. Currently this is implemented via
@@ -57,6 +61,9 @@ pub struct Gui {
     /// Whether the location has been edited by the user without clicking Go.
     location_dirty: bool,
 
+    /// The suggestions shown below the location field.
+    search_bar: SearchBar,
+
     /// The [`LoadStatus`] of the active `WebView`.
     load_status: LoadStatus,
 
@@ -80,9 +87,16 @@ pub struct Gui {
     /// If the webviews need their theme updated to match winit then this contains the new theme, otherwise its None
     updated_theme: Option<servo::Theme>,
 
-    browser_history_cache: Vec<HistoryEntry>,
     current_page: AppPage,
+    settings_page: SettingsPage,
     menu_is_active: bool,
+    command_palette: CommandPalette,
+
+    /// An image that should be put on the clipboard during the next egui update.
+    pending_clipboard_image: Option<egui::ColorImage>,
+
+    /// Text that should be put on the clipboard during the next egui update.
+    pending_clipboard_text: Option<String>,
 }
 
 impl Drop for Gui {
@@ -128,6 +142,7 @@ impl Gui {
         });
         egui_extras::install_image_loaders(&context.egui_ctx);
 
+        let search_bar = SearchBar::new(event_loop_proxy.clone(), winit_window.id());
         let colour_scheme_subscription = mundy::Preferences::subscribe(
             mundy::Interest::ColorScheme,
             Self::update_style(
@@ -146,6 +161,7 @@ impl Gui {
             last_mouse_position: None,
             location: String::new(),
             location_dirty: false,
+            search_bar,
             load_status: LoadStatus::Complete,
             status_text: None,
             can_go_back: false,
@@ -153,14 +169,19 @@ impl Gui {
             favicon_textures: Default::default(),
             _colour_scheme_subscription: colour_scheme_subscription,
             updated_theme: None,
-            browser_history_cache: Default::default(),
             current_page: AppPage::Main,
+            settings_page: SettingsPage::default(),
+            command_palette: CommandPalette::default(),
             menu_is_active: false,
+            pending_clipboard_image: None,
+            pending_clipboard_text: None,
         }
     }
 
     pub(crate) fn webview_should_get_user_input(&self) -> bool {
-        !self.menu_is_active && matches!(self.current_page, AppPage::Main)
+        !self.menu_is_active
+            && !self.command_palette.is_open()
+            && matches!(self.current_page, AppPage::Main)
     }
 
     fn update_style(
@@ -214,6 +235,26 @@ impl Gui {
         std::mem::take(&mut self.event_queue)
     }
 
+    pub(crate) fn queue_user_interface_command(&mut self, command: UserInterfaceCommand) {
+        self.event_queue.push(command);
+    }
+
+    pub(crate) fn open_command_palette(&mut self) {
+        self.command_palette.open();
+    }
+
+    pub(crate) fn show_settings_page(&mut self) {
+        self.current_page = AppPage::Settings;
+    }
+
+    pub(crate) fn copy_text_to_clipboard(&mut self, text: String) {
+        self.pending_clipboard_text = Some(text);
+    }
+
+    pub(crate) fn copy_image_to_clipboard(&mut self, image: egui::ColorImage) {
+        self.pending_clipboard_image = Some(image);
+    }
+
     pub(crate) fn on_window_event(
         &mut self,
         winit_window: &Window,
@@ -270,18 +311,23 @@ impl Gui {
     fn is_in_egui_toolbar_rect(&self, position: Point2D<f32, DeviceIndependentPixel>) -> bool {
         match self.current_page {
             AppPage::Main => {
-                if self.menu_is_active {
+                if self.menu_is_active
+                    || self.command_palette.is_open()
+                    || self
+                        .search_bar
+                        .is_over_suggestions(pos2(position.x, position.y))
+                {
                     true
                 } else {
                     position.y < self.toolbar_height.get() || position.x < self.tabbar_width.get()
                 }
             }
-            AppPage::History => true,
+            AppPage::Settings => true,
         }
     }
 
     /// Create a frameless button with square sizing, as used in the toolbar.
-    fn toolbar_image_button(image_value: egui::ImageSource) -> egui::Button<'_> {
+    pub(crate) fn toolbar_image_button(image_value: egui::ImageSource) -> egui::Button<'_> {
         egui::Button::image(image_value)
             .frame(false)
             .min_size(Vec2 { x: 20.0, y: 20.0 })
@@ -300,13 +346,17 @@ impl Gui {
             tabbar_width,
             location,
             location_dirty,
+            search_bar,
             favicon_textures,
             current_page,
-            browser_history_cache,
+            settings_page,
             can_go_back,
             can_go_forward,
             load_status,
             menu_is_active,
+            command_palette,
+            pending_clipboard_image,
+            pending_clipboard_text,
             ..
         } = self;
 
@@ -314,6 +364,12 @@ impl Gui {
         let current_theme = winit_window.theme().unwrap_or(winit::window::Theme::Light);
         context.run(winit_window, |ctx| {
             load_pending_favicons(ctx, browser_window, favicon_textures);
+            if let Some(image) = pending_clipboard_image.take() {
+                ctx.copy_image(image);
+            }
+            if let Some(text) = pending_clipboard_text.take() {
+                ctx.copy_text(text);
+            }
 
             // TODO: While in fullscreen add some way to mitigate the increased phishing risk
             // when not displaying the URL bar: https://github.com/servo/servo/issues/32443
@@ -322,13 +378,13 @@ impl Gui {
                     .fill(ctx.style().visuals.window_fill)
                     .inner_margin(4.0);
                 match current_page {
-                    AppPage::History => {
-                        create_history_page(
+                    AppPage::Settings => {
+                        create_settings_page(
                             ctx,
-                            browser_history_cache,
+                            state,
                             current_theme,
                             current_page,
-                            event_queue,
+                            settings_page,
                         );
                     }
                     AppPage::Main => {
@@ -338,6 +394,7 @@ impl Gui {
                             event_queue,
                             location,
                             location_dirty,
+                            search_bar,
                             favicon_textures,
                             current_page,
                             current_theme,
@@ -346,7 +403,6 @@ impl Gui {
                             *can_go_back,
                             *can_go_forward,
                             *load_status,
-                            browser_history_cache,
                             menu_is_active,
                         );
                     }
@@ -362,7 +418,8 @@ impl Gui {
             let scale =
                 Scale::<_, DeviceIndependentPixel, DevicePixel>::new(ctx.pixels_per_point());
 
-            browser_window.for_each_active_dialog(|dialog| dialog.update(ctx));
+            browser_window.for_each_active_dialog(|dialog| dialog.update(ctx, event_queue));
+            command_palette.show(ctx, &state.keymap(), event_queue);
 
             // If the top parts of the GUI changed size, then update the size of the WebView and also
             // the size of its RenderingContext.
@@ -566,6 +623,7 @@ fn create_main_page(
     event_queue: &mut Vec<UserInterfaceCommand>,
     location: &mut String,
     location_dirty: &mut bool,
+    search_bar: &mut SearchBar,
     favicon_textures: &mut HashMap<WebViewId, (egui::TextureHandle, egui::load::SizedTexture)>,
     current_page: &mut AppPage,
     current_theme: winit::window::Theme,
@@ -574,7 +632,6 @@ fn create_main_page(
     can_go_back: bool,
     can_go_forward: bool,
     load_status: LoadStatus,
-    browser_history_cache: &mut Vec<HistoryEntry>,
     menu_is_active: &mut bool,
 ) {
     TopBottomPanel::top("toolbar").frame(frame).show(ctx, |ui| {
@@ -670,46 +727,25 @@ fn create_main_page(
                     ui.available_size(),
                     egui::Layout::left_to_right(egui::Align::Center),
                     |ui| {
-                        let location_id = egui::Id::new("location_input");
-                        let location_field = ui.add_sized(
-                            Vec2::new(ui.available_width() - 20.0, ui.available_height()),
-                            egui::TextEdit::singleline(location)
-                                .id(location_id)
-                                .hint_text("Search or enter address"),
+                        let badge_is_open = content_blocking_badge(
+                            ui,
+                            state,
+                            browser_window,
+                            event_queue,
+                            current_theme,
+                        );
+                        reader_mode_button(ui, browser_window, event_queue, current_theme);
+                        let dark_mode_is_open =
+                            forced_dark_mode_toggle(ui, state, browser_window, current_theme);
+                        security_indicator(ui, browser_window, current_theme);
+                        search_bar.show(
+                            ui,
+                            location,
+                            location_dirty,
+                            state,
+                            browser_window,
+                            event_queue,
                         );
-
-                        if location_field.changed() {
-                            *location_dirty = true;
-                        }
-                        // Handle adddress bar shortcut.
-                        if ui.input(|i| {
-                            if cfg!(target_os = "macos") {
-                                i.clone().consume_key(Modifiers::COMMAND, Key::L)
-                            } else {
-                                i.clone().consume_key(Modifiers::COMMAND, Key::L)
-                                    || i.clone().consume_key(Modifiers::ALT, Key::D)
-                            }
-                        }) {
-                            // The focus request immediately makes gained_focus return true.
-                            location_field.request_focus();
-                        }
-                        // Select address bar text when it's focused (click or shortcut).
-                        if location_field.gained_focus() {
-                            if let Some(mut state) = TextEditState::load(ui.ctx(), location_id) {
-                                // Select the whole input.
-                                state.cursor.set_char_range(Some(CCursorRange::two(
-                                    CCursor::new(0),
-                                    CCursor::new(location.len()),
-                                )));
-                                state.store(ui.ctx(), location_id);
-                            }
-                        }
-                        // Navigate to address when enter is pressed in the address bar.
-                        if location_field.lost_focus()
-                            && ui.input(|i| i.clone().key_pressed(Key::Enter))
-                        {
-                            event_queue.push(UserInterfaceCommand::Go(location.clone()));
-                        }
 
                         let menu_button = ui.add(Gui::toolbar_image_button(match current_theme {
                             winit::window::Theme::Dark => {
@@ -720,21 +756,78 @@ fn create_main_page(
                             }
                         }));
                         let menu_popup = Popup::menu(&menu_button);
-                        *menu_is_active = menu_popup.is_open();
+                        *menu_is_active =
+                            menu_popup.is_open() || badge_is_open || dark_mode_is_open;
                         menu_popup.show(|ui| {
                             ui.set_min_width(160.0);
 
                             if ui.button("History").clicked() {
-                                *browser_history_cache = state.get_browser_history();
-                                *current_page = AppPage::History;
+                                event_queue.push(UserInterfaceCommand::ShowHistory);
+                                ui.close_kind(egui::UiKind::Menu);
+                            }
+
+                            if ui.button("Bookmarks").clicked() {
+                                event_queue.push(UserInterfaceCommand::ShowBookmarks);
                                 ui.close_kind(egui::UiKind::Menu);
                             }
 
                             if ui.button("Downloads").clicked() {
+                                event_queue.push(UserInterfaceCommand::ShowDownloads);
+                                ui.close_kind(egui::UiKind::Menu);
+                            }
+
+                            ui.separator();
+                            if ui.button("Copy clean link").clicked() {
+                                event_queue.push(UserInterfaceCommand::CopyCleanLink(None));
+                                ui.close_kind(egui::UiKind::Menu);
+                            }
+                            ui.menu_button("Save page as…", |ui| {
+                                if ui.button("HTML only").clicked() {
+                                    event_queue.push(UserInterfaceCommand::SavePage(
+                                        SavePageMode::HtmlOnly,
+                                    ));
+                                    ui.close_kind(egui::UiKind::Menu);
+                                }
+                                if ui.button("Complete").clicked() {
+                                    event_queue.push(UserInterfaceCommand::SavePage(
+                                        SavePageMode::Complete,
+                                    ));
+                                    ui.close_kind(egui::UiKind::Menu);
+                                }
+                            });
+                            screenshot_menu(
+                                ui,
+                                "Screenshot visible area",
+                                ScreenshotArea::Visible,
+                                event_queue,
+                            );
+                            screenshot_menu(
+                                ui,
+                                "Screenshot full page",
+                                ScreenshotArea::FullPage,
+                                event_queue,
+                            );
+                            ui.separator();
+
+                            for search_engine in uninstalled_search_engines(state, browser_window) {
+                                if ui
+                                    .button(format!("Add search engine “{}”", search_engine.title))
+                                    .clicked()
+                                {
+                                    event_queue.push(UserInterfaceCommand::InstallSearchEngine(
+                                        search_engine.description_url,
+                                    ));
+                                    ui.close_kind(egui::UiKind::Menu);
+                                }
+                            }
+
+                            if ui.button("Command palette").clicked() {
+                                event_queue.push(UserInterfaceCommand::OpenCommandPalette);
                                 ui.close_kind(egui::UiKind::Menu);
                             }
 
                             if ui.button("Settings").clicked() {
+                                *current_page = AppPage::Settings;
                                 ui.close_kind(egui::UiKind::Menu);
                             }
                         });
@@ -793,85 +886,265 @@ fn create_main_page(
         });
 }
 
-fn create_history_page(
-    ctx: &egui::Context,
-    browser_history_cache: &[HistoryEntry],
+/// Show how many requests the content blocker has blocked on the page in the active tab, with a
+/// popup to turn content blocking off for the site. Returns whether the popup is open.
+fn content_blocking_badge(
+    ui: &mut egui::Ui,
+    state: &RunningAppState,
+    browser_window: &BrowserWindow,
+    event_queue: &mut Vec<UserInterfaceCommand>,
     current_theme: winit::window::Theme,
-    current_page: &mut AppPage,
+) -> bool {
+    let Some(url) = browser_window
+        .active_webview()
+        .and_then(|webview| webview.url())
+    else {
+        return false;
+    };
+    let Some(host) = url
+        .host_str()
+        .filter(|_| matches!(url.scheme(), "http" | "https"))
+    else {
+        return false;
+    };
+    if !state.content_blocking_enabled() || state.content_blocker().is_empty() {
+        return false;
+    }
+
+    let enabled = state.content_blocking_enabled_for(&url);
+    let count = browser_window.active_blocked_request_count();
+    let badge = ui.add(
+        egui::Button::image_and_text(
+            match current_theme {
+                winit::window::Theme::Dark => {
+                    egui::include_image!("../../resources/icons/shield_dark.svg")
+                }
+                winit::window::Theme::Light => {
+                    egui::include_image!("../../resources/icons/shield_light.svg")
+                }
+            },
+            if enabled {
+                count.to_string()
+            } else {
+                "Off".to_owned()
+            },
+        )
+        .frame(false),
+    );
+    badge.widget_info(|| {
+        let mut info = WidgetInfo::new(WidgetType::Button);
+        info.label = Some("Content blocking".into());
+        info
+    });
+
+    let popup = Popup::menu(&badge);
+    let is_open = popup.is_open();
+    popup.show(|ui| {
+        ui.set_min_width(200.0);
+        if enabled {
+            ui.label(match count {
+                1 => "1 request blocked on this page".to_owned(),
+                count => format!("{count} requests blocked on this page"),
+            });
+        }
+        let mut site_enabled = enabled;
+        if ui
+            .checkbox(&mut site_enabled, format!("Block content on {host}"))
+            .changed()
+        {
+            state.set_content_blocking_enabled_for_host(host, site_enabled);
+            event_queue.push(UserInterfaceCommand::Reload);
+            ui.close_kind(egui::UiKind::Menu);
+        }
+    });
+    is_open
+}
+
+/// A button to read the article in the active tab in reader mode, which is only shown if the
+/// page looks like an article, or to go back to the original page from reader mode.
+fn reader_mode_button(
+    ui: &mut egui::Ui,
+    browser_window: &BrowserWindow,
     event_queue: &mut Vec<UserInterfaceCommand>,
+    current_theme: winit::window::Theme,
 ) {
-    egui::CentralPanel::default().show(ctx, |ui| {
-        ui.allocate_ui_with_layout(
-            ui.available_size(),
-            egui::Layout::top_down(egui::Align::LEFT),
-            |ui| {
-                let back_button = ui.add(Gui::toolbar_image_button(match current_theme {
+    let in_reader_mode = browser_window
+        .active_webview()
+        .and_then(|webview| webview.url())
+        .and_then(|url| original_url(&url))
+        .is_some();
+    if !in_reader_mode && browser_window.active_reader_article().is_none() {
+        return;
+    }
+
+    let button = ui.add(
+        Gui::toolbar_image_button(match current_theme {
+            winit::window::Theme::Dark => {
+                egui::include_image!("../../resources/icons/reader_dark.svg")
+            }
+            winit::window::Theme::Light => {
+                egui::include_image!("../../resources/icons/reader_light.svg")
+            }
+        })
+        .selected(in_reader_mode),
+    );
+    let label = if in_reader_mode {
+        "Leave reader mode"
+    } else {
+        "Reader mode"
+    };
+    button.widget_info(|| {
+        let mut info = WidgetInfo::new(WidgetType::Button);
+        info.label = Some(label.into());
+        info
+    });
+    if button.on_hover_text(label).clicked() {
+        event_queue.push(UserInterfaceCommand::ToggleReaderMode);
+    }
+}
+
+/// A button with a popup to turn the forced dark mode on or off, everywhere or for the site in
+/// the active tab. Returns whether the popup is open.
+fn forced_dark_mode_toggle(
+    ui: &mut egui::Ui,
+    state: &RunningAppState,
+    browser_window: &BrowserWindow,
+    current_theme: winit::window::Theme,
+) -> bool {
+    let Some(url) = browser_window
+        .active_webview()
+        .and_then(|webview| webview.url())
+    else {
+        return false;
+    };
+    let Some(host) = url
+        .host_str()
+        .filter(|_| matches!(url.scheme(), "http" | "https"))
+    else {
+        return false;
+    };
+
+    let button = ui.add(Gui::toolbar_image_button(match current_theme {
+        winit::window::Theme::Dark => {
+            egui::include_image!("../../resources/icons/moon_dark.svg")
+        }
+        winit::window::Theme::Light => {
+            egui::include_image!("../../resources/icons/moon_light.svg")
+        }
+    }));
+    button.widget_info(|| {
+        let mut info = WidgetInfo::new(WidgetType::Button);
+        info.label = Some("Dark mode".into());
+        info
+    });
+
+    let popup = Popup::menu(&button);
+    let is_open = popup.is_open();
+    popup.show(|ui| {
+        ui.set_min_width(200.0);
+        let mut enabled = state.forced_dark_mode_enabled();
+        if ui.checkbox(&mut enabled, "Force dark mode").changed() {
+            state.set_forced_dark_mode_enabled(enabled);
+        }
+        ui.add_enabled_ui(enabled, |ui| {
+            let mut site_enabled = state.forced_dark_mode_enabled_for(&url);
+            if ui
+                .checkbox(&mut site_enabled, format!("Darken {host}"))
+                .changed()
+            {
+                state.set_forced_dark_mode_enabled_for_host(host, site_enabled);
+            }
+        });
+    });
+    is_open
+}
+
+/// Show whether the connection to the site in the active tab is encrypted, next to the location
+/// field. Internal pages are neither, so nothing is shown for them.
+fn security_indicator(
+    ui: &mut egui::Ui,
+    browser_window: &BrowserWindow,
+    current_theme: winit::window::Theme,
+) {
+    let Some(url) = browser_window
+        .active_webview()
+        .and_then(|webview| webview.url())
+    else {
+        return;
+    };
+    match url.scheme() {
+        "https" => {
+            let padlock = ui.add(
+                egui::Image::new(match current_theme {
                     winit::window::Theme::Dark => {
-                        egui::include_image!("../../resources/icons/back_dark.svg")
+                        egui::include_image!("../../resources/icons/padlock_dark.svg")
                     }
                     winit::window::Theme::Light => {
-                        egui::include_image!("../../resources/icons/back_light.svg")
+                        egui::include_image!("../../resources/icons/padlock_light.svg")
                     }
-                }));
-                back_button.widget_info(|| {
-                    let mut info = WidgetInfo::new(WidgetType::Button);
-                    info.label = Some("Back".into());
-                    info
-                });
-                if back_button.clicked() {
-                    *current_page = AppPage::Main;
-                }
-                egui::ScrollArea::vertical()
-                    .auto_shrink([false, false])
-                    .stick_to_right(true)
-                    .show(ui, |ui| {
-                        ui.vertical(|ui| {
-                            egui_extras::TableBuilder::new(ui)
-                                .striped(true)
-                                .column(egui_extras::Column::remainder())
-                                .column(egui_extras::Column::remainder())
-                                .column(egui_extras::Column::exact(150.0))
-                                .header(20.0, |mut header| {
-                                    header.col(|ui| {
-                                        ui.label("Title");
-                                    });
-                                    header.col(|ui| {
-                                        ui.label("URL");
-                                    });
-                                    header.col(|ui| {
-                                        ui.label("Time Accessed");
-                                    });
-                                })
-                                .body(|mut body| {
-                                    for entry in browser_history_cache {
-                                        body.row(20.0, |mut row| {
-                                            row.col(|ui| {
-                                                ui.label(&entry.title);
-                                            });
-                                            row.col(|ui| {
-                                                let url_link = ui.link(&entry.url);
-                                                if url_link.clicked() {
-                                                    event_queue.push(UserInterfaceCommand::Go(
-                                                        entry.url.clone(),
-                                                    ));
-                                                    *current_page = AppPage::Main;
-                                                }
-                                            });
-                                            row.col(|ui| {
-                                                ui.label(
-                                                    entry
-                                                        .time_accessed
-                                                        .format("%d-%m-%Y %H:%M:%S")
-                                                        .to_string(),
-                                                );
-                                            });
-                                        });
-                                    }
-                                });
-                        });
-                    });
-            },
-        );
+                })
+                .fit_to_exact_size(Vec2 { x: 16.0, y: 16.0 }),
+            );
+            padlock.on_hover_text("The connection to this site is secure");
+        }
+        "http" => {
+            ui.add(Label::new(RichText::new("Not secure").small().weak()))
+                .on_hover_text(
+                    "The connection to this site isn't encrypted, so others on your network \
+                    can see and change what you send and receive",
+                );
+        }
+        _ => {}
+    }
+}
+
+/// The search engines advertised by the active page whose site has no installed engine yet.
+fn uninstalled_search_engines(
+    state: &RunningAppState,
+    browser_window: &BrowserWindow,
+) -> Vec<AdvertisedSearchEngine> {
+    let search_engines = state.search_engines();
+    let installed_hosts: Vec<String> = search_engines
+        .iter()
+        .filter_map(|search_engine| {
+            let url = url::Url::parse(&search_engine.search_url.replace("%s", "")).ok()?;
+            url.host_str().map(str::to_owned)
+        })
+        .collect();
+    browser_window
+        .active_advertised_search_engines()
+        .into_iter()
+        .filter(|advertised| {
+            advertised
+                .description_url
+                .host_str()
+                .is_some_and(|host| !installed_hosts.iter().any(|installed| installed == host))
+        })
+        .collect()
+}
+
+/// A submenu offering the places a screenshot of the given area can be sent to.
+fn screenshot_menu(
+    ui: &mut egui::Ui,
+    label: &str,
+    area: ScreenshotArea,
+    event_queue: &mut Vec<UserInterfaceCommand>,
+) {
+    ui.menu_button(label, |ui| {
+        if ui.button("Save as PNG…").clicked() {
+            event_queue.push(UserInterfaceCommand::Screenshot(
+                area,
+                ScreenshotTarget::File,
+            ));
+            ui.close_kind(egui::UiKind::Menu);
+        }
+        if ui.button("Copy to clipboard").clicked() {
+            event_queue.push(UserInterfaceCommand::Screenshot(
+                area,
+                ScreenshotTarget::Clipboard,
+            ));
+            ui.close_kind(egui::UiKind::Menu);
+        }
     });
 }
 
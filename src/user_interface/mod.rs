@@ -1,4 +1,6 @@
 pub mod browser_tab;
+pub mod command_palette;
+pub mod context_menu;
 pub mod gui;
 pub mod search_bar;
 pub mod settings_menu;
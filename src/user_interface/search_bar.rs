@@ -0,0 +1,715 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, unbounded};
+use egui::text::{CCursor, CCursorRange};
+use egui::text_edit::TextEditState;
+use egui::{Key, Modifiers, RichText, Vec2};
+use log::warn;
+use servo::WebViewId;
+use url::Url;
+use winit::event_loop::EventLoopProxy;
+use winit::window::WindowId;
+
+use crate::browser_window::BrowserWindow;
+use crate::data_storage::BrowserDataConnection;
+use crate::data_storage::bookmarks::BookmarkEntry;
+use crate::data_storage::history::VisitedPage;
+use crate::data_storage::search_engines::fill_in_search_terms;
+use crate::event_loop::AppEvent;
+use crate::misc_utils::search_suggestions::fetch_search_suggestions;
+use crate::parser::location_bar_input_to_search;
+use crate::running_app_state::{RunningAppState, UserInterfaceCommand};
+
+/// The most suggestions from the history, bookmarks and open tabs shown below the location
+/// field.
+const MAXIMUM_SUGGESTIONS: usize = 8;
+
+/// The most suggestions from the search engine shown below the others.
+const MAXIMUM_SEARCH_SUGGESTIONS: usize = 5;
+
+/// How long typing has to pause before the search engine is asked for suggestions.
+const SEARCH_SUGGESTIONS_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// How long to wait for the search engine, after which its suggestions would be out of date.
+const SEARCH_SUGGESTIONS_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum SuggestionKind {
+    /// A tab that is already open, choosing it switches to that tab.
+    OpenTab(WebViewId),
+    Bookmark,
+    History,
+    /// A search suggested by the search engine, choosing it searches for the title.
+    Search,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Suggestion {
+    pub kind: SuggestionKind,
+    pub title: String,
+    pub url: String,
+    score: i64,
+}
+
+impl Suggestion {
+    fn user_interface_command(&self) -> UserInterfaceCommand {
+        match self.kind {
+            SuggestionKind::OpenTab(webview_id) => {
+                UserInterfaceCommand::ActivateWebView(webview_id)
+            }
+            SuggestionKind::Bookmark | SuggestionKind::History | SuggestionKind::Search => {
+                UserInterfaceCommand::Go(self.url.clone())
+            }
+        }
+    }
+}
+
+/// The parts of an open tab that are needed for suggestions. [`servo::WebView`]s can't leave
+/// the main thread, so they are copied out before a query is sent to the worker.
+#[derive(Clone)]
+pub(crate) struct OpenTabInfo {
+    pub id: WebViewId,
+    pub title: String,
+    pub url: String,
+}
+
+struct SuggestionQuery {
+    generation: u64,
+    text: String,
+    open_tabs: Vec<OpenTabInfo>,
+}
+
+enum SuggestionRequest {
+    Query(SuggestionQuery),
+    DeleteFromHistory(String),
+}
+
+/// A request for search engine suggestions. Queries without a URL cancel the pending one, as
+/// the text is no longer a search or suggestions are disabled.
+struct SearchSuggestionQuery {
+    generation: u64,
+    suggestions_url: Option<Url>,
+    /// The search results page of the engine, with `%s` standing in for the search terms.
+    search_url: String,
+}
+
+struct SuggestionResults {
+    generation: u64,
+    suggestions: Vec<Suggestion>,
+}
+
+/// The location field and the dropdown of suggestions below it. Suggestions are looked up on a
+/// worker thread with its own database connection, so that typing never waits on the database,
+/// and search engine suggestions on another, so that the local ones never wait on the network.
+pub(crate) struct SearchBar {
+    requests: Sender<SuggestionRequest>,
+    results: Receiver<SuggestionResults>,
+    search_requests: Sender<SearchSuggestionQuery>,
+    search_results: Receiver<SuggestionResults>,
+    /// Incremented for every edit, so that results for older text can be ignored.
+    generation: u64,
+    /// The text as typed by the user, without any inline completion.
+    typed: String,
+    /// Whether the last edit added to the end of the text, which is the only time we complete
+    /// inline. Otherwise deleting the completion would bring it straight back.
+    typed_forwards: bool,
+    /// Suggestions from the open tabs, bookmarks and history.
+    local_suggestions: Vec<Suggestion>,
+    search_suggestions: Vec<Suggestion>,
+    /// The local suggestions followed by the search suggestions, as shown.
+    suggestions: Vec<Suggestion>,
+    selected: Option<usize>,
+    open: bool,
+    /// Where the dropdown was drawn last, so that pointer events over it are kept from the
+    /// `WebView` below.
+    suggestions_rect: Option<egui::Rect>,
+}
+
+impl SearchBar {
+    pub(crate) fn new(event_loop_proxy: EventLoopProxy<AppEvent>, window_id: WindowId) -> Self {
+        let (requests, request_receiver) = unbounded();
+        let (result_sender, results) = unbounded();
+        let local_event_loop_proxy = event_loop_proxy.clone();
+        thread::Builder::new()
+            .name("LocationSuggestions".into())
+            .spawn(move || {
+                run_suggestion_worker(
+                    request_receiver,
+                    result_sender,
+                    local_event_loop_proxy,
+                    window_id,
+                )
+            })
+            .expect("Could not start location suggestion thread");
+
+        let (search_requests, search_request_receiver) = unbounded();
+        let (search_result_sender, search_results) = unbounded();
+        thread::Builder::new()
+            .name("SearchSuggestions".into())
+            .spawn(move || {
+                run_search_suggestion_worker(
+                    search_request_receiver,
+                    search_result_sender,
+                    event_loop_proxy,
+                    window_id,
+                )
+            })
+            .expect("Could not start search suggestion thread");
+
+        Self {
+            requests,
+            results,
+            search_requests,
+            search_results,
+            generation: 0,
+            typed: String::new(),
+            typed_forwards: false,
+            local_suggestions: vec![],
+            search_suggestions: vec![],
+            suggestions: vec![],
+            selected: None,
+            open: false,
+            suggestions_rect: None,
+        }
+    }
+
+    /// Whether the given position, in egui points, is over the suggestions dropdown.
+    pub(crate) fn is_over_suggestions(&self, position: egui::Pos2) -> bool {
+        self.open
+            && self
+                .suggestions_rect
+                .is_some_and(|rect| rect.contains(position))
+    }
+
+    /// Show the location field, followed by the suggestions dropdown if it is open.
+    pub(crate) fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        location: &mut String,
+        location_dirty: &mut bool,
+        state: &RunningAppState,
+        browser_window: &BrowserWindow,
+        event_queue: &mut Vec<UserInterfaceCommand>,
+    ) {
+        let location_id = egui::Id::new("location_input");
+        self.receive_suggestions(ui.ctx(), location_id, location);
+
+        // The dropdown keys have to be taken before the text field handles them.
+        let has_focus = ui.ctx().memory(|memory| memory.has_focus(location_id));
+        let (up, down, escape, delete) = if has_focus && self.open {
+            ui.input_mut(|input| {
+                (
+                    input.consume_key(Modifiers::NONE, Key::ArrowUp),
+                    input.consume_key(Modifiers::NONE, Key::ArrowDown),
+                    input.consume_key(Modifiers::NONE, Key::Escape),
+                    input.consume_key(Modifiers::SHIFT, Key::Delete),
+                )
+            })
+        } else {
+            (false, false, false, false)
+        };
+        if down && !self.suggestions.is_empty() {
+            self.selected = Some(
+                self.selected
+                    .map_or(0, |selected| (selected + 1).min(self.suggestions.len() - 1)),
+            );
+        }
+        if up {
+            self.selected = self.selected.and_then(|selected| selected.checked_sub(1));
+        }
+        if escape {
+            self.close();
+        }
+        if delete && let Some(selected) = self.selected {
+            self.delete_from_history(selected);
+        }
+
+        let location_field = ui.add_sized(
+            Vec2::new(ui.available_width() - 20.0, ui.available_height()),
+            egui::TextEdit::singleline(location)
+                .id(location_id)
+                .hint_text("Search or enter address"),
+        );
+
+        if location_field.changed() {
+            *location_dirty = true;
+            self.location_edited(location, state, browser_window);
+        }
+        // Handle adddress bar shortcut.
+        if ui.input(|i| {
+            if cfg!(target_os = "macos") {
+                i.clone().consume_key(Modifiers::COMMAND, Key::L)
+            } else {
+                i.clone().consume_key(Modifiers::COMMAND, Key::L)
+                    || i.clone().consume_key(Modifiers::ALT, Key::D)
+            }
+        }) {
+            // The focus request immediately makes gained_focus return true.
+            location_field.request_focus();
+        }
+        // Select address bar text when it's focused (click or shortcut).
+        if location_field.gained_focus() {
+            if let Some(mut state) = TextEditState::load(ui.ctx(), location_id) {
+                // Select the whole input.
+                state.cursor.set_char_range(Some(CCursorRange::two(
+                    CCursor::new(0),
+                    CCursor::new(location.len()),
+                )));
+                state.store(ui.ctx(), location_id);
+            }
+        }
+        if location_field.lost_focus() {
+            // Navigate to address when enter is pressed in the address bar.
+            if ui.input(|i| i.clone().key_pressed(Key::Enter)) {
+                match self
+                    .selected
+                    .and_then(|selected| self.suggestions.get(selected))
+                {
+                    Some(suggestion) => event_queue.push(suggestion.user_interface_command()),
+                    None => event_queue.push(UserInterfaceCommand::Go(location.clone())),
+                }
+                self.close();
+            } else if !ui.input(|input| {
+                input
+                    .pointer
+                    .interact_pos()
+                    .is_some_and(|position| self.is_over_suggestions(position))
+            }) {
+                self.close();
+            }
+        }
+
+        self.show_suggestions(ui.ctx(), location_field.rect, event_queue);
+    }
+
+    fn location_edited(
+        &mut self,
+        location: &str,
+        state: &RunningAppState,
+        browser_window: &BrowserWindow,
+    ) {
+        self.typed_forwards =
+            location.len() > self.typed.len() && location.starts_with(&self.typed);
+        self.typed = location.to_owned();
+        self.selected = None;
+        self.generation += 1;
+
+        if location.trim().is_empty() {
+            self.close();
+            return;
+        }
+        self.open = true;
+
+        let open_tabs = browser_window
+            .webviews()
+            .into_iter()
+            .filter_map(|(id, webview)| {
+                Some(OpenTabInfo {
+                    id,
+                    title: webview.page_title().unwrap_or_default(),
+                    url: webview.url()?.to_string(),
+                })
+            })
+            .collect();
+        let query = SuggestionQuery {
+            generation: self.generation,
+            text: location.to_owned(),
+            open_tabs,
+        };
+        if self.requests.send(SuggestionRequest::Query(query)).is_err() {
+            warn!("Location suggestion thread has stopped");
+        }
+
+        // Always send a query, so that one for older text is cancelled even when this text
+        // won't be searched for.
+        let search_engines = state.search_engines();
+        let search = location_bar_input_to_search(location, &search_engines)
+            .filter(|_| state.search_suggestions_enabled());
+        let search_query = SearchSuggestionQuery {
+            generation: self.generation,
+            suggestions_url: search
+                .and_then(|(search_engine, terms)| search_engine.suggestions_url_for(terms)),
+            search_url: search
+                .map(|(search_engine, _)| search_engine.search_url.clone())
+                .unwrap_or_default(),
+        };
+        if search_query.suggestions_url.is_none() && !self.search_suggestions.is_empty() {
+            self.search_suggestions.clear();
+            self.merge_suggestions();
+        }
+        if self.search_requests.send(search_query).is_err() {
+            warn!("Search suggestion thread has stopped");
+        }
+    }
+
+    /// Take the suggestions for the current text from the worker, if they have arrived, and
+    /// complete the top domain inline.
+    fn receive_suggestions(
+        &mut self,
+        ctx: &egui::Context,
+        location_id: egui::Id,
+        location: &mut String,
+    ) {
+        if let Some(results) = self
+            .search_results
+            .try_iter()
+            .filter(|results| results.generation == self.generation)
+            .last()
+        {
+            self.search_suggestions = results.suggestions;
+            self.merge_suggestions();
+        }
+
+        let Some(results) = self
+            .results
+            .try_iter()
+            .filter(|results| results.generation == self.generation)
+            .last()
+        else {
+            return;
+        };
+        self.local_suggestions = results.suggestions;
+        self.merge_suggestions();
+
+        if !self.typed_forwards || *location != self.typed {
+            return;
+        }
+        let Some(completed) = inline_completion(&self.typed, &self.local_suggestions) else {
+            return;
+        };
+        *location = completed;
+        if let Some(mut state) = TextEditState::load(ctx, location_id) {
+            // Select the completed part, so that typing on replaces it.
+            state.cursor.set_char_range(Some(CCursorRange::two(
+                CCursor::new(self.typed.chars().count()),
+                CCursor::new(location.chars().count()),
+            )));
+            state.store(ctx, location_id);
+        }
+    }
+
+    /// Show the search suggestions below the local ones, leaving out searches that are already
+    /// suggested.
+    fn merge_suggestions(&mut self) {
+        self.suggestions = self.local_suggestions.clone();
+        for suggestion in &self.search_suggestions {
+            if !self
+                .suggestions
+                .iter()
+                .any(|existing| existing.url == suggestion.url)
+            {
+                self.suggestions.push(suggestion.clone());
+            }
+        }
+        self.selected = None;
+    }
+
+    fn show_suggestions(
+        &mut self,
+        ctx: &egui::Context,
+        field_rect: egui::Rect,
+        event_queue: &mut Vec<UserInterfaceCommand>,
+    ) {
+        if !self.open || self.suggestions.is_empty() {
+            self.suggestions_rect = None;
+            return;
+        }
+
+        let mut chosen = None;
+        let mut deleted = None;
+        let response = egui::Area::new(egui::Id::new("location_suggestions"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(field_rect.left_bottom())
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(field_rect.width());
+                    for (index, suggestion) in self.suggestions.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let prefix = match suggestion.kind {
+                                SuggestionKind::OpenTab(_) => "Switch to tab: ",
+                                SuggestionKind::Bookmark => "★ ",
+                                SuggestionKind::History => "",
+                                SuggestionKind::Search => "🔍 ",
+                            };
+                            let title = if suggestion.title.is_empty() {
+                                &suggestion.url
+                            } else {
+                                &suggestion.title
+                            };
+                            let row = ui.selectable_label(
+                                self.selected == Some(index),
+                                format!("{prefix}{title}"),
+                            );
+                            ui.label(RichText::new(&suggestion.url).weak().small());
+                            if row.clicked() {
+                                chosen = Some(index);
+                            }
+                            if suggestion.kind == SuggestionKind::History {
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        if ui
+                                            .small_button("✕")
+                                            .on_hover_text("Remove from history")
+                                            .clicked()
+                                        {
+                                            deleted = Some(index);
+                                        }
+                                    },
+                                );
+                            }
+                        });
+                    }
+                });
+            })
+            .response;
+        self.suggestions_rect = Some(response.rect);
+
+        if let Some(index) = deleted {
+            self.delete_from_history(index);
+        } else if let Some(suggestion) = chosen.and_then(|index| self.suggestions.get(index)) {
+            event_queue.push(suggestion.user_interface_command());
+            self.close();
+        }
+    }
+
+    fn delete_from_history(&mut self, index: usize) {
+        if self
+            .suggestions
+            .get(index)
+            .is_none_or(|suggestion| suggestion.kind != SuggestionKind::History)
+        {
+            return;
+        }
+        let suggestion = self.suggestions.remove(index);
+        self.local_suggestions
+            .retain(|local_suggestion| local_suggestion.url != suggestion.url);
+        self.selected = None;
+        if self
+            .requests
+            .send(SuggestionRequest::DeleteFromHistory(suggestion.url))
+            .is_err()
+        {
+            warn!("Location suggestion thread has stopped");
+        }
+    }
+
+    fn close(&mut self) {
+        self.open = false;
+        self.selected = None;
+        self.local_suggestions.clear();
+        self.search_suggestions.clear();
+        self.suggestions.clear();
+        self.suggestions_rect = None;
+        self.typed.clear();
+        // Drop any results that are still on their way.
+        self.generation += 1;
+    }
+}
+
+fn run_suggestion_worker(
+    requests: Receiver<SuggestionRequest>,
+    results: Sender<SuggestionResults>,
+    event_loop_proxy: EventLoopProxy<AppEvent>,
+    window_id: WindowId,
+) {
+    let connection = BrowserDataConnection::new();
+    while let Ok(request) = requests.recv() {
+        // Only the newest query matters, so skip any that were superseded while we were busy.
+        let mut query = None;
+        for request in std::iter::once(request).chain(requests.try_iter()) {
+            match request {
+                SuggestionRequest::Query(newer_query) => query = Some(newer_query),
+                SuggestionRequest::DeleteFromHistory(url) => {
+                    connection.delete_from_browser_history(&url)
+                }
+            }
+        }
+        let Some(query) = query else {
+            continue;
+        };
+
+        let suggestions = find_suggestions(&connection, &query.text, &query.open_tabs);
+        let results_for_window = SuggestionResults {
+            generation: query.generation,
+            suggestions,
+        };
+        if results.send(results_for_window).is_err() {
+            break;
+        }
+        if let Err(error) = event_loop_proxy.send_event(AppEvent::RedrawWindow { window_id }) {
+            warn!("Failed to wake up event loop ({error}).");
+        }
+    }
+}
+
+fn run_search_suggestion_worker(
+    requests: Receiver<SearchSuggestionQuery>,
+    results: Sender<SuggestionResults>,
+    event_loop_proxy: EventLoopProxy<AppEvent>,
+    window_id: WindowId,
+) {
+    while let Ok(mut query) = requests.recv() {
+        // Wait for typing to pause, so that we don't send a request for every key press.
+        loop {
+            match requests.recv_timeout(SEARCH_SUGGESTIONS_DEBOUNCE) {
+                Ok(newer_query) => query = newer_query,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        let Some(suggestions_url) = query.suggestions_url else {
+            continue;
+        };
+
+        let suggestions =
+            match fetch_search_suggestions(&suggestions_url, SEARCH_SUGGESTIONS_TIMEOUT) {
+                Ok(suggestions) => suggestions,
+                Err(error) => {
+                    warn!("Failed to fetch search suggestions from {suggestions_url}: {error}");
+                    continue;
+                }
+            };
+        // The text changed while we were waiting, so these suggestions are out of date.
+        if !requests.is_empty() {
+            continue;
+        }
+
+        let suggestions = suggestions
+            .into_iter()
+            .filter_map(|terms| {
+                let url = Url::parse(&fill_in_search_terms(&query.search_url, &terms)).ok()?;
+                Some(Suggestion {
+                    kind: SuggestionKind::Search,
+                    title: terms,
+                    url: url.into(),
+                    score: 0,
+                })
+            })
+            .take(MAXIMUM_SEARCH_SUGGESTIONS)
+            .collect();
+        let results_for_window = SuggestionResults {
+            generation: query.generation,
+            suggestions,
+        };
+        if results.send(results_for_window).is_err() {
+            break;
+        }
+        if let Err(error) = event_loop_proxy.send_event(AppEvent::RedrawWindow { window_id }) {
+            warn!("Failed to wake up event loop ({error}).");
+        }
+    }
+}
+
+/// Look up and rank the suggestions for `text`.
+fn find_suggestions(
+    connection: &BrowserDataConnection,
+    text: &str,
+    open_tabs: &[OpenTabInfo],
+) -> Vec<Suggestion> {
+    let needle = text.trim().to_lowercase();
+    rank_suggestions(
+        text,
+        open_tabs,
+        connection.search_bookmarks(&needle, MAXIMUM_SUGGESTIONS),
+        connection.search_browser_history(&needle, MAXIMUM_SUGGESTIONS * 2),
+    )
+}
+
+/// Rank the open tabs that match `text` along with the bookmarks and history that were found
+/// for it. Open tabs rank above bookmarks, which rank above history, but a strong match on the
+/// start of the domain beats all of those.
+pub(crate) fn rank_suggestions(
+    text: &str,
+    open_tabs: &[OpenTabInfo],
+    bookmarks: Vec<BookmarkEntry>,
+    history: Vec<VisitedPage>,
+) -> Vec<Suggestion> {
+    let needle = text.trim().to_lowercase();
+    let mut by_url: HashMap<String, Suggestion> = HashMap::new();
+    let mut add = |kind, title: String, url: String, score: i64| {
+        let score = score + match_bonus(&needle, &title, &url);
+        match by_url.get_mut(&url) {
+            // A tab or bookmark that is also in the history ranks a little higher.
+            Some(existing) => existing.score += score / 4,
+            None => {
+                by_url.insert(
+                    url.clone(),
+                    Suggestion {
+                        kind,
+                        title,
+                        url,
+                        score,
+                    },
+                );
+            }
+        }
+    };
+
+    for tab in open_tabs {
+        if tab.title.to_lowercase().contains(&needle) || tab.url.to_lowercase().contains(&needle) {
+            add(
+                SuggestionKind::OpenTab(tab.id),
+                tab.title.clone(),
+                tab.url.clone(),
+                300,
+            );
+        }
+    }
+    for bookmark in bookmarks {
+        add(SuggestionKind::Bookmark, bookmark.title, bookmark.url, 200);
+    }
+    for page in history {
+        let visits = i64::from(page.visit_count.min(50));
+        add(
+            SuggestionKind::History,
+            page.title,
+            page.url,
+            100 + visits * 5,
+        );
+    }
+
+    let mut suggestions: Vec<Suggestion> = by_url.into_values().collect();
+    suggestions.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.url.cmp(&b.url)));
+    suggestions.truncate(MAXIMUM_SUGGESTIONS);
+    suggestions
+}
+
+fn match_bonus(needle: &str, title: &str, url: &str) -> i64 {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    if host.starts_with(needle) {
+        500
+    } else if title
+        .to_lowercase()
+        .split_whitespace()
+        .any(|word| word.starts_with(needle))
+    {
+        100
+    } else {
+        0
+    }
+}
+
+/// The typed text completed to the domain of the best suggestion that starts with it, keeping
+/// the case of the typed part.
+pub(crate) fn inline_completion(typed: &str, suggestions: &[Suggestion]) -> Option<String> {
+    if typed.is_empty()
+        || typed.contains(|character: char| character.is_whitespace() || character == '/')
+    {
+        return None;
+    }
+    let lowercase_typed = typed.to_lowercase();
+    suggestions.iter().find_map(|suggestion| {
+        let url = Url::parse(&suggestion.url).ok()?;
+        let host = url.host_str()?;
+        let host = host.strip_prefix("www.").unwrap_or(host);
+        (host.len() > lowercase_typed.len() && host.starts_with(&lowercase_typed))
+            .then(|| format!("{typed}{}", &host[lowercase_typed.len()..]))
+    })
+}
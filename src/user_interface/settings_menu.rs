@@ -0,0 +1,1419 @@
+use std::path::PathBuf;
+
+use egui::{RichText, WidgetInfo, WidgetType};
+use servo::ServoUrl;
+use url::Url;
+
+use crate::data_storage::redirect_rules::{RedirectRule, RedirectRuleKind};
+use crate::data_storage::search_engines::{SearchEngine, search_engine_for_keyword};
+use crate::data_storage::user_scripts::UserScript;
+use crate::data_storage::user_stylesheets::UserStylesheet;
+use crate::keymap::{BrowserAction, Keymap};
+use crate::misc_utils::ca_certificates::{load_certificates, profile_certificates_dir};
+use crate::misc_utils::content_blocker::FILTER_LISTS_DIR;
+use crate::misc_utils::redirects::{RedirectError, Redirector};
+use crate::misc_utils::user_scripts::UserScriptMetadata;
+use crate::misc_utils::user_stylesheets::parse_url_patterns;
+use crate::prefs::default_config_dir;
+use crate::running_app_state::RunningAppState;
+use crate::url_cleaner::{RuleAction, UrlCleaner};
+
+use super::gui::{AppPage, Gui};
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum SettingsSection {
+    #[default]
+    SearchEngines,
+    KeyboardShortcuts,
+    ContentBlocking,
+    LinkCleaning,
+    Redirects,
+    UserScripts,
+    UserStylesheets,
+    DarkMode,
+    HttpsOnly,
+    CertificateExceptions,
+    CertificateAuthorities,
+}
+
+impl SettingsSection {
+    const ALL: &[SettingsSection] = &[
+        SettingsSection::SearchEngines,
+        SettingsSection::KeyboardShortcuts,
+        SettingsSection::ContentBlocking,
+        SettingsSection::LinkCleaning,
+        SettingsSection::Redirects,
+        SettingsSection::UserScripts,
+        SettingsSection::UserStylesheets,
+        SettingsSection::DarkMode,
+        SettingsSection::HttpsOnly,
+        SettingsSection::CertificateExceptions,
+        SettingsSection::CertificateAuthorities,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SettingsSection::SearchEngines => "Search engines",
+            SettingsSection::KeyboardShortcuts => "Keyboard shortcuts",
+            SettingsSection::ContentBlocking => "Content blocking",
+            SettingsSection::LinkCleaning => "Link cleaning",
+            SettingsSection::Redirects => "Redirects",
+            SettingsSection::UserScripts => "User scripts",
+            SettingsSection::UserStylesheets => "User style sheets",
+            SettingsSection::DarkMode => "Dark mode",
+            SettingsSection::HttpsOnly => "HTTPS-only mode",
+            SettingsSection::CertificateExceptions => "Certificate exceptions",
+            SettingsSection::CertificateAuthorities => "Certificate authorities",
+        }
+    }
+}
+
+/// The state of the settings page that has to outlive a single frame.
+#[derive(Default)]
+pub(crate) struct SettingsPage {
+    section: SettingsSection,
+    new_search_engine: NewSearchEngineForm,
+    new_ca_certificate_path: NewCaCertificatePathForm,
+    redirect_rule: RedirectRuleForm,
+    /// The URL typed into the redirect rule tester.
+    redirect_test_url: String,
+    user_script_editor: UserScriptEditor,
+    user_stylesheet_editor: UserStylesheetEditor,
+}
+
+/// The source of a new user script, to be filled in.
+const NEW_USER_SCRIPT: &str = "// ==UserScript==
+// @name        New script
+// @match       https://example.com/*
+// @run-at      document-idle
+// ==/UserScript==
+
+";
+
+/// A user style sheet being written or edited, which is shown on the open pages as it changes.
+#[derive(Default)]
+struct UserStylesheetEditor {
+    open: bool,
+    /// The style sheet being edited, or `None` if a new one is being written.
+    editing: Option<i32>,
+    name: String,
+    url_patterns: String,
+    css: String,
+    error: Option<String>,
+}
+
+/// A user script being written or edited.
+#[derive(Default)]
+struct UserScriptEditor {
+    open: bool,
+    /// The script being edited, or `None` if a new script is being written.
+    editing: Option<i32>,
+    source: String,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct RedirectRuleForm {
+    /// The rule being edited, or `None` if a new rule is being added.
+    editing: Option<i32>,
+    kind: RedirectRuleKind,
+    pattern: String,
+    replacement: String,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct NewCaCertificatePathForm {
+    path: String,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct NewSearchEngineForm {
+    name: String,
+    keyword: String,
+    search_url: String,
+    icon_url: String,
+    error: Option<String>,
+}
+
+impl NewSearchEngineForm {
+    /// Check the form, returning the search engine it describes.
+    fn validate(&self, existing: &[SearchEngine]) -> Result<SearchEngine, String> {
+        let name = self.name.trim();
+        let keyword = self.keyword.trim();
+        let search_url = self.search_url.trim();
+        let icon_url = self.icon_url.trim();
+        if name.is_empty() {
+            return Err("The search engine needs a name".into());
+        }
+        if !search_url.contains("%s") {
+            return Err("The URL needs a %s where the search terms go".into());
+        }
+        if ServoUrl::parse(&search_url.replace("%s", "test")).is_err() {
+            return Err("The URL is not valid".into());
+        }
+        if keyword.contains(char::is_whitespace) {
+            return Err("Keywords can't contain spaces".into());
+        }
+        if !keyword.is_empty()
+            && let Some(engine) = search_engine_for_keyword(existing, keyword)
+        {
+            return Err(format!("The keyword is already used by {}", engine.name));
+        }
+        Ok(SearchEngine {
+            id: 0,
+            name: name.into(),
+            search_url: search_url.into(),
+            keyword: (!keyword.is_empty()).then(|| keyword.into()),
+            icon_url: (!icon_url.is_empty()).then(|| icon_url.into()),
+            suggestions_url: None,
+            suggestions_enabled: true,
+            is_default: false,
+        })
+    }
+}
+
+pub(crate) fn create_settings_page(
+    ctx: &egui::Context,
+    state: &RunningAppState,
+    current_theme: winit::window::Theme,
+    current_page: &mut AppPage,
+    settings_page: &mut SettingsPage,
+) {
+    egui::SidePanel::left("settings_sections")
+        .resizable(false)
+        .show(ctx, |ui| {
+            let back_button = ui.add(Gui::toolbar_image_button(match current_theme {
+                winit::window::Theme::Dark => {
+                    egui::include_image!("../../resources/icons/back_dark.svg")
+                }
+                winit::window::Theme::Light => {
+                    egui::include_image!("../../resources/icons/back_light.svg")
+                }
+            }));
+            back_button.widget_info(|| {
+                let mut info = WidgetInfo::new(WidgetType::Button);
+                info.label = Some("Back".into());
+                info
+            });
+            if back_button.clicked() {
+                *current_page = AppPage::Main;
+            }
+            ui.heading("Settings");
+            ui.separator();
+            for section in SettingsSection::ALL {
+                ui.selectable_value(&mut settings_page.section, *section, section.label());
+            }
+        });
+
+    egui::CentralPanel::default().show(ctx, |ui| {
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| match settings_page.section {
+                SettingsSection::SearchEngines => {
+                    search_engines_section(ui, state, &mut settings_page.new_search_engine)
+                }
+                SettingsSection::KeyboardShortcuts => keyboard_shortcuts_section(ui, state),
+                SettingsSection::ContentBlocking => content_blocking_section(ui, state),
+                SettingsSection::LinkCleaning => link_cleaning_section(ui, state),
+                SettingsSection::Redirects => redirects_section(
+                    ui,
+                    state,
+                    &mut settings_page.redirect_rule,
+                    &mut settings_page.redirect_test_url,
+                ),
+                SettingsSection::UserScripts => {
+                    user_scripts_section(ui, state, &mut settings_page.user_script_editor)
+                }
+                SettingsSection::UserStylesheets => {
+                    user_stylesheets_section(ui, state, &mut settings_page.user_stylesheet_editor)
+                }
+                SettingsSection::DarkMode => dark_mode_section(ui, state),
+                SettingsSection::HttpsOnly => https_only_section(ui, state),
+                SettingsSection::CertificateExceptions => certificate_exceptions_section(ui, state),
+                SettingsSection::CertificateAuthorities => certificate_authorities_section(
+                    ui,
+                    state,
+                    &mut settings_page.new_ca_certificate_path,
+                ),
+            });
+    });
+}
+
+fn search_engines_section(
+    ui: &mut egui::Ui,
+    state: &RunningAppState,
+    new_search_engine: &mut NewSearchEngineForm,
+) {
+    ui.heading("Search engines");
+    ui.label("Type a keyword followed by a space in the location bar to search with that engine.");
+    let mut suggestions_enabled = state.search_suggestions_enabled();
+    if ui
+        .checkbox(
+            &mut suggestions_enabled,
+            "Show suggestions from the search engine while typing",
+        )
+        .on_hover_text("What you type in the location bar is sent to the search engine")
+        .changed()
+    {
+        state.set_search_suggestions_enabled(suggestions_enabled);
+    }
+    ui.add_space(8.0);
+
+    // Changes are made after the table is drawn, as they need to borrow the engines mutably.
+    let mut new_default = None;
+    let mut removed = None;
+    let mut suggestions_toggled = None;
+    {
+        let search_engines = state.search_engines();
+        let can_remove = search_engines.len() > 1;
+        egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .column(egui_extras::Column::auto().at_least(60.0))
+            .column(egui_extras::Column::auto().at_least(150.0))
+            .column(egui_extras::Column::auto().at_least(80.0))
+            .column(egui_extras::Column::remainder())
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto())
+            .header(20.0, |mut header| {
+                for title in ["Default", "Name", "Keyword", "URL", "Suggestions", ""] {
+                    header.col(|ui| {
+                        ui.label(title);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for search_engine in search_engines.iter() {
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            if ui.radio(search_engine.is_default, "").clicked() {
+                                new_default = Some(search_engine.id);
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(&search_engine.name);
+                        });
+                        row.col(|ui| {
+                            ui.label(
+                                RichText::new(search_engine.keyword.as_deref().unwrap_or(""))
+                                    .monospace(),
+                            );
+                        });
+                        row.col(|ui| {
+                            ui.label(&search_engine.search_url);
+                        });
+                        row.col(|ui| {
+                            let mut enabled = search_engine.suggestions_enabled;
+                            if ui
+                                .add_enabled(
+                                    search_engine.suggestions_url.is_some(),
+                                    egui::Checkbox::without_text(&mut enabled),
+                                )
+                                .changed()
+                            {
+                                suggestions_toggled = Some((search_engine.id, enabled));
+                            }
+                        });
+                        row.col(|ui| {
+                            if ui
+                                .add_enabled(can_remove, egui::Button::new("Remove"))
+                                .clicked()
+                            {
+                                removed = Some(search_engine.id);
+                            }
+                        });
+                    });
+                }
+            });
+    }
+    if let Some(id) = new_default {
+        state.set_default_search_engine(id);
+    }
+    if let Some(id) = removed {
+        state.remove_search_engine(id);
+    }
+    if let Some((id, enabled)) = suggestions_toggled {
+        state.set_search_engine_suggestions_enabled(id, enabled);
+    }
+
+    ui.add_space(16.0);
+    ui.heading("Add a search engine");
+    egui::Grid::new("new_search_engine")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut new_search_engine.name);
+            ui.end_row();
+            ui.label("Keyword");
+            ui.text_edit_singleline(&mut new_search_engine.keyword);
+            ui.end_row();
+            ui.label("URL with %s in place of the search terms");
+            ui.text_edit_singleline(&mut new_search_engine.search_url);
+            ui.end_row();
+            ui.label("Icon URL");
+            ui.text_edit_singleline(&mut new_search_engine.icon_url);
+            ui.end_row();
+        });
+    if let Some(error) = &new_search_engine.error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+    if ui.button("Add").clicked() {
+        let validated = new_search_engine.validate(&state.search_engines());
+        match validated {
+            Ok(search_engine) => {
+                state.add_search_engine(search_engine);
+                *new_search_engine = NewSearchEngineForm::default();
+            }
+            Err(error) => new_search_engine.error = Some(error),
+        }
+    }
+}
+
+fn keyboard_shortcuts_section(ui: &mut egui::Ui, state: &RunningAppState) {
+    ui.heading("Keyboard shortcuts");
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Shortcuts are read from {}",
+            Keymap::keymap_file_path().display()
+        ));
+        if ui.button("Reload").clicked() {
+            state.reload_keymap();
+        }
+    });
+    ui.add_space(8.0);
+
+    let keymap = state.keymap();
+    for error in &keymap.errors {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+    for conflict in keymap.conflicts() {
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            format!(
+                "{} is used for more than one action: {}. Only the first one will run.",
+                conflict.shortcut,
+                conflict.uses.join(", ")
+            ),
+        );
+    }
+    ui.add_space(8.0);
+
+    egui_extras::TableBuilder::new(ui)
+        .striped(true)
+        .column(egui_extras::Column::auto().at_least(200.0))
+        .column(egui_extras::Column::auto().at_least(200.0))
+        .column(egui_extras::Column::auto().at_least(150.0))
+        .column(egui_extras::Column::remainder())
+        .header(20.0, |mut header| {
+            header.col(|ui| {
+                ui.label("Action");
+            });
+            header.col(|ui| {
+                ui.label("Name");
+            });
+            header.col(|ui| {
+                ui.label("Shortcuts");
+            });
+            header.col(|ui| {
+                ui.label("Page can override");
+            });
+        })
+        .body(|mut body| {
+            for action in BrowserAction::ALL {
+                let bindings: Vec<_> = keymap
+                    .bindings()
+                    .iter()
+                    .filter(|binding| binding.action == *action)
+                    .collect();
+                body.row(20.0, |mut row| {
+                    row.col(|ui| {
+                        ui.label(action.description());
+                    });
+                    row.col(|ui| {
+                        ui.label(RichText::new(action.name()).monospace());
+                    });
+                    row.col(|ui| {
+                        let shortcuts = keymap.shortcuts_for(*action);
+                        if shortcuts.is_empty() {
+                            ui.weak("None");
+                        } else {
+                            ui.label(shortcuts.join(", "));
+                        }
+                    });
+                    row.col(|ui| {
+                        if bindings.iter().any(|binding| binding.overridable_by_page) {
+                            ui.label("Yes");
+                        }
+                    });
+                });
+            }
+        });
+}
+
+fn content_blocking_section(ui: &mut egui::Ui, state: &RunningAppState) {
+    ui.heading("Content blocking");
+    let mut enabled = state.content_blocking_enabled();
+    if ui
+        .checkbox(&mut enabled, "Block ads and trackers with filter lists")
+        .changed()
+    {
+        state.set_content_blocking_enabled(enabled);
+    }
+    ui.label(format!(
+        "Filter lists in the Adblock Plus format, such as EasyList, are loaded from the .txt \
+        files in {}. Changes to the lists take effect after restarting the browser. Filters that \
+        only apply to some kinds of requests, such as $script or $image, and regular expression \
+        filters are skipped.",
+        default_config_dir().join(FILTER_LISTS_DIR).display()
+    ));
+    ui.add_space(8.0);
+
+    let lists = state.content_blocker().filter_lists();
+    if lists.is_empty() {
+        ui.weak("No filter lists have been added.");
+    } else {
+        egui_extras::TableBuilder::new(ui)
+            .id_salt("filter_lists")
+            .striped(true)
+            .column(egui_extras::Column::remainder())
+            .column(egui_extras::Column::auto().at_least(120.0))
+            .column(egui_extras::Column::auto().at_least(120.0))
+            .header(20.0, |mut header| {
+                for title in ["List", "Blocking filters", "Hiding filters"] {
+                    header.col(|ui| {
+                        ui.label(title);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for list in lists {
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            ui.label(&list.name);
+                        });
+                        row.col(|ui| {
+                            ui.label(list.network_filters.to_string());
+                        });
+                        row.col(|ui| {
+                            ui.label(list.cosmetic_filters.to_string());
+                        });
+                    });
+                }
+            });
+    }
+
+    ui.add_space(16.0);
+    ui.strong("Sites where content blocking is turned off");
+    let exceptions = state.content_blocking_exceptions();
+    if exceptions.is_empty() {
+        ui.weak("There are no exceptions.");
+        return;
+    }
+
+    let mut removed = None;
+    egui_extras::TableBuilder::new(ui)
+        .id_salt("content_blocking_exceptions")
+        .striped(true)
+        .column(egui_extras::Column::remainder())
+        .column(egui_extras::Column::auto().at_least(150.0))
+        .column(egui_extras::Column::auto())
+        .header(20.0, |mut header| {
+            for title in ["Site", "Added", ""] {
+                header.col(|ui| {
+                    ui.label(title);
+                });
+            }
+        })
+        .body(|mut body| {
+            for exception in &exceptions {
+                body.row(20.0, |mut row| {
+                    row.col(|ui| {
+                        ui.label(&exception.host);
+                    });
+                    row.col(|ui| {
+                        ui.label(exception.time_added.format("%d-%m-%Y %H:%M").to_string());
+                    });
+                    row.col(|ui| {
+                        if ui.button("Remove").clicked() {
+                            removed = Some(exception.host.clone());
+                        }
+                    });
+                });
+            }
+        });
+    if let Some(host) = removed {
+        state.set_content_blocking_enabled_for_host(&host, true);
+    }
+}
+
+fn link_cleaning_section(ui: &mut egui::Ui, state: &RunningAppState) {
+    ui.heading("Link cleaning");
+    ui.label(
+        "Tracking parameters are removed from links before they are loaded, and from links \
+        copied with \"Copy clean link\".",
+    );
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Rules are read from {}",
+            UrlCleaner::rules_file_path().display()
+        ));
+        if ui.button("Reload").clicked() {
+            state.reload_url_cleaner();
+        }
+    });
+    ui.add_space(8.0);
+
+    let url_cleaner = state.url_cleaner();
+    for error in &url_cleaner.errors {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+    egui_extras::TableBuilder::new(ui)
+        .id_salt("url_cleaning_rules")
+        .striped(true)
+        .column(egui_extras::Column::auto().at_least(200.0))
+        .column(egui_extras::Column::auto().at_least(80.0))
+        .column(egui_extras::Column::remainder())
+        .header(20.0, |mut header| {
+            for title in ["Parameter", "Action", "Domain"] {
+                header.col(|ui| {
+                    ui.label(title);
+                });
+            }
+        })
+        .body(|mut body| {
+            for rule in url_cleaner.rules() {
+                body.row(20.0, |mut row| {
+                    row.col(|ui| {
+                        ui.label(RichText::new(&rule.parameter).monospace());
+                    });
+                    row.col(|ui| {
+                        ui.label(match rule.action {
+                            RuleAction::Strip => "Remove",
+                            RuleAction::Keep => "Keep",
+                        });
+                    });
+                    row.col(|ui| {
+                        ui.label(rule.domain.as_deref().unwrap_or("All sites"));
+                    });
+                });
+            }
+        });
+
+    ui.add_space(16.0);
+    ui.strong("Sites whose links are never cleaned");
+    if url_cleaner.exempt_domains().is_empty() {
+        ui.weak("There are no exempt sites.");
+    }
+    for domain in url_cleaner.exempt_domains() {
+        ui.label(domain);
+    }
+}
+
+fn redirects_section(
+    ui: &mut egui::Ui,
+    state: &RunningAppState,
+    form: &mut RedirectRuleForm,
+    test_url: &mut String,
+) {
+    ui.heading("Redirects");
+    ui.label(
+        "Pages that match a rule are loaded from another address instead. The first rule that \
+        matches is used, and the rules are then applied to the new address too.",
+    );
+    ui.add_space(8.0);
+
+    let rules = state.redirect_rules();
+    // Rules are shown and referred to by their position rather than their ID.
+    let rule_number = |id| {
+        rules
+            .iter()
+            .position(|rule| rule.id == id)
+            .map_or(0, |index| index + 1)
+    };
+    let mut toggled = None;
+    let mut removed = None;
+    if rules.is_empty() {
+        ui.weak("No redirect rules have been added.");
+    } else {
+        egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto().at_least(120.0))
+            .column(egui_extras::Column::auto().at_least(200.0))
+            .column(egui_extras::Column::remainder())
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto())
+            .header(20.0, |mut header| {
+                for title in ["#", "Enabled", "Match", "Pattern", "Replacement", "", ""] {
+                    header.col(|ui| {
+                        ui.label(title);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for (index, rule) in rules.iter().enumerate() {
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            ui.label((index + 1).to_string());
+                        });
+                        row.col(|ui| {
+                            let mut enabled = rule.enabled;
+                            if ui.checkbox(&mut enabled, "").changed() {
+                                toggled = Some(RedirectRule {
+                                    enabled,
+                                    ..rule.clone()
+                                });
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(rule.kind.label());
+                        });
+                        row.col(|ui| {
+                            ui.label(RichText::new(&rule.pattern).monospace());
+                        });
+                        row.col(|ui| {
+                            ui.label(RichText::new(&rule.replacement).monospace());
+                        });
+                        row.col(|ui| {
+                            if ui.button("Edit").clicked() {
+                                *form = RedirectRuleForm {
+                                    editing: Some(rule.id),
+                                    kind: rule.kind,
+                                    pattern: rule.pattern.clone(),
+                                    replacement: rule.replacement.clone(),
+                                    error: None,
+                                };
+                            }
+                        });
+                        row.col(|ui| {
+                            if ui.button("Remove").clicked() {
+                                removed = Some(rule.id);
+                            }
+                        });
+                    });
+                }
+            });
+    }
+    if let Some(rule) = toggled {
+        state.update_redirect_rule(&rule);
+    }
+    if let Some(id) = removed {
+        state.remove_redirect_rule(id);
+        if form.editing == Some(id) {
+            *form = Default::default();
+        }
+    }
+
+    ui.add_space(16.0);
+    ui.heading(match form.editing {
+        Some(id) => format!("Edit rule {}", rule_number(id)),
+        None => "Add a rule".into(),
+    });
+    egui::Grid::new("redirect_rule")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Match");
+            egui::ComboBox::from_id_salt("redirect_rule_kind")
+                .selected_text(form.kind.label())
+                .show_ui(ui, |ui| {
+                    for kind in RedirectRuleKind::ALL {
+                        ui.selectable_value(&mut form.kind, *kind, kind.label());
+                    }
+                });
+            ui.end_row();
+            ui.label("Pattern");
+            ui.text_edit_singleline(&mut form.pattern);
+            ui.end_row();
+            ui.label("Replacement");
+            ui.text_edit_singleline(&mut form.replacement);
+            ui.end_row();
+        });
+    ui.weak(match form.kind {
+        RedirectRuleKind::Host => {
+            "A host such as reddit.com, which also matches www.reddit.com, is replaced by \
+            another host such as old.reddit.com."
+        }
+        RedirectRuleKind::Wildcard => {
+            "A whole address in which * matches any text, such as *://go/*. $1, $2 and so on \
+            in the replacement are the text matched by each *, as in \
+            https://intranet.example.com/links/$2."
+        }
+        RedirectRuleKind::Regex => {
+            "A regular expression that matches any part of the address unless it uses ^ and $. \
+            $1 or ${name} in the replacement is the text matched by a group."
+        }
+    });
+    if let Some(error) = &form.error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+    ui.horizontal(|ui| {
+        let label = if form.editing.is_some() {
+            "Save"
+        } else {
+            "Add"
+        };
+        if ui.button(label).clicked() {
+            let enabled = rules
+                .iter()
+                .find(|rule| Some(rule.id) == form.editing)
+                .is_none_or(|rule| rule.enabled);
+            let rule = RedirectRule {
+                id: form.editing.unwrap_or_default(),
+                kind: form.kind,
+                pattern: form.pattern.trim().into(),
+                replacement: form.replacement.trim().into(),
+                enabled,
+            };
+            match Redirector::validate(&rule) {
+                Ok(()) if form.editing.is_some() => {
+                    state.update_redirect_rule(&rule);
+                    *form = Default::default();
+                }
+                Ok(()) => {
+                    state.add_redirect_rule(&rule);
+                    *form = Default::default();
+                }
+                Err(error) => form.error = Some(error),
+            }
+        }
+        if form.editing.is_some() && ui.button("Cancel").clicked() {
+            *form = Default::default();
+        }
+    });
+
+    ui.add_space(16.0);
+    ui.heading("Test the rules");
+    ui.add(egui::TextEdit::singleline(test_url).hint_text("https://example.com/"));
+    let test_url = test_url.trim();
+    if test_url.is_empty() {
+        return;
+    }
+    let Ok(url) = Url::parse(test_url) else {
+        ui.colored_label(ui.visuals().error_fg_color, "This is not a valid URL");
+        return;
+    };
+    match state.redirector().redirect(&url) {
+        Ok(None) => {
+            ui.label("No enabled rule changes this URL.");
+        }
+        Ok(Some(redirect)) => {
+            let rule_numbers: Vec<String> = redirect
+                .rule_ids
+                .iter()
+                .map(|id| rule_number(*id).to_string())
+                .collect();
+            ui.label(format!(
+                "Loaded from {} by rule {}",
+                redirect.url,
+                rule_numbers.join(", then ")
+            ));
+        }
+        Err(error) => {
+            let message = match error {
+                RedirectError::InvalidUrl(id, _) => format!("Rule {}: {error}", rule_number(id)),
+                RedirectError::Loop => error.to_string(),
+            };
+            ui.colored_label(ui.visuals().error_fg_color, message);
+        }
+    }
+}
+
+fn user_scripts_section(ui: &mut egui::Ui, state: &RunningAppState, editor: &mut UserScriptEditor) {
+    ui.heading("User scripts");
+    ui.label(
+        "Scripts run on the pages that their @match and @include lines allow. Open a link to a \
+        .user.js file to install a script.",
+    );
+    ui.add_space(8.0);
+
+    let user_scripts = state.user_scripts();
+    let mut toggled = None;
+    let mut removed = None;
+    if user_scripts.is_empty() {
+        ui.weak("No user scripts have been installed.");
+    } else {
+        egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto().at_least(150.0))
+            .column(egui_extras::Column::auto().at_least(60.0))
+            .column(egui_extras::Column::remainder())
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto())
+            .header(20.0, |mut header| {
+                for title in ["Enabled", "Name", "Version", "Runs on", "Runs at", "", ""] {
+                    header.col(|ui| {
+                        ui.label(title);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for user_script in &user_scripts {
+                    let metadata = UserScriptMetadata::parse(&user_script.source);
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            let mut enabled = user_script.enabled;
+                            if ui.checkbox(&mut enabled, "").changed() {
+                                toggled = Some(UserScript {
+                                    enabled,
+                                    ..user_script.clone()
+                                });
+                            }
+                        });
+                        match &metadata {
+                            Ok(metadata) => {
+                                row.col(|ui| {
+                                    ui.label(&metadata.name);
+                                });
+                                row.col(|ui| {
+                                    ui.label(metadata.version.as_deref().unwrap_or(""));
+                                });
+                                row.col(|ui| {
+                                    if metadata.matches.is_empty() {
+                                        ui.label("Every page");
+                                    } else {
+                                        ui.label(metadata.matches.join(", "));
+                                    }
+                                });
+                                row.col(|ui| {
+                                    ui.label(metadata.run_at.label());
+                                });
+                            }
+                            Err(error) => {
+                                row.col(|ui| {
+                                    ui.colored_label(ui.visuals().error_fg_color, error);
+                                });
+                                row.col(|_| {});
+                                row.col(|_| {});
+                                row.col(|_| {});
+                            }
+                        }
+                        row.col(|ui| {
+                            if ui.button("Edit").clicked() {
+                                *editor = UserScriptEditor {
+                                    open: true,
+                                    editing: Some(user_script.id),
+                                    source: user_script.source.clone(),
+                                    error: None,
+                                };
+                            }
+                        });
+                        row.col(|ui| {
+                            if ui.button("Remove").clicked() {
+                                removed = Some(user_script.id);
+                            }
+                        });
+                    });
+                }
+            });
+    }
+    if let Some(user_script) = toggled {
+        state.update_user_script(&user_script);
+    }
+    if let Some(id) = removed {
+        state.remove_user_script(id);
+        if editor.editing == Some(id) {
+            *editor = Default::default();
+        }
+    }
+
+    ui.add_space(8.0);
+    if !editor.open {
+        if ui.button("New script").clicked() {
+            *editor = UserScriptEditor {
+                open: true,
+                editing: None,
+                source: NEW_USER_SCRIPT.into(),
+                error: None,
+            };
+        }
+        return;
+    }
+
+    ui.add_space(8.0);
+    let edited_script = user_scripts
+        .iter()
+        .find(|user_script| Some(user_script.id) == editor.editing);
+    ui.heading(match edited_script {
+        Some(_) => "Edit script",
+        None => "New script",
+    });
+    if let Some(download_url) =
+        edited_script.and_then(|user_script| user_script.download_url.as_deref())
+    {
+        ui.weak(format!("Installed from {download_url}"));
+    }
+    ui.add(
+        egui::TextEdit::multiline(&mut editor.source)
+            .code_editor()
+            .desired_rows(20)
+            .desired_width(f32::INFINITY),
+    );
+    if let Some(error) = &editor.error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+    ui.horizontal(|ui| {
+        if ui.button("Save").clicked() {
+            match UserScriptMetadata::parse(&editor.source) {
+                Ok(_) => {
+                    match edited_script {
+                        Some(user_script) => state.update_user_script(&UserScript {
+                            source: editor.source.clone(),
+                            ..user_script.clone()
+                        }),
+                        None => state.add_user_script(&UserScript {
+                            id: 0,
+                            source: editor.source.clone(),
+                            enabled: true,
+                            download_url: None,
+                        }),
+                    }
+                    *editor = Default::default();
+                }
+                Err(error) => editor.error = Some(error),
+            }
+        }
+        if ui.button("Cancel").clicked() {
+            *editor = Default::default();
+        }
+    });
+}
+
+fn user_stylesheets_section(
+    ui: &mut egui::Ui,
+    state: &RunningAppState,
+    editor: &mut UserStylesheetEditor,
+) {
+    ui.heading("User style sheets");
+    ui.label(
+        "Style sheets are added to the pages that match their URL patterns, or to every page if \
+        they have none. Changes are shown on open pages straight away.",
+    );
+    ui.add_space(8.0);
+
+    let stylesheets = state.user_stylesheets();
+    let mut toggled = None;
+    let mut removed = None;
+    if stylesheets.is_empty() {
+        ui.weak("No user style sheets have been added.");
+    } else {
+        egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto().at_least(150.0))
+            .column(egui_extras::Column::remainder())
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto())
+            .header(20.0, |mut header| {
+                for title in ["Enabled", "Name", "Pages", "", ""] {
+                    header.col(|ui| {
+                        ui.label(title);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for stylesheet in &stylesheets {
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            let mut enabled = stylesheet.enabled;
+                            if ui.checkbox(&mut enabled, "").changed() {
+                                toggled = Some(UserStylesheet {
+                                    enabled,
+                                    ..stylesheet.clone()
+                                });
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(&stylesheet.name);
+                        });
+                        row.col(|ui| {
+                            let patterns: Vec<&str> = stylesheet
+                                .url_patterns
+                                .lines()
+                                .map(str::trim)
+                                .filter(|pattern| !pattern.is_empty())
+                                .collect();
+                            if patterns.is_empty() {
+                                ui.label("Every page");
+                            } else {
+                                ui.label(RichText::new(patterns.join(", ")).monospace());
+                            }
+                        });
+                        row.col(|ui| {
+                            if ui.button("Edit").clicked() {
+                                *editor = UserStylesheetEditor {
+                                    open: true,
+                                    editing: Some(stylesheet.id),
+                                    name: stylesheet.name.clone(),
+                                    url_patterns: stylesheet.url_patterns.clone(),
+                                    css: stylesheet.css.clone(),
+                                    error: None,
+                                };
+                            }
+                        });
+                        row.col(|ui| {
+                            if ui.button("Remove").clicked() {
+                                removed = Some(stylesheet.id);
+                            }
+                        });
+                    });
+                }
+            });
+    }
+    if let Some(stylesheet) = toggled {
+        state.update_user_stylesheet(&stylesheet);
+    }
+    if let Some(id) = removed {
+        if editor.editing == Some(id) {
+            *editor = Default::default();
+            state.preview_user_stylesheet(None);
+        }
+        state.remove_user_stylesheet(id);
+    }
+
+    ui.add_space(8.0);
+    if !editor.open {
+        if ui.button("New style sheet").clicked() {
+            *editor = UserStylesheetEditor {
+                open: true,
+                ..Default::default()
+            };
+        }
+        return;
+    }
+
+    ui.add_space(8.0);
+    let edited_stylesheet = stylesheets
+        .iter()
+        .find(|stylesheet| Some(stylesheet.id) == editor.editing);
+    ui.heading(match edited_stylesheet {
+        Some(_) => "Edit style sheet",
+        None => "New style sheet",
+    });
+    egui::Grid::new("user_stylesheet")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut editor.name);
+            ui.end_row();
+            ui.label("URL patterns, one per line, such as https://*.example.com/*");
+            ui.text_edit_multiline(&mut editor.url_patterns);
+            ui.end_row();
+        });
+    ui.add(
+        egui::TextEdit::multiline(&mut editor.css)
+            .code_editor()
+            .desired_rows(16)
+            .desired_width(f32::INFINITY)
+            .hint_text("body { font-family: sans-serif; }"),
+    );
+
+    let stylesheet = UserStylesheet {
+        id: editor.editing.unwrap_or_default(),
+        name: editor.name.trim().into(),
+        url_patterns: editor.url_patterns.clone(),
+        css: editor.css.clone(),
+        enabled: edited_stylesheet.is_none_or(|stylesheet| stylesheet.enabled),
+    };
+    let patterns_error = parse_url_patterns(&stylesheet.url_patterns).err();
+    match &patterns_error {
+        Some(error) => {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        None => state.preview_user_stylesheet(Some(stylesheet.clone())),
+    }
+    if let Some(error) = &editor.error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+    ui.horizontal(|ui| {
+        if ui.button("Save").clicked() {
+            if stylesheet.name.is_empty() {
+                editor.error = Some("The style sheet needs a name".into());
+            } else if patterns_error.is_none() {
+                state.preview_user_stylesheet(None);
+                match edited_stylesheet {
+                    Some(_) => state.update_user_stylesheet(&stylesheet),
+                    None => state.add_user_stylesheet(&stylesheet),
+                }
+                *editor = Default::default();
+            }
+        }
+        if ui.button("Cancel").clicked() {
+            state.preview_user_stylesheet(None);
+            *editor = Default::default();
+        }
+    });
+}
+
+fn dark_mode_section(ui: &mut egui::Ui, state: &RunningAppState) {
+    ui.heading("Dark mode");
+    let mut enabled = state.forced_dark_mode_enabled();
+    if ui
+        .checkbox(
+            &mut enabled,
+            "Force dark mode on sites without a dark theme",
+        )
+        .changed()
+    {
+        state.set_forced_dark_mode_enabled(enabled);
+    }
+    ui.label(
+        "Light pages are darkened by inverting their colours, while images and videos are \
+        left as they are. Sites that are dark already, or that follow the system's dark colour \
+        scheme, are not changed. It can be turned off for a site from the moon button in the \
+        toolbar.",
+    );
+
+    ui.add_space(16.0);
+    ui.strong("Sites that are never darkened");
+    let exceptions = state.dark_mode_exceptions();
+    if exceptions.is_empty() {
+        ui.weak("There are no exceptions.");
+        return;
+    }
+
+    let mut removed = None;
+    egui_extras::TableBuilder::new(ui)
+        .id_salt("dark_mode_exceptions")
+        .striped(true)
+        .column(egui_extras::Column::remainder())
+        .column(egui_extras::Column::auto().at_least(150.0))
+        .column(egui_extras::Column::auto())
+        .header(20.0, |mut header| {
+            for title in ["Site", "Added", ""] {
+                header.col(|ui| {
+                    ui.label(title);
+                });
+            }
+        })
+        .body(|mut body| {
+            for exception in &exceptions {
+                body.row(20.0, |mut row| {
+                    row.col(|ui| {
+                        ui.label(&exception.host);
+                    });
+                    row.col(|ui| {
+                        ui.label(exception.time_added.format("%d-%m-%Y %H:%M").to_string());
+                    });
+                    row.col(|ui| {
+                        if ui.button("Remove").clicked() {
+                            removed = Some(exception.host.clone());
+                        }
+                    });
+                });
+            }
+        });
+    if let Some(host) = removed {
+        state.set_forced_dark_mode_enabled_for_host(&host, true);
+    }
+}
+
+fn https_only_section(ui: &mut egui::Ui, state: &RunningAppState) {
+    ui.heading("HTTPS-only mode");
+    let mut enabled = state.https_only_enabled();
+    if ui
+        .checkbox(
+            &mut enabled,
+            "Load sites over HTTPS, even when linked to over HTTP",
+        )
+        .changed()
+    {
+        state.set_https_only_enabled(enabled);
+    }
+    ui.label(
+        "When a site can't be loaded over HTTPS, you can choose to load it over HTTP instead. \
+        These sites are then always loaded over HTTP.",
+    );
+    ui.add_space(8.0);
+
+    let exceptions = state.https_exceptions();
+    if exceptions.is_empty() {
+        ui.weak("There are no exceptions.");
+        return;
+    }
+
+    let mut removed = None;
+    egui_extras::TableBuilder::new(ui)
+        .striped(true)
+        .column(egui_extras::Column::remainder())
+        .column(egui_extras::Column::auto().at_least(150.0))
+        .column(egui_extras::Column::auto())
+        .header(20.0, |mut header| {
+            for title in ["Site", "Added", ""] {
+                header.col(|ui| {
+                    ui.label(title);
+                });
+            }
+        })
+        .body(|mut body| {
+            for exception in &exceptions {
+                body.row(20.0, |mut row| {
+                    row.col(|ui| {
+                        ui.label(&exception.host);
+                    });
+                    row.col(|ui| {
+                        ui.label(exception.time_added.format("%d-%m-%Y %H:%M").to_string());
+                    });
+                    row.col(|ui| {
+                        if ui.button("Remove").clicked() {
+                            removed = Some(exception.id);
+                        }
+                    });
+                });
+            }
+        });
+    if let Some(id) = removed {
+        state.remove_https_exception(id);
+    }
+}
+
+fn certificate_exceptions_section(ui: &mut egui::Ui, state: &RunningAppState) {
+    ui.heading("Certificate exceptions");
+    ui.label(
+        "These sites are trusted with a certificate that couldn't be verified. \
+        Revoking an exception takes effect after restarting the browser.",
+    );
+    ui.add_space(8.0);
+
+    let exceptions = state.certificate_exceptions();
+    if exceptions.is_empty() {
+        ui.weak("There are no exceptions.");
+        return;
+    }
+
+    let mut revoked = None;
+    egui_extras::TableBuilder::new(ui)
+        .striped(true)
+        .column(egui_extras::Column::auto().at_least(200.0))
+        .column(egui_extras::Column::remainder())
+        .column(egui_extras::Column::auto().at_least(150.0))
+        .column(egui_extras::Column::auto())
+        .header(20.0, |mut header| {
+            for title in ["Site", "SHA-256 fingerprint", "Added", ""] {
+                header.col(|ui| {
+                    ui.label(title);
+                });
+            }
+        })
+        .body(|mut body| {
+            for exception in &exceptions {
+                body.row(20.0, |mut row| {
+                    row.col(|ui| {
+                        ui.label(&exception.host);
+                    });
+                    row.col(|ui| {
+                        ui.label(RichText::new(&exception.fingerprint).monospace());
+                    });
+                    row.col(|ui| {
+                        ui.label(exception.time_added.format("%d-%m-%Y %H:%M").to_string());
+                    });
+                    row.col(|ui| {
+                        if ui.button("Revoke").clicked() {
+                            revoked = Some(exception.id);
+                        }
+                    });
+                });
+            }
+        });
+    if let Some(id) = revoked {
+        state.remove_certificate_exception(id);
+    }
+}
+
+fn certificate_authorities_section(
+    ui: &mut egui::Ui,
+    state: &RunningAppState,
+    new_path: &mut NewCaCertificatePathForm,
+) {
+    ui.heading("Certificate authorities");
+    ui.label(
+        "Sites whose certificates are signed by these authorities are trusted, in addition to \
+        the built-in ones. Changes take effect after restarting the browser.",
+    );
+    ui.add_space(8.0);
+
+    let mut system_enabled = state.system_ca_certificates_enabled();
+    if ui
+        .checkbox(
+            &mut system_enabled,
+            "Trust the certificate authorities installed on the system",
+        )
+        .changed()
+    {
+        state.set_system_ca_certificates_enabled(system_enabled);
+    }
+    ui.label(format!(
+        "PEM files in {} are always trusted.",
+        profile_certificates_dir().display()
+    ));
+    ui.add_space(8.0);
+
+    let paths = state.ca_certificate_paths();
+    let mut removed = None;
+    if paths.is_empty() {
+        ui.weak("No certificate files or directories have been added.");
+    } else {
+        egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .column(egui_extras::Column::remainder())
+            .column(egui_extras::Column::auto().at_least(150.0))
+            .column(egui_extras::Column::auto())
+            .header(20.0, |mut header| {
+                for title in ["File or directory", "Certificates", ""] {
+                    header.col(|ui| {
+                        ui.label(title);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for ca_certificate_path in &paths {
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            ui.label(ca_certificate_path.path.display().to_string());
+                        });
+                        row.col(|ui| match load_certificates(&ca_certificate_path.path) {
+                            Ok(certificates) => {
+                                ui.label(certificates.len().to_string());
+                            }
+                            Err(error) => {
+                                ui.colored_label(ui.visuals().error_fg_color, error);
+                            }
+                        });
+                        row.col(|ui| {
+                            if ui.button("Remove").clicked() {
+                                removed = Some(ca_certificate_path.id);
+                            }
+                        });
+                    });
+                }
+            });
+    }
+    if let Some(id) = removed {
+        state.remove_ca_certificate_path(id);
+    }
+
+    ui.add_space(16.0);
+    ui.label("Add a PEM file, or a directory of them such as /etc/ssl/certs:");
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut new_path.path);
+        if ui.button("Add").clicked() {
+            let path = PathBuf::from(new_path.path.trim());
+            match load_certificates(&path) {
+                Ok(certificates) if certificates.is_empty() => {
+                    new_path.error = Some("No certificates found".into());
+                }
+                Ok(_) => {
+                    state.add_ca_certificate_path(&path);
+                    *new_path = Default::default();
+                }
+                Err(error) => new_path.error = Some(error),
+            }
+        }
+    });
+    if let Some(error) = &new_path.error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+}
@@ -0,0 +1,6 @@
+
+// ==UserScript==
+// @name  Everywhere
+// ==/UserScript==
+
+console.log("Hello from " + location.href);
@@ -0,0 +1,17 @@
+// ==UserScript==
+// @name        Wide articles
+// @namespace   https://example.com/scripts
+// @description Lets articles use the whole width of the window.
+// @version     1.2
+// @match       https://*.example.com/articles/*
+// @match       *://news.example.org/*
+// @include     http://legacy.example.net/*
+// @exclude     https://www.example.com/articles/print/*
+// @exclude     /^https://news\.example\.org/(login|account)/
+// @run-at      document-end
+// @grant       none
+// ==/UserScript==
+
+for (const article of document.querySelectorAll("article")) {
+    article.style.maxWidth = "none";
+}